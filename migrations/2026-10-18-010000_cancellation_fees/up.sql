@@ -0,0 +1,9 @@
+create table cancellation_fees
+(
+    apartment_id    integer         not null,
+    hours_before    integer         not null,
+    fee             numeric(6, 2)   not null,
+    constraint cancellation_fees_pk primary key (apartment_id, hours_before),
+    constraint cancellation_fees_apartment_id_fk foreign key (apartment_id) references apartments(id),
+    constraint cancellation_fees_range check (hours_before > 0 and fee >= 0.0)
+);
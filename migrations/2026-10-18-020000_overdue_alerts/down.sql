@@ -0,0 +1 @@
+drop table if exists overdue_alerts;
@@ -0,0 +1,10 @@
+create table overdue_alerts
+(
+    agreement_id                integer     not null,
+    renter_notified_at          timestamptz,
+    staff_notified_at           timestamptz,
+    conflicting_agreement_id    integer,
+    constraint overdue_alerts_pk primary key (agreement_id),
+    constraint overdue_alerts_agreement_id_fk foreign key (agreement_id) references agreements(id),
+    constraint overdue_alerts_conflicting_agreement_id_fk foreign key (conflicting_agreement_id) references agreements(id)
+);
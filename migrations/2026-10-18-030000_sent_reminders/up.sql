@@ -0,0 +1,8 @@
+create table sent_reminders
+(
+    agreement_id    integer         not null,
+    reminder        varchar(16)     not null,
+    sent_at         timestamptz     not null default now(),
+    constraint sent_reminders_pk primary key (agreement_id, reminder),
+    constraint sent_reminders_agreement_id_fk foreign key (agreement_id) references agreements(id)
+);
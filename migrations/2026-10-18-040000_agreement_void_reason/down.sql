@@ -0,0 +1,2 @@
+alter table agreements
+    drop column if exists void_reason;
@@ -0,0 +1,2 @@
+alter table agreements
+    add column void_reason varchar(255);
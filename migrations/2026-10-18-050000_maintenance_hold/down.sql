@@ -0,0 +1,2 @@
+alter table vehicles
+    drop column if exists maintenance_hold;
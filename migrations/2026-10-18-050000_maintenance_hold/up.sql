@@ -0,0 +1,2 @@
+alter table vehicles
+    add column maintenance_hold boolean not null default false;
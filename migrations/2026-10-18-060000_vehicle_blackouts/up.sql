@@ -0,0 +1,15 @@
+create table vehicle_blackouts
+(
+    id          serial,
+    vehicle_id  integer         not null,
+    start_time  timestamptz     not null,
+    end_time    timestamptz     not null,
+    reason      varchar(255)    not null,
+    created_by  integer         not null,
+    constraint vehicle_blackouts_pk primary key (id),
+    constraint vehicle_blackouts_vehicle_id_fk foreign key (vehicle_id) references vehicles(id),
+    constraint vehicle_blackouts_created_by_fk foreign key (created_by) references renters(id),
+    constraint vehicle_blackouts_time_check check (end_time > start_time)
+);
+
+create index vehicle_blackouts_vehicle_id_end_time_idx on vehicle_blackouts (vehicle_id, end_time);
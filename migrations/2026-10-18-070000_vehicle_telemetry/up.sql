@@ -0,0 +1,14 @@
+create table vehicle_telemetry
+(
+    id              serial,
+    vehicle_id      integer             not null,
+    recorded_at     timestamptz         not null default now(),
+    battery_level   integer             not null,
+    odometer        integer             not null,
+    latitude        double precision    not null,
+    longitude       double precision    not null,
+    constraint vehicle_telemetry_pk primary key (id),
+    constraint vehicle_telemetry_vehicle_id_fk foreign key (vehicle_id) references vehicles(id)
+);
+
+create index vehicle_telemetry_vehicle_id_recorded_at_idx on vehicle_telemetry (vehicle_id, recorded_at desc);
@@ -0,0 +1,4 @@
+drop table if exists low_charge_alerts;
+
+alter table apartments
+    drop column if exists min_pickup_charge;
@@ -0,0 +1,21 @@
+alter table apartments
+    add column min_pickup_charge integer not null default 50
+        constraint apartments_min_pickup_charge_check check (min_pickup_charge between 0 and 100);
+
+create table low_charge_alerts
+(
+    agreement_id            integer     not null,
+    vehicle_id              integer     not null,
+    minimum_level           integer     not null,
+    level                   integer     not null,
+    detected_at             timestamptz not null default now(),
+    current_agreement_id    integer,
+    renter_notified_at      timestamptz,
+    staff_notified_at       timestamptz,
+    handed_off_at           timestamptz,
+    handoff_level           integer,
+    constraint low_charge_alerts_pk primary key (agreement_id),
+    constraint low_charge_alerts_agreement_id_fk foreign key (agreement_id) references agreements(id),
+    constraint low_charge_alerts_vehicle_id_fk foreign key (vehicle_id) references vehicles(id),
+    constraint low_charge_alerts_current_agreement_id_fk foreign key (current_agreement_id) references agreements(id)
+);
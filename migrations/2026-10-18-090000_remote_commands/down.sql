@@ -0,0 +1,3 @@
+drop table if exists remote_commands;
+drop type if exists remote_command_status_enum;
+drop type if exists remote_command_enum;
@@ -0,0 +1,24 @@
+create type remote_command_enum as enum ('lock', 'unlock', 'honk');
+create type remote_command_status_enum as enum ('succeeded', 'rejected', 'offline', 'timeout', 'unsupported', 'failed');
+
+create table remote_commands
+(
+    id              serial,
+    vehicle_id      integer                     not null,
+    agreement_id    integer,
+    triggered_by    integer,
+    command         remote_command_enum         not null,
+    attempt         integer                     not null default 1,
+    status          remote_command_status_enum  not null,
+    detail          text,
+    latency_ms      integer                     not null,
+    sent_at         timestamptz                 not null default now(),
+    constraint remote_commands_pk primary key (id),
+    constraint remote_commands_vehicle_id_fk foreign key (vehicle_id) references vehicles(id),
+    constraint remote_commands_agreement_id_fk foreign key (agreement_id) references agreements(id),
+    constraint remote_commands_triggered_by_fk foreign key (triggered_by) references renters(id),
+    constraint remote_commands_attempt_check check (attempt >= 1),
+    constraint remote_commands_latency_ms_check check (latency_ms >= 0)
+);
+
+create index remote_commands_vehicle_id_sent_at_idx on remote_commands (vehicle_id, sent_at);
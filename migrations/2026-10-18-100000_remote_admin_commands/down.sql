@@ -0,0 +1,7 @@
+delete from remote_commands where command in ('wake', 'flash', 'climate_on', 'climate_off');
+
+alter type remote_command_enum rename to remote_command_enum_old;
+create type remote_command_enum as enum ('lock', 'unlock', 'honk');
+alter table remote_commands
+    alter column command type remote_command_enum using command::text::remote_command_enum;
+drop type remote_command_enum_old;
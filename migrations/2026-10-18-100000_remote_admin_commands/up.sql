@@ -0,0 +1,4 @@
+alter type remote_command_enum add value if not exists 'wake';
+alter type remote_command_enum add value if not exists 'flash';
+alter type remote_command_enum add value if not exists 'climate_on';
+alter type remote_command_enum add value if not exists 'climate_off';
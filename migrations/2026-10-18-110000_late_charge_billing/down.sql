@@ -0,0 +1,4 @@
+alter table charges
+    drop column if exists flagged_at;
+alter table charges
+    drop column if exists billing_attempts;
@@ -0,0 +1,4 @@
+alter table charges
+    add column billing_attempts integer not null default 0;
+alter table charges
+    add column flagged_at timestamptz;
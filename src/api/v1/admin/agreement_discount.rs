@@ -0,0 +1,232 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use rust_decimal::prelude::*;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+use crate::{connection_pool, helper_model, methods, model, schema};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("agreement-discount")
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |method: Method, body: helper_model::ManualDiscountRequest, auth: String, user_agent: String| {
+            if method != Method::POST {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            if body.agreement_id <= 0 {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+            if let Some(manual_discount) = body.manual_discount && manual_discount < Decimal::zero() {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id;
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            user_id = match user_id_parsed_result {
+                Ok(int) => int,
+                Err(_) => {
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid =
+                methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                    .await;
+
+            match if_token_valid {
+                Err(err) => {
+                    match err {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreement-discount: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok((_token, token_id)) => {
+                    let admin = methods::user::get_user_by_id(&access_token.user_id)
+                        .await;
+                    let Ok(admin) = admin else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/agreement-discount: Database error loading admin by id"),
+                        );
+                    };
+
+                    if !admin.is_manager() {
+                        return methods::standard_replies::user_not_admin()
+                    }
+                    if !admin.is_operational_manager() {
+                        return methods::standard_replies::admin_not_verified()
+                    }
+
+                    let result = methods::tokens::extend_token(token_id, &user_agent).await;
+                    match result {
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreement-discount: Token extension error"),
+                            )
+                        }
+                        Ok(is_renewed) => {
+                            if !is_renewed {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("admin/agreement-discount: Token extension failed (returned false)"),
+                                )
+                            }
+                        }
+                    }
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    use schema::agreements::dsl as ag_q;
+                    use schema::locations::dsl as l_q;
+                    use schema::renters::dsl as r_q;
+                    let agreement = ag_q::agreements
+                        .find(&body.agreement_id)
+                        .inner_join(l_q::locations)
+                        .inner_join(r_q::renters)
+                        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                        .select((ag_q::agreements::all_columns(), l_q::apartment_id, r_q::stripe_id))
+                        .get_result::<(model::Agreement, i32, String)>(&mut pool);
+
+                    let (mut agreement, apartment_id, stripe_id) = match agreement {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return match e {
+                                Error::NotFound => {
+                                    let msg = helper_model::ErrorResponse {
+                                        title: String::from("Agreement Not Found"),
+                                        message: String::from("Agreement not found or no longer active. "),
+                                    };
+                                    methods::standard_replies::response_with_obj(&msg, StatusCode::NOT_FOUND)
+                                }
+                                _ => {
+                                    methods::standard_replies::internal_server_error_response_500(
+                                        String::from("admin/agreement-discount: Database error loading agreement"),
+                                    )
+                                }
+                            };
+                        }
+                    };
+
+                    if !admin.is_operational_admin() && admin.apartment_id != apartment_id {
+                        return methods::standard_replies::agreement_not_allowed_response()
+                    }
+
+                    let audit_path = format!("admin/agreement-discount/{}", agreement.confirmation);
+                    let manual_discount = body.manual_discount.map(|discount| {
+                        let mut discount_2dp = discount.round_dp(2);
+                        discount_2dp.rescale(2);
+                        discount_2dp
+                    });
+                    agreement.manual_discount = manual_discount;
+                    let discount_note = match manual_discount {
+                        Some(discount) => format!("set manual discount ${}", discount),
+                        None => String::from("removed manual discount"),
+                    };
+
+                    // Open agreements pick the discount up at check-in, closed ones are re-settled now
+                    let is_settled = agreement.actual_drop_off_time.is_some() && agreement.vehicle_snapshot_after.is_some();
+                    if !is_settled {
+                        let agreement = agreement.save_changes::<model::Agreement>(&mut pool);
+                        let Ok(agreement) = agreement else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreement-discount: could not save agreement updates"),
+                            )
+                        };
+                        audit(admin.id, format!("{}: {}", audit_path, discount_note)).await;
+                        let resp = helper_model::ManualDiscountResponse {
+                            agreement,
+                            price: None,
+                            refunded_amount: Decimal::zero(),
+                        };
+                        return methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                    }
+
+                    let price = methods::agreement::settled_price(&agreement).await;
+                    let Ok(price) = price else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/agreement-discount: Database error recalculating agreement total"),
+                        )
+                    };
+
+                    use schema::payments::dsl as pmt_q;
+                    let payments = pmt_q::payments
+                        .filter(pmt_q::agreement_id.eq(agreement.id))
+                        .filter(pmt_q::payment_type.eq(model::PaymentType::Succeeded))
+                        .get_results::<model::Payment>(&mut pool);
+                    let Ok(payments) = payments else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/agreement-discount: Database error loading payments"),
+                        )
+                    };
+                    let paid_amount: Decimal = payments.iter().map(|payment| payment.amount - payment.refund_amount).sum();
+
+                    let mut total_2dp = price.total.round_dp(2);
+                    total_2dp.rescale(2);
+                    // The renter was already charged, a settled total can only go down
+                    if total_2dp > paid_amount {
+                        return methods::standard_replies::bad_request_400("discount cannot increase a settled total. ")
+                    }
+
+                    let agreement = agreement.save_changes::<model::Agreement>(&mut pool);
+                    let Ok(agreement) = agreement else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/agreement-discount: could not save agreement updates"),
+                        )
+                    };
+                    audit(admin.id, format!("{}: {}", audit_path, discount_note)).await;
+
+                    let overpaid_amount = paid_amount - total_2dp;
+                    let refunded_amount = if overpaid_amount >= Decimal::new(50, 2) {
+                        let refunded_amount = methods::agreement::refund_agreement_payments(agreement.id, &stripe_id, Some(overpaid_amount)).await;
+                        let Ok(refunded_amount) = refunded_amount else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreement-discount: Stripe error refunding overpaid amount, discount saved"),
+                            )
+                        };
+                        audit(admin.id, format!("{}: refunded ${}", audit_path, refunded_amount)).await;
+                        refunded_amount
+                    } else {
+                        Decimal::zero()
+                    };
+
+                    let agreement_moved = agreement.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = methods::receipt::email_receipt(&agreement_moved).await {
+                            eprintln!("admin/agreement-discount: could not email receipt for RSVP #{}: {:?}", agreement_moved.confirmation, err);
+                        }
+                    });
+
+                    let resp = helper_model::ManualDiscountResponse {
+                        agreement,
+                        price: Some(price),
+                        refunded_amount,
+                    };
+                    methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                }
+            }
+        })
+}
+
+// A failed audit write is logged, the discount itself is already saved
+async fn audit(admin_id: i32, entry: String) {
+    if methods::audit::record(Some(admin_id), model::AuditActionType::Update, entry.clone()).await.is_err() {
+        eprintln!("admin/agreement-discount: DB error recording audit \"{}\"", entry);
+    }
+}
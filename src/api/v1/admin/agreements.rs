@@ -0,0 +1,329 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rust_decimal::prelude::*;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+use crate::{connection_pool, helper_model, methods, model, schema};
+use crate::helper_model::VeygoError;
+
+// Agreements examined per request when filtering on the settled balance. The settled price
+// is computed by the pricing code, not SQL, so every examined agreement costs a few queries.
+const BALANCE_SCAN_LIMIT: usize = 200;
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Pickup,
+    DropOff,
+    Creation,
+}
+
+impl SortKey {
+    fn value_of(&self, agreement: &model::Agreement) -> DateTime<Utc> {
+        match self {
+            SortKey::Pickup => agreement.rsvp_pickup_time,
+            SortKey::DropOff => agreement.rsvp_drop_off_time,
+            SortKey::Creation => agreement.date_of_creation,
+        }
+    }
+}
+
+// cursor is "<sort value in microseconds>_<agreement id>" of the last agreement returned
+fn encode_cursor(sort_key: SortKey, agreement: &model::Agreement) -> String {
+    format!("{}_{}", sort_key.value_of(agreement).timestamp_micros(), agreement.id)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(DateTime<Utc>, i32)> {
+    let (micros, id) = cursor.split_once('_')?;
+    let time = DateTime::<Utc>::from_timestamp_micros(micros.parse::<i64>().ok()?)?;
+    Some((time, id.parse::<i32>().ok()?))
+}
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("agreements")
+        .and(warp::path::end())
+        .and(warp::query::<helper_model::AdminAgreementSearchQuery>())
+        .and(warp::method())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |query: helper_model::AdminAgreementSearchQuery, method: Method, auth: String, user_agent: String| {
+            if method != Method::GET {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+
+            let sort_key = match query.sort_by.as_deref() {
+                None | Some("rsvp_pickup_time") => SortKey::Pickup,
+                Some("rsvp_drop_off_time") => SortKey::DropOff,
+                Some("date_of_creation") => SortKey::Creation,
+                Some(_) => return methods::standard_replies::bad_request_400("wrong parameters. "),
+            };
+            let ascending = match query.sort_order.as_deref() {
+                None | Some("desc") => false,
+                Some("asc") => true,
+                Some(_) => return methods::standard_replies::bad_request_400("wrong parameters. "),
+            };
+            let mut cursor = match query.cursor.as_deref() {
+                None => None,
+                Some(cursor) => match decode_cursor(cursor) {
+                    Some(cursor) => Some(cursor),
+                    None => return methods::standard_replies::bad_request_400("wrong parameters. "),
+                },
+            };
+            let pickup_from = match query.pickup_from {
+                None => None,
+                Some(secs) => match DateTime::<Utc>::from_timestamp(secs, 0) {
+                    Some(time) => Some(time),
+                    None => return methods::standard_replies::bad_request_400("wrong parameters. "),
+                },
+            };
+            let pickup_to = match query.pickup_to {
+                None => None,
+                Some(secs) => match DateTime::<Utc>::from_timestamp(secs, 0) {
+                    Some(time) => Some(time),
+                    None => return methods::standard_replies::bad_request_400("wrong parameters. "),
+                },
+            };
+            let page_size = query.limit.unwrap_or(50).clamp(1, 100);
+
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id;
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            user_id = match user_id_parsed_result {
+                Ok(int) => int,
+                Err(_) => {
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid =
+                methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                    .await;
+
+            match if_token_valid {
+                Err(err) => {
+                    match err {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreements: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok((_token, token_id)) => {
+                    let admin = methods::user::get_user_by_id(&access_token.user_id)
+                        .await;
+                    let Ok(admin) = admin else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/agreements: Database error loading admin by id"),
+                        );
+                    };
+
+                    if !admin.is_manager() {
+                        return methods::standard_replies::user_not_admin()
+                    }
+                    if !admin.is_operational_manager() {
+                        return methods::standard_replies::admin_not_verified()
+                    }
+
+                    let result = methods::tokens::extend_token(token_id, &user_agent).await;
+                    match result {
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreements: Token extension error"),
+                            )
+                        }
+                        Ok(is_renewed) => {
+                            if !is_renewed {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("admin/agreements: Token extension failed (returned false)"),
+                                )
+                            }
+                        }
+                    }
+
+                    // Managers only see their own apartment
+                    let scoped_apartment_id = if admin.is_operational_admin() {
+                        None
+                    } else {
+                        Some(admin.apartment_id)
+                    };
+
+                    let mut pool = connection_pool().await.get().unwrap();
+                    let now = Utc::now();
+
+                    let mut agreements: Vec<model::Agreement> = Vec::new();
+                    let mut next_cursor: Option<String> = None;
+                    let mut scanned: usize = 0;
+                    let mut truncated = false;
+
+                    use schema::agreements::dsl as ag_q;
+                    use schema::locations::dsl as l_q;
+                    'pages: loop {
+                        let mut search = ag_q::agreements
+                            .inner_join(l_q::locations)
+                            .select(ag_q::agreements::all_columns())
+                            .into_boxed();
+
+                        if let Some(apartment_id) = scoped_apartment_id {
+                            search = search.filter(l_q::apartment_id.eq(apartment_id));
+                        }
+                        if let Some(apartment_id) = query.apartment_id {
+                            search = search.filter(l_q::apartment_id.eq(apartment_id));
+                        }
+                        if let Some(location_id) = query.location_id {
+                            search = search.filter(ag_q::location_id.eq(location_id));
+                        }
+                        if let Some(vehicle_id) = query.vehicle_id {
+                            search = search.filter(ag_q::vehicle_id.eq(vehicle_id));
+                        }
+                        if let Some(renter_id) = query.renter_id {
+                            search = search.filter(ag_q::renter_id.eq(renter_id));
+                        }
+                        if let Some(status) = query.status {
+                            search = search.filter(ag_q::status.eq(status));
+                        }
+                        if let Some(pickup_from) = pickup_from {
+                            search = search.filter(ag_q::rsvp_pickup_time.ge(pickup_from));
+                        }
+                        if let Some(pickup_to) = pickup_to {
+                            search = search.filter(ag_q::rsvp_pickup_time.lt(pickup_to));
+                        }
+                        match query.checked_out {
+                            Some(true) => {
+                                search = search
+                                    .filter(ag_q::actual_pickup_time.is_not_null())
+                                    .filter(ag_q::actual_drop_off_time.is_null());
+                            }
+                            Some(false) => {
+                                search = search.filter(
+                                    ag_q::actual_pickup_time.is_null()
+                                        .or(ag_q::actual_drop_off_time.is_not_null())
+                                );
+                            }
+                            None => {}
+                        }
+                        match query.overdue {
+                            Some(true) => {
+                                search = search
+                                    .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                                    .filter(ag_q::actual_pickup_time.is_not_null())
+                                    .filter(ag_q::actual_drop_off_time.is_null())
+                                    .filter(ag_q::rsvp_drop_off_time.lt(now));
+                            }
+                            Some(false) => {
+                                search = search.filter(
+                                    ag_q::status.ne(model::AgreementStatus::Rental)
+                                        .or(ag_q::actual_pickup_time.is_null())
+                                        .or(ag_q::actual_drop_off_time.is_not_null())
+                                        .or(ag_q::rsvp_drop_off_time.ge(now))
+                                );
+                            }
+                            None => {}
+                        }
+                        if query.balance_due.is_some() {
+                            search = search
+                                .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                                .filter(ag_q::actual_drop_off_time.is_not_null())
+                                .filter(ag_q::vehicle_snapshot_after.is_not_null());
+                        }
+
+                        // keyset pagination on (sort value, id)
+                        search = match (sort_key, ascending, cursor) {
+                            (SortKey::Pickup, true, Some((value, id))) => search.filter(
+                                ag_q::rsvp_pickup_time.gt(value).or(ag_q::rsvp_pickup_time.eq(value).and(ag_q::id.gt(id)))
+                            ),
+                            (SortKey::Pickup, false, Some((value, id))) => search.filter(
+                                ag_q::rsvp_pickup_time.lt(value).or(ag_q::rsvp_pickup_time.eq(value).and(ag_q::id.lt(id)))
+                            ),
+                            (SortKey::DropOff, true, Some((value, id))) => search.filter(
+                                ag_q::rsvp_drop_off_time.gt(value).or(ag_q::rsvp_drop_off_time.eq(value).and(ag_q::id.gt(id)))
+                            ),
+                            (SortKey::DropOff, false, Some((value, id))) => search.filter(
+                                ag_q::rsvp_drop_off_time.lt(value).or(ag_q::rsvp_drop_off_time.eq(value).and(ag_q::id.lt(id)))
+                            ),
+                            (SortKey::Creation, true, Some((value, id))) => search.filter(
+                                ag_q::date_of_creation.gt(value).or(ag_q::date_of_creation.eq(value).and(ag_q::id.gt(id)))
+                            ),
+                            (SortKey::Creation, false, Some((value, id))) => search.filter(
+                                ag_q::date_of_creation.lt(value).or(ag_q::date_of_creation.eq(value).and(ag_q::id.lt(id)))
+                            ),
+                            (_, _, None) => search,
+                        };
+                        search = match (sort_key, ascending) {
+                            (SortKey::Pickup, true) => search.order((ag_q::rsvp_pickup_time.asc(), ag_q::id.asc())),
+                            (SortKey::Pickup, false) => search.order((ag_q::rsvp_pickup_time.desc(), ag_q::id.desc())),
+                            (SortKey::DropOff, true) => search.order((ag_q::rsvp_drop_off_time.asc(), ag_q::id.asc())),
+                            (SortKey::DropOff, false) => search.order((ag_q::rsvp_drop_off_time.desc(), ag_q::id.desc())),
+                            (SortKey::Creation, true) => search.order((ag_q::date_of_creation.asc(), ag_q::id.asc())),
+                            (SortKey::Creation, false) => search.order((ag_q::date_of_creation.desc(), ag_q::id.desc())),
+                        };
+
+                        let batch = search
+                            .limit(page_size)
+                            .get_results::<model::Agreement>(&mut pool);
+                        let Ok(batch) = batch else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/agreements: Database error loading agreements"),
+                            )
+                        };
+                        let batch_len = batch.len();
+
+                        for agreement in batch {
+                            cursor = Some((sort_key.value_of(&agreement), agreement.id));
+                            scanned += 1;
+
+                            let matches = match query.balance_due {
+                                None => true,
+                                Some(wanted) => {
+                                    let balance = methods::agreement::balance_due(&agreement).await;
+                                    let Ok(balance) = balance else {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("admin/agreements: Database error calculating balance"),
+                                        )
+                                    };
+                                    (balance > Decimal::zero()) == wanted
+                                }
+                            };
+                            if matches {
+                                agreements.push(agreement);
+                            }
+
+                            if agreements.len() as i64 == page_size {
+                                next_cursor = agreements.last().map(|ag| encode_cursor(sort_key, ag));
+                                break 'pages;
+                            }
+                        }
+
+                        if (batch_len as i64) < page_size {
+                            // no more agreements
+                            break;
+                        }
+                        if scanned >= BALANCE_SCAN_LIMIT {
+                            // hand the scan position back so the client can keep going
+                            truncated = true;
+                            next_cursor = cursor.map(|(value, id)| format!("{}_{}", value.timestamp_micros(), id));
+                            break;
+                        }
+                    }
+
+                    let resp = helper_model::AdminAgreementSearchResponse {
+                        agreements,
+                        next_cursor,
+                        truncated,
+                    };
+                    methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                }
+            }
+        })
+}
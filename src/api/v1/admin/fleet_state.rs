@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+use crate::{connection_pool, helper_model, methods, model, schema};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("fleet-state")
+        .and(warp::path::end())
+        .and(warp::query::<helper_model::FleetStateQuery>())
+        .and(warp::method())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |query: helper_model::FleetStateQuery, method: Method, auth: String, user_agent: String| {
+            if method != Method::GET {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            if query.apartment_id.is_some_and(|apartment_id| apartment_id <= 0) {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id;
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            user_id = match user_id_parsed_result {
+                Ok(int) => int,
+                Err(_) => {
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid =
+                methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                    .await;
+
+            match if_token_valid {
+                Err(err) => {
+                    match err {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/fleet-state: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok((_token, token_id)) => {
+                    let admin = methods::user::get_user_by_id(&access_token.user_id)
+                        .await;
+                    let Ok(admin) = admin else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/fleet-state: Database error loading admin by id"),
+                        );
+                    };
+
+                    if !admin.is_manager() {
+                        return methods::standard_replies::user_not_admin()
+                    }
+                    if !admin.is_operational_manager() {
+                        return methods::standard_replies::admin_not_verified()
+                    }
+
+                    let result = methods::tokens::extend_token(token_id, &user_agent).await;
+                    match result {
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/fleet-state: Token extension error"),
+                            )
+                        }
+                        Ok(is_renewed) => {
+                            if !is_renewed {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("admin/fleet-state: Token extension failed (returned false)"),
+                                )
+                            }
+                        }
+                    }
+
+                    // Managers only see their own apartment
+                    let scoped_apartment_id = if admin.is_operational_admin() {
+                        None
+                    } else {
+                        Some(admin.apartment_id)
+                    };
+
+                    let apartment_id = match scoped_apartment_id {
+                        Some(own_apartment_id) => {
+                            if let Some(requested_apartment_id) = query.apartment_id && requested_apartment_id != own_apartment_id {
+                                return methods::standard_replies::apartment_not_allowed_response(requested_apartment_id)
+                            }
+                            Some(own_apartment_id)
+                        }
+                        None => query.apartment_id,
+                    };
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    use schema::vehicles::dsl as v_q;
+                    use schema::locations::dsl as l_q;
+                    let mut fleet = v_q::vehicles
+                        .inner_join(l_q::locations)
+                        .filter(v_q::remote_mgmt.eq(model::RemoteMgmtType::Tesla))
+                        .select(v_q::vehicles::all_columns())
+                        .order(v_q::id)
+                        .into_boxed();
+                    if let Some(apartment_id) = apartment_id {
+                        fleet = fleet.filter(l_q::apartment_id.eq(apartment_id));
+                    }
+                    let vehicles = fleet.get_results::<model::Vehicle>(&mut pool);
+                    let Ok(vehicles) = vehicles else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/fleet-state: Database error loading vehicles"),
+                        )
+                    };
+
+                    // newest reading per vehicle, as stored by the telemetry poller
+                    use schema::vehicle_telemetry::dsl as vt_q;
+                    let vehicle_ids = vehicles.iter().map(|vehicle| vehicle.id).collect::<Vec<i32>>();
+                    let latest = vt_q::vehicle_telemetry
+                        .filter(vt_q::vehicle_id.eq_any(&vehicle_ids))
+                        .distinct_on(vt_q::vehicle_id)
+                        .order((vt_q::vehicle_id, vt_q::recorded_at.desc()))
+                        .get_results::<model::VehicleTelemetry>(&mut pool);
+                    let Ok(latest) = latest else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/fleet-state: Database error loading telemetry"),
+                        )
+                    };
+                    let mut latest_by_vehicle = latest
+                        .into_iter()
+                        .map(|telemetry| (telemetry.vehicle_id, telemetry))
+                        .collect::<HashMap<i32, model::VehicleTelemetry>>();
+
+                    let resp = vehicles
+                        .into_iter()
+                        .map(|vehicle| helper_model::FleetStateEntry {
+                            telemetry: latest_by_vehicle.remove(&vehicle.id),
+                            vehicle: vehicle.into(),
+                        })
+                        .collect::<Vec<_>>();
+                    methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                }
+            }
+        })
+}
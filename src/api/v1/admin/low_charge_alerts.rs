@@ -0,0 +1,128 @@
+use diesel::prelude::*;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+use crate::{connection_pool, helper_model, methods, model, schema};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("low-charge-alerts")
+        .and(warp::path::end())
+        .and(warp::query::<helper_model::LowChargeAlertQuery>())
+        .and(warp::method())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |query: helper_model::LowChargeAlertQuery, method: Method, auth: String, user_agent: String| {
+            if method != Method::GET {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            if query.agreement_id.is_some_and(|agreement_id| agreement_id <= 0)
+                || query.vehicle_id.is_some_and(|vehicle_id| vehicle_id <= 0) {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id;
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            user_id = match user_id_parsed_result {
+                Ok(int) => int,
+                Err(_) => {
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid =
+                methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                    .await;
+
+            match if_token_valid {
+                Err(err) => {
+                    match err {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/low-charge-alerts: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok((_token, token_id)) => {
+                    let admin = methods::user::get_user_by_id(&access_token.user_id)
+                        .await;
+                    let Ok(admin) = admin else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/low-charge-alerts: Database error loading admin by id"),
+                        );
+                    };
+
+                    if !admin.is_manager() {
+                        return methods::standard_replies::user_not_admin()
+                    }
+                    if !admin.is_operational_manager() {
+                        return methods::standard_replies::admin_not_verified()
+                    }
+
+                    let result = methods::tokens::extend_token(token_id, &user_agent).await;
+                    match result {
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/low-charge-alerts: Token extension error"),
+                            )
+                        }
+                        Ok(is_renewed) => {
+                            if !is_renewed {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("admin/low-charge-alerts: Token extension failed (returned false)"),
+                                )
+                            }
+                        }
+                    }
+
+                    // Managers only see their own apartment
+                    let scoped_apartment_id = if admin.is_operational_admin() {
+                        None
+                    } else {
+                        Some(admin.apartment_id)
+                    };
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    use schema::low_charge_alerts::dsl as lca_q;
+                    use schema::agreements::dsl as ag_q;
+                    use schema::locations::dsl as l_q;
+                    let mut alerts = lca_q::low_charge_alerts
+                        .inner_join(ag_q::agreements.inner_join(l_q::locations))
+                        .select(lca_q::low_charge_alerts::all_columns())
+                        .order(lca_q::detected_at.desc())
+                        .limit(100)
+                        .into_boxed();
+                    if let Some(apartment_id) = scoped_apartment_id {
+                        alerts = alerts.filter(l_q::apartment_id.eq(apartment_id));
+                    }
+                    if let Some(agreement_id) = query.agreement_id {
+                        alerts = alerts.filter(lca_q::agreement_id.eq(agreement_id));
+                    }
+                    if let Some(vehicle_id) = query.vehicle_id {
+                        alerts = alerts.filter(lca_q::vehicle_id.eq(vehicle_id));
+                    }
+                    let alerts = alerts.get_results::<model::LowChargeAlert>(&mut pool);
+                    let Ok(alerts) = alerts else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/low-charge-alerts: Database error loading alerts"),
+                        )
+                    };
+                    methods::standard_replies::response_with_obj(alerts, StatusCode::OK)
+                }
+            }
+        })
+}
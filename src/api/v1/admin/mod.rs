@@ -6,6 +6,12 @@ mod verify_dl;
 mod renter_need_verify;
 mod verify_lease;
 mod verify_ins;
+mod void_agreement;
+mod agreement_discount;
+mod agreements;
+mod fleet_state;
+mod low_charge_alerts;
+mod remote_command;
 
 use warp::Filter;
 
@@ -19,6 +25,12 @@ pub fn api_v1_admin() -> impl Filter<Extract = (impl warp::Reply,), Error = warp
         .or(verify_dl::main())
         .or(verify_lease::main())
         .or(verify_ins::main())
+        .or(void_agreement::main())
+        .or(agreement_discount::main())
+        .or(agreements::main())
+        .or(fleet_state::main())
+        .or(low_charge_alerts::main())
+        .or(remote_command::main())
         .boxed();
 
     warp::path("admin")
@@ -0,0 +1,167 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+use crate::{connection_pool, helper_model, methods, model, schema};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("remote-command")
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |method: Method, body: helper_model::AdminRemoteCommandRequest, auth: String, user_agent: String| {
+            if method != Method::POST {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            if body.vehicle_id <= 0 {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id;
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            user_id = match user_id_parsed_result {
+                Ok(int) => int,
+                Err(_) => {
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid =
+                methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                    .await;
+
+            match if_token_valid {
+                Err(err) => {
+                    match err {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/remote-command: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok((_token, token_id)) => {
+                    let admin = methods::user::get_user_by_id(&access_token.user_id)
+                        .await;
+                    let Ok(admin) = admin else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/remote-command: Database error loading admin by id"),
+                        );
+                    };
+
+                    if !admin.is_manager() {
+                        return methods::standard_replies::user_not_admin()
+                    }
+                    if !admin.is_operational_manager() {
+                        return methods::standard_replies::admin_not_verified()
+                    }
+
+                    let result = methods::tokens::extend_token(token_id, &user_agent).await;
+                    match result {
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/remote-command: Token extension error"),
+                            )
+                        }
+                        Ok(is_renewed) => {
+                            if !is_renewed {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("admin/remote-command: Token extension failed (returned false)"),
+                                )
+                            }
+                        }
+                    }
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    use schema::vehicles::dsl as v_q;
+                    use schema::locations::dsl as l_q;
+                    let vehicle = v_q::vehicles
+                        .find(&body.vehicle_id)
+                        .inner_join(l_q::locations)
+                        .select((v_q::vehicles::all_columns(), l_q::apartment_id))
+                        .get_result::<(model::Vehicle, i32)>(&mut pool);
+
+                    let (vehicle, apartment_id) = match vehicle {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return match e {
+                                Error::NotFound => {
+                                    let msg = helper_model::ErrorResponse {
+                                        title: String::from("Vehicle Not Found"),
+                                        message: String::from("Vehicle not found. "),
+                                    };
+                                    methods::standard_replies::response_with_obj(&msg, StatusCode::NOT_FOUND)
+                                }
+                                _ => {
+                                    methods::standard_replies::internal_server_error_response_500(
+                                        String::from("admin/remote-command: Database error loading vehicle"),
+                                    )
+                                }
+                            };
+                        }
+                    };
+
+                    // Managers can only reach vehicles at their own apartment
+                    if !admin.is_operational_admin() && admin.apartment_id != apartment_id {
+                        return methods::standard_replies::apartment_not_allowed_response(apartment_id)
+                    }
+
+                    let audit_path = format!("admin/remote-command/{}", vehicle.id);
+
+                    if methods::remote_command::requires_admin_pin(body.command)
+                        && !methods::remote_command::admin_pin_matches(vehicle.admin_pin.as_deref(), body.admin_pin.as_deref())
+                    {
+                        let _ = methods::audit::record(
+                            Some(admin.id), model::AuditActionType::Update, format!("{}: {:?} refused, admin pin did not match", audit_path, body.command)
+                        ).await;
+                        let msg = helper_model::ErrorResponse {
+                            title: String::from("Admin PIN Required"),
+                            message: String::from("The admin PIN is missing or does not match this vehicle. "),
+                        };
+                        return methods::standard_replies::response_with_obj(&msg, StatusCode::FORBIDDEN)
+                    }
+
+                    let remote = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id);
+                    let Ok(remote) = remote else {
+                        return methods::standard_replies::bad_request_400("vehicle has no remote management. ")
+                    };
+
+                    // Console commands are outside any rental, the admin is the origin
+                    let origin = methods::remote_command::CommandOrigin {
+                        vehicle_id: vehicle.id,
+                        agreement_id: None,
+                        triggered_by: Some(admin.id),
+                    };
+                    let result = methods::remote_command::send_logged(remote.as_ref(), body.command, origin, 1).await;
+                    let (status, detail) = methods::remote_command::command_status(&result);
+                    let _ = methods::audit::record(
+                        Some(admin.id), model::AuditActionType::Update, format!("{}: {:?} {:?}", audit_path, body.command, status)
+                    ).await;
+
+                    let resp = helper_model::AdminRemoteCommandResponse {
+                        vehicle_id: vehicle.id,
+                        command: body.command,
+                        status,
+                        detail,
+                    };
+                    methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                }
+            }
+        })
+}
@@ -0,0 +1,224 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use rust_decimal::prelude::*;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+use crate::{connection_pool, helper_model, methods, model, schema};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("void-agreement")
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |method: Method, body: helper_model::VoidAgreementRequest, auth: String, user_agent: String| {
+            if method != Method::POST {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            if body.agreement_id <= 0 || body.reason.trim().is_empty() || body.reason.len() > 255 {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+            if let Some(refund_amount) = body.refund_amount && refund_amount < Decimal::zero() {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id;
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            user_id = match user_id_parsed_result {
+                Ok(int) => int,
+                Err(_) => {
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid =
+                methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                    .await;
+
+            match if_token_valid {
+                Err(err) => {
+                    match err {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/void-agreement: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok((_token, token_id)) => {
+                    let admin = methods::user::get_user_by_id(&access_token.user_id)
+                        .await;
+                    let Ok(admin) = admin else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/void-agreement: Database error loading admin by id"),
+                        );
+                    };
+
+                    if !admin.is_manager() {
+                        return methods::standard_replies::user_not_admin()
+                    }
+                    if !admin.is_operational_manager() {
+                        return methods::standard_replies::admin_not_verified()
+                    }
+
+                    let result = methods::tokens::extend_token(token_id, &user_agent).await;
+                    match result {
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/void-agreement: Token extension error"),
+                            )
+                        }
+                        Ok(is_renewed) => {
+                            if !is_renewed {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("admin/void-agreement: Token extension failed (returned false)"),
+                                )
+                            }
+                        }
+                    }
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    use schema::agreements::dsl as ag_q;
+                    use schema::locations::dsl as l_q;
+                    use schema::renters::dsl as r_q;
+                    let agreement = ag_q::agreements
+                        .find(&body.agreement_id)
+                        .inner_join(l_q::locations)
+                        .inner_join(r_q::renters)
+                        .filter(ag_q::status.ne(model::AgreementStatus::Void))
+                        .select((ag_q::agreements::all_columns(), l_q::apartment_id, r_q::stripe_id))
+                        .get_result::<(model::Agreement, i32, String)>(&mut pool);
+
+                    let (agreement, apartment_id, stripe_id) = match agreement {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return match e {
+                                Error::NotFound => {
+                                    let msg = helper_model::ErrorResponse {
+                                        title: String::from("Agreement Not Found"),
+                                        message: String::from("Agreement not found or already voided. "),
+                                    };
+                                    methods::standard_replies::response_with_obj(&msg, StatusCode::NOT_FOUND)
+                                }
+                                _ => {
+                                    methods::standard_replies::internal_server_error_response_500(
+                                        String::from("admin/void-agreement: Database error loading agreement"),
+                                    )
+                                }
+                            };
+                        }
+                    };
+
+                    // Managers can only void bookings at their own apartment
+                    if !admin.is_operational_admin() && admin.apartment_id != apartment_id {
+                        return methods::standard_replies::agreement_not_allowed_response()
+                    }
+
+                    let audit_path = format!("admin/void-agreement/{}", agreement.confirmation);
+
+                    // Void the booking before any money moves, a concurrent void finds nothing left to do
+                    let claimed = diesel::update(
+                        ag_q::agreements
+                            .find(agreement.id)
+                            .filter(ag_q::status.ne(model::AgreementStatus::Void))
+                    )
+                        .set((
+                            ag_q::status.eq(model::AgreementStatus::Void),
+                            ag_q::void_reason.eq(Some(body.reason.trim().to_string())),
+                        ))
+                        .get_result::<model::Agreement>(&mut pool);
+                    let voided = match claimed {
+                        Ok(voided) => voided,
+                        Err(Error::NotFound) => {
+                            let msg = helper_model::ErrorResponse {
+                                title: String::from("Agreement Not Found"),
+                                message: String::from("Agreement not found or already voided. "),
+                            };
+                            return methods::standard_replies::response_with_obj(&msg, StatusCode::NOT_FOUND)
+                        }
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/void-agreement: could not save agreement updates"),
+                            )
+                        }
+                    };
+                    audit(admin.id, format!("{}: voided", audit_path)).await;
+
+                    // Nothing could be returned, put the booking back so the void can be retried
+                    let restore = async |pool: &mut diesel::PgConnection| {
+                        let restored = diesel::update(ag_q::agreements.find(agreement.id))
+                            .set((ag_q::status.eq(agreement.status), ag_q::void_reason.eq(agreement.void_reason.clone())))
+                            .execute(pool);
+                        if restored.is_err() {
+                            eprintln!("admin/void-agreement: DB error restoring RSVP #{} after a failed void", agreement.confirmation);
+                        }
+                        audit(admin.id, format!("{}: void undone", audit_path)).await;
+                    };
+
+                    // Release every hold that was never captured, the deposit included
+                    let released_holds = methods::agreement::release_auth_holds(agreement.id).await;
+                    let Ok(released_holds) = released_holds else {
+                        restore(&mut pool).await;
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/void-agreement: Stripe error releasing auth holds"),
+                        )
+                    };
+                    let auth_hold_released = released_holds > 0;
+                    if auth_hold_released {
+                        audit(admin.id, format!("{}: released {} auth hold(s)", audit_path, released_holds)).await;
+                    }
+
+                    let refunded_amount = if body.refund_amount.is_some_and(|amount| amount.is_zero()) {
+                        Decimal::zero()
+                    } else {
+                        let refunded_amount = methods::agreement::refund_agreement_payments(agreement.id, &stripe_id, body.refund_amount).await;
+                        let Ok(refunded_amount) = refunded_amount else {
+                            restore(&mut pool).await;
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("admin/void-agreement: Stripe error refunding payments"),
+                            )
+                        };
+                        audit(admin.id, format!("{}: refunded ${}", audit_path, refunded_amount)).await;
+                        refunded_amount
+                    };
+
+                    if methods::agreement::reverse_reward_transactions(agreement.id).await.is_err() {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("admin/void-agreement: Database error reversing reward transactions, agreement voided"),
+                        )
+                    }
+                    audit(admin.id, format!("{}: reversed reward hours", audit_path)).await;
+                    let agreement = voided;
+
+                    let resp = helper_model::VoidAgreementResponse {
+                        agreement,
+                        refunded_amount,
+                        auth_hold_released,
+                    };
+                    methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                }
+            }
+        })
+}
+
+// Every void step is audited, a failed audit write is logged and does not undo the step
+async fn audit(admin_id: i32, entry: String) {
+    if methods::audit::record(Some(admin_id), model::AuditActionType::Update, entry.clone()).await.is_err() {
+        eprintln!("admin/void-agreement: DB error recording audit \"{}\"", entry);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{connection_pool, helper_model, integration, methods, model, schema};
+use crate::helper_model::VeygoError;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error;
+use rust_decimal::prelude::*;
+use stripe_core::PaymentIntentCaptureMethod;
+use warp::{Filter, Reply, http::Method, http::StatusCode};
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("cancel")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |method: Method, body: helper_model::CancelAgreementRequest, auth: String, user_agent: String| {
+            // Checking method is POST
+            if method != Method::POST {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            if body.agreement_id <= 0 {
+                return methods::standard_replies::bad_request_400("wrong parameters. ")
+            }
+
+            // Checking token
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                // RETURN: UNAUTHORIZED
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            let user_id = match user_id_parsed_result {
+                Ok(int) => {
+                    int
+                }
+                Err(_) => {
+                    // RETURN: UNAUTHORIZED
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+            let access_token = model::RequestToken {
+                user_id,
+                token: String::from(token_and_id[0]),
+            };
+            let if_token_valid_result = methods::tokens::verify_user_token(&access_token.user_id, &access_token.token).await;
+
+            match if_token_valid_result {
+                Err(e) => {
+                    match e {
+                        VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/cancel: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok(valid_token) => {
+                    // token is valid
+                    let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                    match ext_result {
+                        Ok(bool) => {
+                            if !bool {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/cancel: Token extension failed (returned false)"),
+                                );
+                            }
+                        }
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/cancel: Token extension error"),
+                            );
+                        }
+                    }
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    // Only reservations that have not been picked up can be canceled
+                    use schema::agreements::dsl as ag_q;
+                    use schema::locations::dsl as l_q;
+                    use schema::renters::dsl as r_q;
+                    use schema::payment_methods::dsl as pm_q;
+                    let agreement = ag_q::agreements
+                        .find(&body.agreement_id)
+                        .inner_join(l_q::locations)
+                        .inner_join(r_q::renters)
+                        .inner_join(pm_q::payment_methods)
+                        .filter(ag_q::renter_id.eq(&user_id))
+                        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                        .filter(ag_q::actual_pickup_time.is_null())
+                        .select((ag_q::agreements::all_columns(), l_q::apartment_id, r_q::stripe_id, pm_q::token))
+                        .get_result::<(model::Agreement, i32, String, String)>(&mut pool);
+
+                    let (agreement, apartment_id, stripe_id, payment_method_token) = match agreement {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return match e {
+                                Error::NotFound => {
+                                    // RETURN: FORBIDDEN
+                                    methods::standard_replies::agreement_not_allowed_response()
+                                }
+                                _ => {
+                                    methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/cancel: Database error loading agreement"),
+                                    )
+                                }
+                            };
+                        }
+                    };
+
+                    let now = Utc::now();
+                    let cancellation_fee = methods::agreement::cancellation_fee_for(apartment_id, agreement.rsvp_pickup_time - now).await;
+                    let Ok(cancellation_fee) = cancellation_fee else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("agreement/cancel: Database error loading cancellation fee policy"),
+                        )
+                    };
+                    let mut cancellation_fee_2dp = cancellation_fee.round_dp(2);
+                    cancellation_fee_2dp.rescale(2);
+
+                    // Claim the booking before any money moves, a repeated request finds nothing left to cancel
+                    let claimed = diesel::update(
+                        ag_q::agreements
+                            .find(agreement.id)
+                            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                            .filter(ag_q::actual_pickup_time.is_null())
+                    )
+                        .set(ag_q::status.eq(model::AgreementStatus::Canceled))
+                        .get_result::<model::Agreement>(&mut pool);
+                    let agreement = match claimed {
+                        Ok(agreement) => agreement,
+                        Err(Error::NotFound) => {
+                            return methods::standard_replies::agreement_not_allowed_response()
+                        }
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/cancel: Database error canceling agreement"),
+                            )
+                        }
+                    };
+
+                    // Fees below the Stripe minimum charge are waived
+                    let fee_intent = if cancellation_fee_2dp.mantissa() >= 50 {
+                        let description = "RSVP #".to_owned() + &*agreement.confirmation + " Cancellation Fee";
+                        let pmi = integration::stripe_veygo::create_payment_intent(
+                            &stripe_id, &payment_method_token, cancellation_fee_2dp.mantissa() as i64, PaymentIntentCaptureMethod::Automatic, &description
+                        ).await;
+                        match pmi {
+                            Ok(pmi) => Some(pmi),
+                            Err(err) => {
+                                // Nothing was collected, hand the booking back so the renter can try again
+                                let restored = diesel::update(ag_q::agreements.find(agreement.id))
+                                    .set(ag_q::status.eq(model::AgreementStatus::Rental))
+                                    .execute(&mut pool);
+                                if restored.is_err() {
+                                    return methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/cancel: DB error restoring agreement after failed cancellation fee"),
+                                    )
+                                }
+                                return if err == VeygoError::CardDeclined {
+                                    methods::standard_replies::card_declined_402()
+                                } else {
+                                    methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/cancel: Stripe error creating cancellation fee payment intent")
+                                    )
+                                }
+                            }
+                        }
+                    } else {
+                        cancellation_fee_2dp = Decimal::new(0, 2);
+                        None
+                    };
+
+                    // The fee is collected from here on, record it and release the rewards before anything can bail out
+                    let fee_reference = fee_intent.as_ref().map(|pmi| pmi.id.to_string());
+                    let mut fee_saved = true;
+                    if let Some(fee_reference) = &fee_reference {
+                        let _ = diesel::update(pm_q::payment_methods.filter(pm_q::token.eq(&payment_method_token)))
+                            .set(pm_q::last_used_date_time.eq(now))
+                            .execute(&mut pool);
+
+                        use schema::payments::dsl as pmt_q;
+                        let new_payment = model::NewPayment {
+                            payment_type: model::PaymentType::Succeeded,
+                            amount: cancellation_fee_2dp,
+                            note: Some(String::from("Cancellation fee")),
+                            reference_number: Some(fee_reference.clone()),
+                            agreement_id: agreement.id,
+                            renter_id: agreement.renter_id,
+                            payment_method_id: Some(agreement.payment_method_id),
+                            amount_authorized: cancellation_fee_2dp,
+                            capture_before: None,
+                        };
+                        fee_saved = diesel::insert_into(pmt_q::payments)
+                            .values(&new_payment)
+                            .execute(&mut pool)
+                            .is_ok();
+                    }
+                    let rewards_reversed = methods::agreement::reverse_reward_transactions(agreement.id).await.is_ok();
+
+                    if !fee_saved {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("agreement/cancel: DB saving cancellation fee payment error, payment collected"),
+                        )
+                    }
+                    if !rewards_reversed {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("agreement/cancel: Database error reversing reward transactions, agreement canceled"),
+                        )
+                    }
+
+                    // Return everything collected at booking, the fee stays
+                    let refunded_amount = match &fee_reference {
+                        Some(fee_reference) => methods::agreement::refund_agreement_payments_except(agreement.id, &stripe_id, fee_reference).await,
+                        None => methods::agreement::refund_agreement_payments(agreement.id, &stripe_id, None).await,
+                    };
+                    let Ok(refunded_amount) = refunded_amount else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("agreement/cancel: Stripe error refunding booking payments, agreement canceled"),
+                        )
+                    };
+
+                    let resp = helper_model::CancelAgreementResponse {
+                        agreement,
+                        cancellation_fee: cancellation_fee_2dp,
+                        refunded_amount,
+                    };
+                    methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                }
+            }
+        })
+}
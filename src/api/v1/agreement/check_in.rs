@@ -1,5 +1,4 @@
-use std::cmp::{max};
-use crate::{methods, model, helper_model, integration, schema, proj_config, connection_pool};
+use crate::{methods, model, helper_model, integration, schema, connection_pool};
 use diesel::prelude::*;
 use diesel::expression_methods::NullableExpressionMethods;
 use warp::{Filter, Rejection, Reply, http::{Method, StatusCode}};
@@ -195,76 +194,22 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                             };
 
 
-                            let (_fuel, _odo, latitude, longitude) = match vehicle.remote_mgmt {
-                                model::RemoteMgmtType::Tesla => {
-                                    // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                                    let status_path = format!("/api/1/vehicles/{}", vehicle.remote_mgmt_id);
-
-                                    for i in 0..16 { // up to ~10s total
-                                        if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                            if let Ok(body_text) = response.text().await {
-                                                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                                    let state = json
-                                                        .get("response")
-                                                        .and_then(|r| r.get("state"))
-                                                        .and_then(|s| s.as_str())
-                                                        .unwrap_or("");
-                                                    if state == "online" {
-                                                        break;
-                                                    }
-                                                    // Only on the first iteration, if offline, send wake_up once
-                                                    if i == 0 {
-                                                        let wake_path = format!("/api/1/vehicles/{}/wake_up", vehicle.remote_mgmt_id);
-                                                        let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                    }
-
-                                    // Fetch live Tesla vehicle data (odometer + battery level)
-                                    let vehicle_tag = &vehicle.remote_mgmt_id;
-                                    let tesla_path = format!("/api/1/vehicles/{}/vehicle_data?endpoints=location_data%3Bcharge_state%3Bvehicle_state", vehicle_tag);
-
-                                    let tesla_resp = match integration::tesla_veygo::tesla_make_request(Method::GET, &tesla_path, None).await {
-                                        Ok(r) => r,
-                                        Err(_) => {
-                                            return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Tesla API error fetching vehicle_data"));
-                                        }
-                                    };
-
-                                    if !tesla_resp.status().is_success() {
-                                        return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Tesla API returned non-success for vehicle_data"));
-                                    }
-
-                                    let tesla_body: helper_model::TeslaVehicleDataEnvelope = match tesla_resp.json().await {
-                                        Ok(b) => b,
-                                        Err(_) => {
-                                            return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Tesla API response JSON decode error"));
-                                        }
-                                    };
-
-                                    let odometer_i32: i32 = tesla_body.response.vehicle_state.odometer.round() as i32;
-                                    let battery_level_i32: i32 = tesla_body.response.charge_state.battery_level;
-
-                                    let lat = tesla_body.response.drive_state.latitude;
-                                    let lon = tesla_body.response.drive_state.longitude;
-
-                                    vehicle.odometer = odometer_i32;
-                                    vehicle.tank_level_percentage = battery_level_i32;
-
-                                    let _ = vehicle.save_changes::<model::Vehicle>(&mut pool);
-
-                                    (battery_level_i32, odometer_i32, lat, lon)
-                                }
-                                _ => {
-                                    return methods::standard_replies::internal_server_error_response_500(
-                                        String::from("agreement/check-in: Vehicle not supported for remote return"),
-                                    )
-                                }
+                            let remote = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id);
+                            let Ok(remote) = remote else {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/check-in: Vehicle not supported for remote return"),
+                                )
+                            };
+                            let telemetry = methods::remote_vehicle::refresh_readings(remote.as_ref(), &mut vehicle).await;
+                            let Ok(telemetry) = telemetry else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Remote management error fetching vehicle data"));
+                            };
+                            let (Some(latitude), Some(longitude)) = (telemetry.latitude, telemetry.longitude) else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Remote management returned no vehicle location"));
                             };
 
+                            let _ = vehicle.save_changes::<model::Vehicle>(&mut pool);
+
                             vehicle_current_latitude = latitude;
                             vehicle_current_longitude = longitude;
 
@@ -309,75 +254,21 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                             };
 
 
-                            let (fuel, odo, latitude, longitude) = match vehicle.remote_mgmt {
-                                model::RemoteMgmtType::Tesla => {
-                                    // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                                    let status_path = format!("/api/1/vehicles/{}", vehicle.remote_mgmt_id);
-
-                                    for i in 0..16 { // up to ~10s total
-                                        if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                            if let Ok(body_text) = response.text().await {
-                                                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                                    let state = json
-                                                        .get("response")
-                                                        .and_then(|r| r.get("state"))
-                                                        .and_then(|s| s.as_str())
-                                                        .unwrap_or("");
-                                                    if state == "online" {
-                                                        break;
-                                                    }
-                                                    // Only on the first iteration, if offline, send wake_up once
-                                                    if i == 0 {
-                                                        let wake_path = format!("/api/1/vehicles/{}/wake_up", vehicle.remote_mgmt_id);
-                                                        let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                    }
-
-                                    // Fetch live Tesla vehicle data (odometer + battery level)
-                                    let vehicle_tag = &vehicle.remote_mgmt_id;
-                                    let tesla_path = format!("/api/1/vehicles/{}/vehicle_data?endpoints=location_data%3Bcharge_state%3Bvehicle_state", vehicle_tag);
-
-                                    let tesla_resp = match integration::tesla_veygo::tesla_make_request(Method::GET, &tesla_path, None).await {
-                                        Ok(r) => r,
-                                        Err(_) => {
-                                            return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Tesla API error fetching vehicle_data"));
-                                        }
-                                    };
-
-                                    if !tesla_resp.status().is_success() {
-                                        return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Tesla API returned non-success for vehicle_data"));
-                                    }
-
-                                    let tesla_body: helper_model::TeslaVehicleDataEnvelope = match tesla_resp.json().await {
-                                        Ok(b) => b,
-                                        Err(_) => {
-                                            return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Tesla API response JSON decode error"));
-                                        }
-                                    };
-
-                                    let odometer_i32: i32 = tesla_body.response.vehicle_state.odometer.round() as i32;
-                                    let battery_level_i32: i32 = tesla_body.response.charge_state.battery_level;
-
-                                    let lat = tesla_body.response.drive_state.latitude;
-                                    let lon = tesla_body.response.drive_state.longitude;
-
-                                    vehicle.odometer = odometer_i32;
-                                    vehicle.tank_level_percentage = battery_level_i32;
-
-                                    let _ = vehicle.save_changes::<model::Vehicle>(&mut pool);
-
-                                    (battery_level_i32, odometer_i32, lat, lon)
-                                }
-                                _ => {
-                                    return methods::standard_replies::internal_server_error_response_500(
-                                        String::from("agreement/check-in: Vehicle not supported for remote return"),
-                                    )
-                                }
+                            let remote = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id);
+                            let Ok(remote) = remote else {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/check-in: Vehicle not supported for remote return"),
+                                )
+                            };
+                            let telemetry = methods::remote_vehicle::refresh_readings(remote.as_ref(), &mut vehicle).await;
+                            let Ok(telemetry) = telemetry else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Remote management error fetching vehicle data"));
                             };
+                            let (Some(latitude), Some(longitude)) = (telemetry.latitude, telemetry.longitude) else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-in: Remote management returned no vehicle location"));
+                            };
+
+                            let _ = vehicle.save_changes::<model::Vehicle>(&mut pool);
 
                             vehicle_current_latitude = latitude;
                             vehicle_current_longitude = longitude;
@@ -387,8 +278,8 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                                 right_image: right_image_path.clone(),
                                 front_image: front_image_path.clone(),
                                 back_image: back_image_path.clone(),
-                                odometer: odo,
-                                level: fuel,
+                                odometer: telemetry.odometer,
+                                level: telemetry.level,
                                 vehicle_id: vehicle.id,
                                 rear_right: back_right_image_path.clone(),
                                 rear_left: back_left_image_path.clone(),
@@ -505,49 +396,14 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
 
                     // lock the vehicle
 
-                    match vehicle.remote_mgmt {
-                        model::RemoteMgmtType::Tesla => {
-                            // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                            let status_path = format!("/api/1/vehicles/{}", vehicle.remote_mgmt_id);
-
-                            for i in 0..16 { // up to ~10s total
-                                if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                    if let Ok(body_text) = response.text().await {
-                                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                            let state = json
-                                                .get("response")
-                                                .and_then(|r| r.get("state"))
-                                                .and_then(|s| s.as_str())
-                                                .unwrap_or("");
-                                            if state == "online" {
-                                                break;
-                                            }
-                                            // Only on the first iteration, if offline, send wake_up once
-                                            if i == 0 {
-                                                let wake_path = format!("/api/1/vehicles/{}/wake_up", vehicle.remote_mgmt_id);
-                                                let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                            }
-                                        }
-                                    }
-                                }
-                                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                            }
-                            // 2) Proceed to lock/unlock once online (or after timeout anyway)
-                            let cmd_path = format!("/api/1/vehicles/{}/command/door_lock", vehicle.remote_mgmt_id);
-                            let result = integration::tesla_veygo::tesla_make_request(Method::POST, &cmd_path, None).await;
-
-                            let Ok(resp) = result else {
-                                return methods::standard_replies::internal_server_error_response_500(
-                                    String::from("agreement/check-in: Tesla API error at door_lock request")
-                                );
-                            };
-                            if resp.status() != StatusCode::OK {
-                                return methods::standard_replies::internal_server_error_response_500(
-                                    String::from("agreement/check-in: Tesla API returned non-200 at door_lock request")
-                                );
-                            }
-                        }
-                        _ => {}
+                    if let Ok(remote) = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id) {
+                        let origin = methods::remote_command::CommandOrigin {
+                            vehicle_id: vehicle.id,
+                            agreement_id: Some(agreement_to_be_checked_in.id),
+                            triggered_by: Some(access_token.user_id),
+                        };
+                        // a failed lock keeps retrying in the background and alerts staff, the trip still ends here
+                        let _result = methods::remote_command::lock_with_retry(remote, origin).await;
                     }
 
                     // update agreement status to check in (not in db)
@@ -678,306 +534,13 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
 
                     // Calculate total cost
 
-                    // 0. rate offer
-                    let rate_offer = agreement_to_be_checked_in.utilization_factor;
-
-                    // 1. total rental revenue
-                    let trip_duration = agreement_to_be_checked_in.rsvp_drop_off_time
-                        - agreement_to_be_checked_in.rsvp_pickup_time;
-                    let trip_duration_including_late_return =
-                        max(agreement_to_be_checked_in.actual_drop_off_time.unwrap(), agreement_to_be_checked_in.rsvp_drop_off_time)
-                        - agreement_to_be_checked_in.rsvp_pickup_time;
-
-                    let total_hours_reserved = Decimal::new(trip_duration.num_minutes(), 0) / Decimal::new(60, 0);
-
-                    let total_hours_driven = Decimal::new(trip_duration_including_late_return.num_minutes(), 0) / Decimal::new(60, 0);
-                    let total_hours_driven_round_up = total_hours_driven.round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
-
-                    let late_hours = (total_hours_driven - total_hours_reserved)
-                        .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
-
-                    use schema::reward_transactions::dsl as re_q;
-                    let reward_used_sum = re_q::reward_transactions
-                        .filter(re_q::renter_id.eq(agreement_to_be_checked_in.renter_id))
-                        .filter(re_q::agreement_id.eq(agreement_to_be_checked_in.id))
-                        .filter(re_q::duration.gt(Decimal::ZERO))
-                        .select(diesel::dsl::sum(re_q::duration))
-                        .get_result::<Option<Decimal>>(&mut pool);
-
-                    let Ok(reward_used_sum) = reward_used_sum else {
+                    let price = methods::agreement::settled_price(&agreement_to_be_checked_in).await;
+                    let Ok(price) = price else {
                         return methods::standard_replies::internal_server_error_response_500(
-                            String::from("agreement/check-in: Database connection error at summing reward hours")
+                            String::from("agreement/check-in: Database error calculating final price")
                         )
                     };
-
-                    let reward_hours = match reward_used_sum {
-                        None => { Decimal::ZERO }
-                        Some(credit) => {
-                            credit
-                        }
-                    };
-
-                    let billable_days_count_including_late_return: i32 = methods::rental_rate::billable_days_count(trip_duration_including_late_return);
-                    let billable_duration_hours: Decimal = methods::rental_rate::calculate_billable_duration_hours(trip_duration);
-
-                    let duration_revenue_before_reward = billable_duration_hours * agreement_to_be_checked_in.duration_rate * agreement_to_be_checked_in.msrp_factor * rate_offer;
-
-                    let avg_hourly_rate = duration_revenue_before_reward / total_hours_reserved;
-
-                    let duration_revenue = duration_revenue_before_reward - avg_hourly_rate * reward_hours;
-
-                    let duration_revenue_after_promo = match agreement_to_be_checked_in.clone().promo_id {
-                        None => { duration_revenue }
-                        Some(promo) => {
-                            use schema::promos::dsl as p_q;
-                            let discount = p_q::promos
-                                .find(&promo)
-                                .select(p_q::amount)
-                                .get_result::<Decimal>(&mut pool);
-
-                            let Ok(discount) = discount else {
-                                return methods::standard_replies::internal_server_error_response_500(
-                                    String::from("agreement/check-in: Database connection error looking up promo amount")
-                                )
-                            };
-
-                            max(Decimal::zero(), duration_revenue - discount)
-                        }
-                    };
-
-                    // does not including late return
-                    let duration_revenue_after_promo = match agreement_to_be_checked_in.clone().manual_discount {
-                        None => { duration_revenue_after_promo }
-                        Some(discount) => {
-                            max(Decimal::zero(), duration_revenue - discount)
-                        }
-                    };
-
-                    // late return fee is calculated separately
-                    let late_return_fee = Decimal::new(2, 0) * late_hours * agreement_to_be_checked_in.duration_rate * agreement_to_be_checked_in.msrp_factor * rate_offer;
-
-                    let total_rental_revenue = duration_revenue_after_promo + late_return_fee;
-
-                    // 2. total insurance revenue
-
-                    // calculated to include late return
-                    let total_insurance_revenue = {
-                        total_hours_driven_round_up * (
-                            agreement_to_be_checked_in.liability_protection_rate.unwrap_or(Decimal::zero())
-                                + agreement_to_be_checked_in.pcdw_protection_rate.unwrap_or(Decimal::zero())
-                                + agreement_to_be_checked_in.pcdw_ext_protection_rate.unwrap_or(Decimal::zero())
-                                + agreement_to_be_checked_in.pai_protection_rate.unwrap_or(Decimal::zero())
-                                + agreement_to_be_checked_in.rsa_protection_rate.unwrap_or(Decimal::zero())
-                        )
-                    };
-
-                    // 3. mileage package revenue
-
-                    let (total_mileage_package_revenue, miles_allowed) = match agreement_to_be_checked_in.mileage_package_id {
-                        None => {
-                            // didn't select mp
-                            (Decimal::zero(), 10)
-                        }
-                        Some(mp_id) => {
-                            use schema::mileage_packages::dsl as mp_q;
-                            let mp_result = mp_q::mileage_packages
-                                .find(mp_id)
-                                .select((mp_q::miles, mp_q::discounted_rate))
-                                .get_result::<(i32, i32)>(&mut pool);
-                            match mp_result {
-                                Ok((mileage, discount_rate)) => {
-                                    let base_rate_for_mp = if let Some(overwrite) = agreement_to_be_checked_in.mileage_package_overwrite {
-                                        overwrite
-                                    } else {
-                                        agreement_to_be_checked_in.duration_rate * agreement_to_be_checked_in.msrp_factor * agreement_to_be_checked_in.mileage_conversion
-                                    };
-
-                                    (
-                                        base_rate_for_mp * Decimal::new(mileage as i64, 0) * Decimal::new(discount_rate as i64, 2),
-                                        mileage + 10
-                                    )
-                                }
-                                Err(_) => {
-                                    return methods::standard_replies::internal_server_error_response_500(
-                                        String::from( "agreement/check-in: Database error loading mileage package"),
-                                    )
-                                }
-                            }
-                        }
-                    };
-
-                    // 4. charges
-
-
-                    // eg. toll road, tesla supercharging
-                    let total_taxed_charges_cost = c_q::charges
-                        .filter(c_q::agreement_id.eq(agreement_to_be_checked_in.id))
-                        .filter(c_q::is_taxed)
-                        .select(diesel::dsl::sum(c_q::amount))
-                        .get_result::<Option<Decimal>>(&mut pool);
-
-                    let Ok(total_taxed_charges_cost) = total_taxed_charges_cost else {
-                        return methods::standard_replies::internal_server_error_response_500(
-                            String::from("agreement/check-in: Database connection error at summing external charges cost")
-                        )
-                    };
-
-                    let total_taxed_charges_cost = match total_taxed_charges_cost {
-                        None => { Decimal::ZERO }
-                        Some(cost) => { cost }
-                    };
-
-                    // eg. parking citations and fines
-                    let total_not_taxed_charges_cost = c_q::charges
-                        .filter(c_q::agreement_id.eq(agreement_to_be_checked_in.id))
-                        .filter(c_q::is_taxed.eq(false))
-                        .select(diesel::dsl::sum(c_q::amount))
-                        .get_result::<Option<Decimal>>(&mut pool);
-
-                    let Ok(total_not_taxed_charges_cost) = total_not_taxed_charges_cost else {
-                        return methods::standard_replies::internal_server_error_response_500(
-                            String::from("agreement/check-in: Database connection error at summing external charges cost")
-                        )
-                    };
-
-                    let total_not_taxed_charges_cost = match total_not_taxed_charges_cost {
-                        None => { Decimal::ZERO }
-                        Some(cost) => { cost }
-                    };
-
-                    // 5. low fuel & over mileage
-
-                    let check_in_percent = check_in_snapshot.level + 10;
-                    let check_out_percent = {
-                        let check_out_snapshot_id = agreement_to_be_checked_in.vehicle_snapshot_before.unwrap();
-                        let check_out_percent = v_s_q::vehicle_snapshots
-                            .find(check_out_snapshot_id)
-                            .select(v_s_q::level)
-                            .get_result::<i32>(&mut pool);
-                        match check_out_percent {
-                            Ok(check_out_percent) => { check_out_percent }
-                            Err(_) => {
-                                return methods::standard_replies::internal_server_error_response_500(
-                                    String::from("agreement/check-in: Database connection error at fetching check out percent")
-                                )
-                            }
-                        }
-                    };
-
-                    let missing_fuel_level = max(0, check_out_percent - check_in_percent) as i64;
-
-                    let low_fuel_revenue = Decimal::new(missing_fuel_level, 0) * proj_config::PRICE_PER_CENT_ON_GAS;
-                    let over_mileage_revenue = {
-                        let check_in_odo = check_in_snapshot.odometer;
-
-                        let check_out_snap_id = agreement_to_be_checked_in.vehicle_snapshot_before.unwrap();
-                        let check_out_odo = v_s_q::vehicle_snapshots
-                            .find(check_out_snap_id)
-                            .select(v_s_q::odometer)
-                            .get_result::<i32>(&mut pool);
-                        let Ok(check_out_odo) = check_out_odo else {
-                            return methods::standard_replies::internal_server_error_response_500(
-                                String::from("agreement/check-in: Database connection error looking up check out odometer")
-                            )
-                        };
-
-                        let total_driven = check_in_odo - check_out_odo;
-                        if total_driven <= miles_allowed {
-                            Decimal::ZERO
-                        } else {
-                            let over_mileage = total_driven - miles_allowed;
-                            let mileage_rate: Decimal = {
-                                if let Some(overwrite) = agreement_to_be_checked_in.mileage_rate_overwrite {
-                                    overwrite
-                                } else {
-                                    agreement_to_be_checked_in.duration_rate * agreement_to_be_checked_in.msrp_factor * agreement_to_be_checked_in.mileage_conversion
-                                }
-                            };
-                            Decimal::new(over_mileage as i64, 0) * mileage_rate
-                        }
-                    };
-
-                    // 6. taxes
-
-                    let mut hours_driven_round_up = total_hours_driven.round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
-                    hours_driven_round_up.rescale(0);
-                    let hours_driven_round_up_int = hours_driven_round_up.mantissa() as i32;
-
-                    use schema::agreements_taxes::dsl as agreements_taxes_query;
-                    use schema::taxes::dsl as t_q;
-
-                    let taxes = agreements_taxes_query::agreements_taxes
-                        .inner_join(t_q::taxes)
-                        .filter(agreements_taxes_query::agreement_id.eq(&agreement_to_be_checked_in.id))
-                        .select(t_q::taxes::all_columns())
-                        .get_results::<model::Tax>(&mut pool);
-
-                    let Ok(taxes) = taxes else {
-                        return methods::standard_replies::internal_server_error_response_500(
-                            String::from("agreement/check-in: Database error loading apartment taxes")
-                        )
-                    };
-
-                    let mut local_tax_rate_daily = Decimal::zero();
-                    let mut local_tax_rate_fixed = Decimal::zero();
-
-                    let mut local_tax_rate_percent_sales = Decimal::zero();
-                    let mut local_tax_rate_percent_non_sales = Decimal::zero();
-
-                    let mut certain_taxes_needed_to_apply_sales_tax = Decimal::zero();
-
-                    for tax_obj in &taxes {
-                        let mut tax_needed_to_be_counted = false;
-                        if let Some(threshold) = tax_obj.threshold && let Some(is_lower) = tax_obj.is_lower {
-                            if is_lower && hours_driven_round_up_int < threshold || !is_lower && hours_driven_round_up_int >= threshold {
-                                tax_needed_to_be_counted = true;
-                            }
-                        } else {
-                            tax_needed_to_be_counted = true;
-                        }
-
-                        if tax_needed_to_be_counted {
-                            match tax_obj.tax_type {
-                                model::TaxType::Percent => {
-                                    if tax_obj.is_sales_tax {
-                                        local_tax_rate_percent_sales += tax_obj.multiplier;
-                                    } else {
-                                        local_tax_rate_percent_non_sales += tax_obj.multiplier;
-                                    }
-                                },
-                                model::TaxType::Daily => {
-                                    if tax_obj.is_sales_tax {
-                                        certain_taxes_needed_to_apply_sales_tax += tax_obj.multiplier * Decimal::new(billable_days_count_including_late_return as i64, 0);
-                                    }
-                                    local_tax_rate_daily += tax_obj.multiplier;
-                                }
-                                model::TaxType::Fixed => {
-                                    if tax_obj.is_sales_tax {
-                                        certain_taxes_needed_to_apply_sales_tax += tax_obj.multiplier;
-                                    }
-                                    local_tax_rate_fixed += tax_obj.multiplier;
-                                }
-                            }
-                        }
-                    }
-
-                    // 7. summarize
-
-                    let total_subject_to_non_sales_tax = total_rental_revenue
-                        + total_insurance_revenue + total_mileage_package_revenue
-                        + low_fuel_revenue + over_mileage_revenue;
-                    let total_revenue = total_subject_to_non_sales_tax + total_taxed_charges_cost;
-
-                    let total_subject_to_sales_tax = total_revenue + certain_taxes_needed_to_apply_sales_tax;
-
-                    let total_percentage_tax = total_subject_to_non_sales_tax * local_tax_rate_percent_non_sales
-                        + total_subject_to_sales_tax * local_tax_rate_percent_sales;
-                    let total_daily_tax = Decimal::new(billable_days_count_including_late_return as i64, 0) * local_tax_rate_daily;
-                    let total_fixed_tax = local_tax_rate_fixed;
-
-                    let total_stripe_amount = total_revenue + total_percentage_tax
-                        + total_daily_tax + total_fixed_tax + total_not_taxed_charges_cost;
-                    let total_stripe_amount_2dp = total_stripe_amount.round_dp(2);
+                    let total_stripe_amount_2dp = price.total;
 
                     // settle payments
 
@@ -1083,6 +646,12 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                     let new_ag = agreement_to_be_checked_in.save_changes::<model::Agreement>(&mut pool);
                     match new_ag {
                         Ok(ag) => {
+                            let ag_moved = ag.clone();
+                            tokio::spawn(async move {
+                                if let Err(err) = methods::receipt::email_receipt(&ag_moved).await {
+                                    eprintln!("agreement/check-in: could not email receipt for RSVP #{}: {:?}", ag_moved.confirmation, err);
+                                }
+                            });
                             methods::standard_replies::response_with_obj(&ag, StatusCode::OK)
                         }
                         Err(_) => {
@@ -218,72 +218,18 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                             };
 
 
-                            let (fuel, odo) = match vehicle.remote_mgmt {
-                                model::RemoteMgmtType::Tesla => {
-                                    // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                                    let status_path = format!("/api/1/vehicles/{}", vehicle.remote_mgmt_id);
-
-                                    for i in 0..16 { // up to ~10s total
-                                        if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                            if let Ok(body_text) = response.text().await {
-                                                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                                    let state = json
-                                                        .get("response")
-                                                        .and_then(|r| r.get("state"))
-                                                        .and_then(|s| s.as_str())
-                                                        .unwrap_or("");
-                                                    if state == "online" {
-                                                        break;
-                                                    }
-                                                    // Only on the first iteration, if offline, send wake_up once
-                                                    if i == 0 {
-                                                        let wake_path = format!("/api/1/vehicles/{}/wake_up", vehicle.remote_mgmt_id);
-                                                        let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                    }
-
-                                    // Fetch live Tesla vehicle data (odometer + battery level)
-                                    let vehicle_tag = &vehicle.remote_mgmt_id;
-                                    let tesla_path = format!("/api/1/vehicles/{}/vehicle_data?endpoints=location_data%3Bcharge_state%3Bvehicle_state", vehicle_tag);
-
-                                    let tesla_resp = match integration::tesla_veygo::tesla_make_request(Method::GET, &tesla_path, None).await {
-                                        Ok(r) => r,
-                                        Err(_) => {
-                                            return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-out: Tesla API error fetching vehicle_data"));
-                                        }
-                                    };
-
-                                    if !tesla_resp.status().is_success() {
-                                        return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-out: Tesla API returned non-success for vehicle_data"));
-                                    }
-
-                                    let tesla_body: helper_model::TeslaVehicleDataEnvelope = match tesla_resp.json().await {
-                                        Ok(b) => b,
-                                        Err(_) => {
-                                            return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-out: Tesla API response JSON decode error"));
-                                        }
-                                    };
-
-                                    let odometer_i32: i32 = tesla_body.response.vehicle_state.odometer.round() as i32;
-                                    let battery_level_i32: i32 = tesla_body.response.charge_state.battery_level;
-
-                                    vehicle.odometer = odometer_i32;
-                                    vehicle.tank_level_percentage = battery_level_i32;
-
-                                    let _ = vehicle.save_changes::<model::Vehicle>(&mut pool);
-
-                                    (battery_level_i32, odometer_i32)
-                                }
-                                _ => {
-                                    return methods::standard_replies::internal_server_error_response_500(
-                                        String::from("agreement/check-out: Vehicle not supported for remote pickup"),
-                                    )
-                                }
+                            let remote = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id);
+                            let Ok(remote) = remote else {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/check-out: Vehicle not supported for remote pickup"),
+                                )
                             };
+                            let telemetry = methods::remote_vehicle::refresh_readings(remote.as_ref(), &mut vehicle).await;
+                            let Ok(telemetry) = telemetry else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("agreement/check-out: Remote management error fetching vehicle data"));
+                            };
+
+                            let _ = vehicle.save_changes::<model::Vehicle>(&mut pool);
 
 
                             let snapshot_to_be_inserted = model::NewVehicleSnapshot {
@@ -291,8 +237,8 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                                 right_image: right_image_path.clone(),
                                 front_image: front_image_path.clone(),
                                 back_image: back_image_path.clone(),
-                                odometer: odo,
-                                level: fuel,
+                                odometer: telemetry.odometer,
+                                level: telemetry.level,
                                 vehicle_id: vehicle.id,
                                 rear_right: back_right_image_path.clone(),
                                 rear_left: back_left_image_path.clone(),
@@ -468,40 +414,15 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                                         )
                                     };
 
-                                    match vehicle_remote_mgmt {
-                                        model::RemoteMgmtType::Tesla => {
-                                            let _handler = tokio::spawn(async move {
-                                                // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                                                let status_path = format!("/api/1/vehicles/{}", mgmt_id);
-
-                                                for i in 0..16 {
-                                                    if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                                        if let Ok(body_text) = response.text().await {
-                                                            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                                                let state = json
-                                                                    .get("response")
-                                                                    .and_then(|r| r.get("state"))
-                                                                    .and_then(|s| s.as_str())
-                                                                    .unwrap_or("");
-                                                                if state == "online" {
-                                                                    break;
-                                                                }
-                                                                // Only on the first iteration, if offline, send wake_up once
-                                                                if i == 0 {
-                                                                    let wake_path = format!("/api/1/vehicles/{}/wake_up", mgmt_id);
-                                                                    let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                                }
-                                                // 2) Proceed to lock/unlock once online (or after timeout anyway)
-                                                let cmd_path = format!("/api/1/vehicles/{}/command/door_unlock", mgmt_id);
-                                                let _result = integration::tesla_veygo::tesla_make_request(Method::POST, &cmd_path, None).await;
-                                            });
-                                        }
-                                        _ => {}
+                                    if let Ok(remote) = methods::remote_vehicle::for_vehicle(vehicle_remote_mgmt, &mgmt_id) {
+                                        let origin = methods::remote_command::CommandOrigin {
+                                            vehicle_id: ag.vehicle_id,
+                                            agreement_id: Some(ag.id),
+                                            triggered_by: Some(access_token.user_id),
+                                        };
+                                        let _handler = tokio::spawn(async move {
+                                            let _result = methods::remote_command::send_logged(remote.as_ref(), model::RemoteCommandType::Unlock, origin, 1).await;
+                                        });
                                     }
 
                                     methods::standard_replies::response_with_obj(ag, StatusCode::OK)
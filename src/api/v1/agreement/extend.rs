@@ -0,0 +1,364 @@
+use std::cmp::max;
+use crate::{integration, methods, model, proj_config, helper_model, schema, helper_model::VeygoError, connection_pool};
+use chrono::{DateTime, Duration, TimeDelta, Timelike, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use stripe_core::{PaymentIntentCaptureMethod};
+use warp::{Filter, Reply, http::{Method, StatusCode}};
+use rust_decimal::prelude::*;
+
+// Rental, protection and taxes for the reserved window only. Promo, reward hours and mileage
+// package do not change with the length of the trip, check-in settles the exact amount.
+fn estimate_trip_total(agreement: &model::Agreement, taxes: &[model::Tax], trip_duration: TimeDelta) -> Decimal {
+    let mut input = methods::pricing::PricingInput::for_reservation(
+        trip_duration, agreement.duration_rate, agreement.msrp_factor, agreement.utilization_factor, taxes,
+    );
+    input.protection_rates = methods::pricing::ProtectionRates::from(agreement);
+    methods::pricing::calculate(&input).total
+}
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("extend")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::ExtendAgreementRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+
+                if body.agreement_id <= 0 {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+
+                let is_quarter_aligned = |dt: DateTime<Utc>| {
+                    dt.second() == 0 && dt.nanosecond() == 0 && dt.minute() % 15 == 0
+                };
+
+                if !is_quarter_aligned(body.end_time) || body.end_time <= Utc::now() {
+                    // RETURN: BAD_REQUEST
+                    return methods::standard_replies::bad_request_400("Time is invalid")
+                }
+
+                let mut pool = connection_pool().await.get().unwrap();
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    // RETURN: UNAUTHORIZED
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                let user_id = match user_id_parsed_result {
+                    Ok(int) => {
+                        int
+                    }
+                    Err(_) => {
+                        // RETURN: UNAUTHORIZED
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: String::from(token_and_id[0]),
+                };
+                let if_token_valid_result = methods::tokens::verify_user_token(&access_token.user_id, &access_token.token).await;
+
+                return match if_token_valid_result {
+                    Err(e) => {
+                        match e {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/extend: Token verification unexpected error")
+                                )
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/extend: Token extension failed (returned false)")
+                                    );
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/extend: Token extension error")
+                                );
+                            }
+                        }
+
+                        let user_in_request = methods::user::get_user_by_id(&access_token.user_id).await;
+                        let Ok(user_in_request) = user_in_request else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/extend: Database error loading renter")
+                            )
+                        };
+
+                        // Only active trips can be extended
+                        use schema::agreements::dsl as ag_q;
+                        let agreement = ag_q::agreements
+                            .find(&body.agreement_id)
+                            .filter(ag_q::renter_id.eq(&user_in_request.id))
+                            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                            .filter(ag_q::actual_pickup_time.is_not_null())
+                            .filter(ag_q::actual_drop_off_time.is_null())
+                            .filter(ag_q::deposit_pmt_id.is_not_null())
+                            .get_result::<model::Agreement>(&mut pool);
+
+                        let mut agreement = match agreement {
+                            Ok(agreement) => agreement,
+                            Err(err) => {
+                                return match err {
+                                    Error::NotFound => {
+                                        methods::standard_replies::agreement_not_allowed_response()
+                                    }
+                                    _ => {
+                                        methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/extend: Database error loading agreement")
+                                        )
+                                    }
+                                }
+                            }
+                        };
+
+                        let previous_drop_off_time = agreement.rsvp_drop_off_time;
+                        if body.end_time <= previous_drop_off_time
+                            || body.end_time - agreement.rsvp_pickup_time > methods::availability::MAX_TRIP_DURATION
+                        {
+                            // RETURN: BAD_REQUEST
+                            return methods::standard_replies::bad_request_400("Time is invalid")
+                        }
+
+                        // DL and insurance must still be valid on the new return date
+                        let return_date = body.end_time.naive_utc().date();
+                        let dl_valid = user_in_request.drivers_license_expiration.is_some_and(|exp| exp > return_date);
+                        let insurance_valid = user_in_request.insurance_liability_expiration.is_some_and(|exp| exp > return_date);
+                        if !dl_valid || !insurance_valid {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Extension Not Allowed"),
+                                message: String::from("Your driver's licence or insurance expires before the new return time. Please re-submit your documents. "),
+                            };
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        }
+
+                        // Check conflict for the added time, on both the vehicle and the renter
+
+                        let start_time_buffered = previous_drop_off_time - Duration::minutes(proj_config::RSVP_BUFFER);
+                        let end_time_buffered = body.end_time + Duration::minutes(proj_config::RSVP_BUFFER);
+
+                        let is_conflict = diesel::select(diesel::dsl::exists(
+                            ag_q::agreements
+                                .filter(ag_q::id.ne(&agreement.id))
+                                .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                                .filter(ag_q::vehicle_id.eq(&agreement.vehicle_id).or(ag_q::renter_id.eq(&agreement.renter_id)))
+                                .filter(
+                                    methods::diesel_fn::coalesce(ag_q::actual_pickup_time, ag_q::rsvp_pickup_time)
+                                        .lt(end_time_buffered)
+                                        .and(
+                                            methods::diesel_fn::coalesce(
+                                                ag_q::actual_drop_off_time,
+                                                methods::diesel_fn::greatest(ag_q::rsvp_drop_off_time, diesel::dsl::now)
+                                            ).gt(start_time_buffered)
+                                        )
+                                )
+                        )).get_result::<bool>(&mut pool);
+                        let Ok(is_conflict) = is_conflict else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/extend: Database error checking vehicle conflict")
+                            )
+                        };
+
+                        use crate::schema::vehicle_blackouts::dsl as vb_q;
+                        let is_blacked_out = diesel::select(diesel::dsl::exists(
+                            vb_q::vehicle_blackouts
+                                .filter(vb_q::vehicle_id.eq(&agreement.vehicle_id))
+                                .filter(vb_q::start_time.lt(end_time_buffered))
+                                .filter(vb_q::end_time.gt(start_time_buffered))
+                        )).get_result::<bool>(&mut pool);
+                        let Ok(is_blacked_out) = is_blacked_out else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/extend: Database error checking vehicle blackouts")
+                            )
+                        };
+
+                        if is_blacked_out {
+                            let err_msg = helper_model::ErrorResponse {
+                                title: "Vehicle Unavailable".to_string(),
+                                message: "This vehicle is out of service after your trip. Please return it on time. ".to_string(),
+                            };
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::CONFLICT)
+                        }
+
+                        if is_conflict {
+                            let err_msg = helper_model::ErrorResponse {
+                                title: "Vehicle Unavailable".to_string(),
+                                message: "This vehicle is reserved after your trip. Please return it on time. ".to_string(),
+                            };
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::CONFLICT)
+                        }
+
+                        // Estimate the added cost
+
+                        use schema::agreements_taxes::dsl as agreements_taxes_query;
+                        use schema::taxes::dsl as t_q;
+                        let taxes = agreements_taxes_query::agreements_taxes
+                            .inner_join(t_q::taxes)
+                            .filter(agreements_taxes_query::agreement_id.eq(&agreement.id))
+                            .select(t_q::taxes::all_columns())
+                            .get_results::<model::Tax>(&mut pool);
+
+                        let Ok(taxes) = taxes else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/extend: Database error loading agreement taxes")
+                            )
+                        };
+
+                        let previous_total = estimate_trip_total(&agreement, &taxes, previous_drop_off_time - agreement.rsvp_pickup_time);
+                        let new_total = estimate_trip_total(&agreement, &taxes, body.end_time - agreement.rsvp_pickup_time);
+                        let mut additional_cost_2dp = max(new_total - previous_total, Decimal::zero()).round_dp(2);
+                        additional_cost_2dp.rescale(2);
+
+                        use schema::payments::dsl as pmt_q;
+                        let auth_hold_pmt = pmt_q::payments
+                            .find(agreement.deposit_pmt_id.unwrap())
+                            .get_result::<model::Payment>(&mut pool);
+                        let Ok(mut auth_hold_pmt) = auth_hold_pmt else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/extend: Database connection error at loading auth hold payment")
+                            )
+                        };
+
+                        let mut new_amount_authorized = auth_hold_pmt.amount_authorized + additional_cost_2dp;
+                        new_amount_authorized.rescale(2);
+
+                        agreement.rsvp_drop_off_time = body.end_time;
+
+                        if !body.confirm {
+                            // RETURN: OK, price preview only
+                            let resp = helper_model::ExtendAgreementResponse {
+                                agreement,
+                                estimated_additional_cost: additional_cost_2dp,
+                                amount_authorized: new_amount_authorized,
+                                is_confirmed: false,
+                            };
+                            return methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                        }
+
+                        if additional_cost_2dp > Decimal::zero() {
+                            let intent_id = auth_hold_pmt.reference_number.clone().unwrap();
+                            let increment_result = integration::stripe_veygo::increment_authorization(
+                                &intent_id, new_amount_authorized.mantissa() as i64
+                            ).await;
+
+                            match increment_result {
+                                Ok(_) => {
+                                    auth_hold_pmt.amount_authorized = new_amount_authorized;
+                                    let result = auth_hold_pmt.save_changes::<model::Payment>(&mut pool);
+                                    if result.is_err() {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/extend: DB saving incremented auth hold error")
+                                        )
+                                    }
+                                }
+                                Err(VeygoError::CardDeclined) => {
+                                    return methods::standard_replies::card_declined_402()
+                                }
+                                Err(_) => {
+                                    // Card does not support incremental authorization, replace the hold
+                                    use schema::payment_methods::dsl as pm_q;
+                                    let pm_str = pm_q::payment_methods
+                                        .find(agreement.payment_method_id)
+                                        .select(pm_q::token)
+                                        .get_result::<String>(&mut pool);
+                                    let Ok(pm_str) = pm_str else {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/extend: Database error loading payment method"),
+                                        );
+                                    };
+
+                                    let description = "RSVP #".to_owned() + &*agreement.confirmation.clone();
+                                    let stripe_auth = integration::stripe_veygo::create_payment_intent(
+                                        &user_in_request.stripe_id, &pm_str, new_amount_authorized.mantissa() as i64, PaymentIntentCaptureMethod::Manual, &description
+                                    ).await;
+
+                                    let pmi = match stripe_auth {
+                                        Ok(pmi) => pmi,
+                                        Err(err) => {
+                                            return if err == VeygoError::CardDeclined {
+                                                methods::standard_replies::card_declined_402()
+                                            } else {
+                                                methods::standard_replies::internal_server_error_response_500(
+                                                    String::from("agreement/extend: Stripe error creating replacement auth hold")
+                                                )
+                                            }
+                                        }
+                                    };
+
+                                    let new_deposit = model::NewPayment {
+                                        payment_type: pmi.clone().status.into(),
+                                        amount: Decimal::ZERO,
+                                        note: None,
+                                        reference_number: Some(pmi.id.to_string()),
+                                        agreement_id: agreement.id,
+                                        renter_id: agreement.renter_id,
+                                        payment_method_id: Some(agreement.payment_method_id),
+                                        amount_authorized: new_amount_authorized,
+                                        capture_before: Option::from(methods::timestamps::from_seconds(pmi.clone().latest_charge.unwrap().into_object().unwrap().payment_method_details.unwrap().card.unwrap().capture_before.unwrap())),
+                                    };
+
+                                    let result = diesel::insert_into(pmt_q::payments)
+                                        .values(&new_deposit)
+                                        .get_result::<model::Payment>(&mut pool);
+
+                                    let Ok(inserted_payment) = result else {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/extend: Database error inserting replacement auth hold"),
+                                        );
+                                    };
+
+                                    agreement.deposit_pmt_id = Some(inserted_payment.id);
+
+                                    auth_hold_pmt.capture_before = None;
+                                    auth_hold_pmt.payment_type = model::PaymentType::Canceled;
+                                    let _ = auth_hold_pmt.save_changes::<model::Payment>(&mut pool);
+                                    let _ = integration::stripe_veygo::drop_auth(&intent_id).await;
+                                }
+                            }
+                        }
+
+                        let updated_agreement = agreement.save_changes::<model::Agreement>(&mut pool);
+                        let Ok(updated_agreement) = updated_agreement else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/extend: could not save agreement updates")
+                            )
+                        };
+
+                        let resp = helper_model::ExtendAgreementResponse {
+                            agreement: updated_agreement,
+                            estimated_additional_cost: additional_cost_2dp,
+                            amount_authorized: new_amount_authorized,
+                            is_confirmed: true,
+                        };
+                        methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                    }
+                };
+            },
+        )
+}
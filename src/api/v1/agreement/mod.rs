@@ -8,6 +8,11 @@ mod get_past;
 mod get;
 mod lock;
 mod unlock;
+mod cancel;
+mod modify;
+mod extend;
+mod quote;
+mod receipt;
 
 use warp::Filter;
 
@@ -19,6 +24,11 @@ pub fn api_v1_agreement()
         .or(get_past::main())
         .or(check_out::main())
         .or(check_in::main())
+        .or(cancel::main())
+        .or(modify::main())
+        .or(extend::main())
+        .or(quote::main())
+        .or(receipt::main())
         .or(get::main())
         .or(lock::main())
         .or(unlock::main())
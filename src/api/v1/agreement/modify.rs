@@ -0,0 +1,641 @@
+use crate::{integration, methods, model, proj_config, helper_model, schema, helper_model::VeygoError, connection_pool};
+use chrono::{DateTime, Duration, Timelike, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use stripe_core::{PaymentIntentCaptureMethod};
+use warp::{Filter, Reply, http::{Method, StatusCode}};
+use rust_decimal::prelude::*;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("modify")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::ModifyAgreementRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+
+                if body.agreement_id <= 0 || body.hours_using_reward < Decimal::zero() {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+
+                let now = Utc::now();
+                let min_start_time = methods::availability::earliest_start_time(now);
+                let max_start_time = methods::availability::latest_start_time(now);
+                let trip_duration = body.end_time - body.start_time;
+                let min_trip_duration = methods::availability::MIN_TRIP_DURATION;
+                let max_trip_duration = methods::availability::MAX_TRIP_DURATION;
+
+                let is_quarter_aligned = |dt: DateTime<Utc>| {
+                    dt.second() == 0 && dt.nanosecond() == 0 && dt.minute() % 15 == 0
+                };
+
+                if !is_quarter_aligned(body.start_time)
+                    || !is_quarter_aligned(body.end_time)
+                    || body.start_time < min_start_time
+                    || body.start_time > max_start_time
+                    || trip_duration < min_trip_duration
+                    || trip_duration > max_trip_duration
+                {
+                    // RETURN: BAD_REQUEST
+                    return methods::standard_replies::bad_request_400("Time is invalid")
+                }
+                let mut pool = connection_pool().await.get().unwrap();
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    // RETURN: UNAUTHORIZED
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                let user_id = match user_id_parsed_result {
+                    Ok(int) => {
+                        int
+                    }
+                    Err(_) => {
+                        // RETURN: UNAUTHORIZED
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: String::from(token_and_id[0]),
+                };
+                let if_token_valid_result = methods::tokens::verify_user_token(&access_token.user_id, &access_token.token).await;
+
+                return match if_token_valid_result {
+                    Err(e) => {
+                        match e {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/modify: Token verification unexpected error")
+                                )
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/modify: Token extension failed (returned false)")
+                                    );
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/modify: Token extension error")
+                                );
+                            }
+                        }
+
+                        let user_in_request = methods::user::get_user_by_id(&access_token.user_id).await;
+                        let Ok(user_in_request) = user_in_request else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error loading renter")
+                            )
+                        };
+
+                        // Only reservations that have not been picked up can be modified
+                        use schema::agreements::dsl as ag_q;
+                        use schema::locations::dsl as location_query;
+                        let agreement = ag_q::agreements
+                            .find(&body.agreement_id)
+                            .inner_join(location_query::locations)
+                            .filter(ag_q::renter_id.eq(&user_in_request.id))
+                            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                            .filter(ag_q::actual_pickup_time.is_null())
+                            .select((ag_q::agreements::all_columns(), location_query::apartment_id))
+                            .get_result::<(model::Agreement, i32)>(&mut pool);
+
+                        let (agreement, original_apartment_id) = match agreement {
+                            Ok(result) => result,
+                            Err(err) => {
+                                return match err {
+                                    Error::NotFound => {
+                                        methods::standard_replies::agreement_not_allowed_response()
+                                    }
+                                    _ => {
+                                        methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/modify: Database error loading agreement")
+                                        )
+                                    }
+                                }
+                            }
+                        };
+
+                        let current_time = Utc::now();
+
+                        let return_date = body.end_time.naive_utc().date();
+
+                        // Check if Renter DL exp
+
+                        if user_in_request.drivers_license_expiration.is_none() {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Modification Not Allowed"),
+                                message: String::from("Your driver's licences are pending verification. If you are still encountering this issue, please reach out to us. "),
+                            };
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        } else if user_in_request.drivers_license_expiration.unwrap() <= return_date {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Modification Not Allowed"),
+                                message: String::from("Your driver's licences expires before trip ends. Please re-submit your driver's licence. "),
+                            };
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        }
+
+                        // Check if Renter insurance
+
+                        if let Some(lia_exp) = user_in_request.insurance_liability_expiration {
+                            if lia_exp <= return_date {
+                                let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                    title: String::from("Modification Not Allowed"),
+                                    message: String::from("Your insurance expires before trip ends. Please re-submit your insurance documents. "),
+                                };
+                                // RETURN: FORBIDDEN
+                                return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                            }
+                        } else {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Modification Not Allowed"),
+                                message: String::from("Your insurance documents are pending verification. If you are still encountering this issue, please reach out to us. "),
+                            };
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        }
+
+                        // Check if Renter is on DNR record
+
+                        let dnr_records = user_in_request.get_dnr_count().await;
+                        let Ok(record_count) = dnr_records else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error checking DNR records")
+                            )
+                        };
+                        if record_count > 0 {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Do Not Rent Record Found"),
+                                message: String::from("We found one or more dnr records, please contact us to resolve this! "),
+                            };
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        }
+
+                        let renter_agreements_blocking_count = ag_q::agreements
+                            .filter(ag_q::id.ne(&agreement.id))
+                            .filter(ag_q::renter_id.eq(&access_token.user_id))
+                            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                            .filter(
+                                methods::diesel_fn::coalesce(ag_q::actual_pickup_time, ag_q::rsvp_pickup_time)
+                                    .lt(body.end_time + Duration::minutes(proj_config::RSVP_BUFFER))
+                                    .and(
+                                        methods::diesel_fn::coalesce
+                                            (
+                                                ag_q::actual_drop_off_time,
+                                                methods::diesel_fn::greatest(ag_q::rsvp_drop_off_time, diesel::dsl::now)
+                                            )
+                                            .gt(body.start_time - Duration::minutes(proj_config::RSVP_BUFFER))
+                                    )
+                            )
+                            .count()
+                            .get_result::<i64>(&mut pool);
+
+                        let Ok(renter_agreements_blocking_count) = renter_agreements_blocking_count else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error checking blocking agreements")
+                            )
+                        };
+
+                        if renter_agreements_blocking_count > 0 {
+                            return methods::standard_replies::double_booking_not_allowed()
+                        }
+
+                        use schema::apartments::dsl as apartment_query;
+                        use schema::vehicles::dsl as vehicle_query;
+                        let vehicle_result = vehicle_query::vehicles
+                            .inner_join(location_query::locations
+                                .inner_join(apartment_query::apartments)
+                            )
+                            .filter(apartment_query::id.eq(original_apartment_id))
+                            .filter(apartment_query::is_operating)
+                            .filter(location_query::is_operational)
+                            .filter(vehicle_query::id.eq(&body.vehicle_id))
+                            .filter(vehicle_query::available)
+                            .filter(vehicle_query::maintenance_hold.eq(false))
+                            .select(
+                                (
+                                    vehicle_query::vehicles::all_columns(),
+                                    location_query::locations::all_columns(),
+                                    apartment_query::apartments::all_columns()
+                                )
+                            )
+                            .get_result::<(model::Vehicle, model::Location, model::Apartment)>(&mut pool);
+
+                        let (req_vehicle, req_location, req_apt) = match vehicle_result {
+                            Ok(result) => {
+                                result
+                            }
+                            Err(err) => {
+                                return match err {
+                                    Error::NotFound => {
+                                        let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                            title: String::from("Modification Not Allowed"),
+                                            message: String::from("Switching to this vehicle is currently not allowed. Please try again later. "),
+                                        };
+                                        methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                                    }
+                                    _ => {
+                                        methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/modify: Database error loading vehicle")
+                                        )
+                                    }
+                                }
+                            }
+                        };
+
+                        // Options keep the rate they were booked at, newly added ones use the current rate
+                        let option_rate = |selected: bool, booked: Option<Decimal>, current: Option<Decimal>, name: &str| {
+                            if !selected {
+                                return Ok(None);
+                            }
+                            match booked.or(current) {
+                                Some(rate) => Ok(Some(rate)),
+                                None => Err(helper_model::ErrorResponse {
+                                    title: String::from("Options Not Allowed"),
+                                    message: format!("{} is not offered at this location. ", name),
+                                }),
+                            }
+                        };
+
+                        let options = (
+                            option_rate(body.liability, agreement.liability_protection_rate, req_apt.liability_protection_rate, "Liability insurance"),
+                            option_rate(body.pcdw, agreement.pcdw_protection_rate, req_apt.pcdw_protection_rate, "Partial Collision Damage Waiver"),
+                            option_rate(body.pcdw_ext, agreement.pcdw_ext_protection_rate, req_apt.pcdw_ext_protection_rate, "Limited Collision Damage Waiver"),
+                            option_rate(body.rsa, agreement.rsa_protection_rate, req_apt.rsa_protection_rate, "Roadside Assistance"),
+                            option_rate(body.pai, agreement.pai_protection_rate, req_apt.pai_protection_rate, "Personal Accident Insurance"),
+                        );
+                        let (ag_lia, ag_pcdw, ag_pcdw_ext, ag_rsa, ag_pai) = match options {
+                            (Ok(lia), Ok(pcdw), Ok(pcdw_ext), Ok(rsa), Ok(pai)) => (lia, pcdw, pcdw_ext, rsa, pai),
+                            (Err(err_msg), ..) | (_, Err(err_msg), ..) | (_, _, Err(err_msg), ..)
+                            | (_, _, _, Err(err_msg), _) | (_, _, _, _, Err(err_msg)) => {
+                                return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                            }
+                        };
+
+                        use schema::reward_transactions::dsl as reward_q;
+                        let booked_reward_hours = reward_q::reward_transactions
+                            .filter(reward_q::agreement_id.eq(&agreement.id))
+                            .select(diesel::dsl::sum(reward_q::duration))
+                            .first::<Option<Decimal>>(&mut pool);
+                        let Ok(booked_reward_hours) = booked_reward_hours else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error summing booked reward hours"),
+                            )
+                        };
+                        let booked_reward_hours = booked_reward_hours.unwrap_or(Decimal::zero());
+
+                        // Checked on every change, a shorter trip can leave the booked hours longer than the rental
+                        let reward_check = methods::agreement::check_reward_hours(
+                            &user_in_request, body.hours_using_reward, trip_duration, current_time, Some(agreement.id),
+                        ).await;
+                        let Ok(reward_check) = reward_check else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error summing used reward hours"),
+                            )
+                        };
+                        if let Some(message) = reward_check.rejection_message() {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Modification Not Allowed"),
+                                message: String::from(message),
+                            };
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        }
+
+                        // Check conflict
+
+                        let start_time_buffered = body.start_time - Duration::minutes(proj_config::RSVP_BUFFER);
+                        let end_time_buffered = body.end_time + Duration::minutes(proj_config::RSVP_BUFFER);
+
+                        let is_conflict = diesel::select(diesel::dsl::exists(
+                            ag_q::agreements
+                                .filter(ag_q::id.ne(&agreement.id))
+                                .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                                .filter(ag_q::vehicle_id.eq(&body.vehicle_id))
+                                .filter(
+                                    methods::diesel_fn::coalesce(ag_q::actual_pickup_time, ag_q::rsvp_pickup_time)
+                                        .lt(end_time_buffered)
+                                        .and(
+                                            methods::diesel_fn::coalesce(
+                                                ag_q::actual_drop_off_time,
+                                                methods::diesel_fn::greatest(ag_q::rsvp_drop_off_time, diesel::dsl::now)
+                                            ).gt(start_time_buffered)
+                                        )
+                                )
+                        )).get_result::<bool>(&mut pool);
+                        let Ok(is_conflict) = is_conflict else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error checking vehicle conflict")
+                            )
+                        };
+
+                        use crate::schema::vehicle_blackouts::dsl as vb_q;
+                        let is_blacked_out = diesel::select(diesel::dsl::exists(
+                            vb_q::vehicle_blackouts
+                                .filter(vb_q::vehicle_id.eq(&body.vehicle_id))
+                                .filter(vb_q::start_time.lt(end_time_buffered))
+                                .filter(vb_q::end_time.gt(start_time_buffered))
+                        )).get_result::<bool>(&mut pool);
+                        let Ok(is_blacked_out) = is_blacked_out else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error checking vehicle blackouts")
+                            )
+                        };
+
+                        if is_conflict || is_blacked_out {
+                            let err_msg = helper_model::ErrorResponse {
+                                title: "Vehicle Unavailable".to_string(),
+                                message: "Please try again later. ".to_string(),
+                            };
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::CONFLICT)
+                        }
+
+                        // Calculate total cost with the rates stored on the agreement
+
+                        let msrp_factor = if req_vehicle.id == agreement.vehicle_id {
+                            agreement.msrp_factor
+                        } else {
+                            req_vehicle.msrp_factor
+                        };
+
+                        let promo_amount: Option<Decimal> = match agreement.promo_id.clone() {
+                            None => { None }
+                            Some(code) => {
+                                use crate::schema::promos::dsl as promos_query;
+                                let promo = promos_query::promos
+                                    .find(&code)
+                                    .select(promos_query::amount)
+                                    .get_result::<Decimal>(&mut pool);
+                                let Ok(promo) = promo else {
+                                    return methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/modify: Database error loading promo")
+                                    )
+                                };
+                                Some(promo)
+                            }
+                        };
+
+                        // 1. mileage package revenue
+
+                        let mileage_package_cost = match body.mileage_package_id {
+                            None => {
+                                // didn't select mp
+                                Decimal::zero()
+                            }
+                            Some(mp_id) => {
+                                use schema::mileage_packages::dsl as mp_q;
+                                let mp_result = mp_q::mileage_packages
+                                    .filter(mp_q::is_active)
+                                    .find(mp_id)
+                                    .select((mp_q::miles, mp_q::discounted_rate))
+                                    .get_result::<(i32, i32)>(&mut pool);
+                                match mp_result {
+                                    Ok((mileage, discount_rate)) => {
+                                        methods::pricing::mileage_package_cost(
+                                            mileage, discount_rate, agreement.mileage_package_overwrite,
+                                            agreement.duration_rate, msrp_factor, agreement.mileage_conversion,
+                                        )
+                                    }
+                                    Err(_) => {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/modify: Database error loading mileage package"),
+                                        )
+                                    }
+                                }
+                            }
+                        };
+
+                        // 2. taxes
+
+                        use crate::schema::apartments_taxes::dsl as apartments_taxes_query;
+                        use crate::schema::taxes::dsl as t_q;
+
+                        let taxes = apartments_taxes_query::apartments_taxes
+                            .inner_join(t_q::taxes)
+                            .filter(apartments_taxes_query::apartment_id.eq(&req_apt.id))
+                            .select(t_q::taxes::all_columns())
+                            .get_results::<model::Tax>(&mut pool);
+
+                        let Ok(taxes) = taxes else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error loading apartment taxes")
+                            )
+                        };
+
+                        // 3. price
+
+                        let mut pricing_input = methods::pricing::PricingInput::for_reservation(
+                            trip_duration, agreement.duration_rate, msrp_factor, agreement.utilization_factor, &taxes,
+                        );
+                        pricing_input.protection_rates = methods::pricing::ProtectionRates {
+                            liability: ag_lia,
+                            pcdw: ag_pcdw,
+                            pcdw_ext: ag_pcdw_ext,
+                            rsa: ag_rsa,
+                            pai: ag_pai,
+                        };
+                        pricing_input.hours_using_reward = body.hours_using_reward;
+                        pricing_input.promo_amount = promo_amount;
+                        pricing_input.mileage_package_cost = mileage_package_cost;
+                        let price = methods::pricing::calculate(&pricing_input);
+
+                        let total_subject_to_rental_tax = price.total_subject_to_rental_tax;
+                        let total_stripe_amount_2dp = price.total;
+
+                        // 4. compare with what has been paid so far
+
+                        use schema::payments::dsl as pmt_q;
+                        let paid_amount = pmt_q::payments
+                            .filter(pmt_q::agreement_id.eq(agreement.id))
+                            .filter(pmt_q::payment_type.eq(model::PaymentType::Succeeded))
+                            .select(diesel::dsl::sum(pmt_q::amount - pmt_q::refund_amount))
+                            .get_result::<Option<Decimal>>(&mut pool);
+
+                        let Ok(paid_amount) = paid_amount else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database connection error at summing paid amount")
+                            )
+                        };
+                        let mut paid_amount = paid_amount.unwrap_or(Decimal::zero());
+                        paid_amount.rescale(2);
+
+                        let difference = total_stripe_amount_2dp - paid_amount;
+
+                        let mut modified_agreement = agreement.clone();
+                        modified_agreement.rsvp_pickup_time = body.start_time;
+                        modified_agreement.rsvp_drop_off_time = body.end_time;
+                        modified_agreement.liability_protection_rate = ag_lia;
+                        modified_agreement.pcdw_protection_rate = ag_pcdw;
+                        modified_agreement.pcdw_ext_protection_rate = ag_pcdw_ext;
+                        modified_agreement.rsa_protection_rate = ag_rsa;
+                        modified_agreement.pai_protection_rate = ag_pai;
+                        modified_agreement.msrp_factor = msrp_factor;
+                        modified_agreement.vehicle_id = req_vehicle.id;
+                        modified_agreement.location_id = req_location.id;
+                        modified_agreement.mileage_package_id = body.mileage_package_id;
+                        modified_agreement.minimum_earning_rate = total_subject_to_rental_tax;
+
+                        if !body.confirm {
+                            // RETURN: OK, price preview only
+                            let resp = helper_model::ModifyAgreementResponse {
+                                agreement: modified_agreement,
+                                previous_total: paid_amount,
+                                new_total: total_stripe_amount_2dp,
+                                difference,
+                                is_confirmed: false,
+                            };
+                            return methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                        }
+
+                        // save the new terms first, they are put back if the price difference cannot be settled
+
+                        use crate::schema::agreements_taxes::dsl as ag_tx_q;
+                        let previous_tax_ids = ag_tx_q::agreements_taxes
+                            .filter(ag_tx_q::agreement_id.eq(agreement.id))
+                            .select(ag_tx_q::tax_id)
+                            .get_results::<i32>(&mut pool);
+                        let Ok(previous_tax_ids) = previous_tax_ids else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: Database error loading agreement taxes")
+                            )
+                        };
+                        let new_tax_ids = taxes.iter().map(|tax| tax.id).collect::<Vec<i32>>();
+                        let updated_agreement = methods::agreement::save_with_taxes(&mut pool, &modified_agreement, &new_tax_ids);
+                        let Ok(updated_agreement) = updated_agreement else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/modify: could not save agreement updates")
+                            )
+                        };
+
+                        // settle the price difference
+
+                        if difference >= Decimal::new(50, 2) {
+                            use schema::payment_methods::dsl as payment_method_query;
+                            let pm_token = payment_method_query::payment_methods
+                                .find(&agreement.payment_method_id)
+                                .select(payment_method_query::token)
+                                .get_result::<String>(&mut pool);
+                            let Ok(pm_token) = pm_token else {
+                                if methods::agreement::save_with_taxes(&mut pool, &agreement, &previous_tax_ids).is_err() {
+                                    eprintln!("agreement/modify: DB error restoring agreement #{}", agreement.id);
+                                }
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/modify: Database error loading credit card")
+                                )
+                            };
+
+                            let description = "RSVP #".to_owned() + &*agreement.confirmation + " Modification";
+                            let pmi = integration::stripe_veygo::create_payment_intent(
+                                &user_in_request.stripe_id, &pm_token, difference.mantissa() as i64, PaymentIntentCaptureMethod::Automatic, &description
+                            ).await;
+
+                            match pmi {
+                                Ok(pmi) => {
+                                    let _ = diesel::update(payment_method_query::payment_methods.filter(payment_method_query::token.eq(&pm_token)))
+                                        .set(payment_method_query::last_used_date_time.eq(current_time))
+                                        .execute(&mut pool);
+
+                                    let new_payment = model::NewPayment {
+                                        payment_type: model::PaymentType::Succeeded,
+                                        amount: difference,
+                                        note: Some(String::from("Reservation modification")),
+                                        reference_number: Some(pmi.id.to_string()),
+                                        agreement_id: agreement.id,
+                                        renter_id: agreement.renter_id,
+                                        payment_method_id: Some(agreement.payment_method_id),
+                                        amount_authorized: difference,
+                                        capture_before: None,
+                                    };
+
+                                    let payment_result = diesel::insert_into(pmt_q::payments)
+                                        .values(&new_payment)
+                                        .execute(&mut pool);
+
+                                    if payment_result.is_err() {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/modify: DB saving modification payment error, payment collected")
+                                        )
+                                    }
+                                }
+                                Err(err) => {
+                                    if methods::agreement::save_with_taxes(&mut pool, &agreement, &previous_tax_ids).is_err() {
+                                        eprintln!("agreement/modify: DB error restoring agreement #{}", agreement.id);
+                                    }
+                                    return if err == VeygoError::CardDeclined {
+                                        methods::standard_replies::card_declined_402()
+                                    } else {
+                                        methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/modify: Stripe cannot process modification payment")
+                                        )
+                                    }
+                                }
+                            }
+                        } else if difference < Decimal::zero() {
+                            let refund_result = methods::agreement::refund_agreement_payments(agreement.id, &user_in_request.stripe_id, Some(-difference)).await;
+                            if refund_result.is_err() {
+                                if methods::agreement::save_with_taxes(&mut pool, &agreement, &previous_tax_ids).is_err() {
+                                    eprintln!("agreement/modify: DB error restoring agreement #{}", agreement.id);
+                                }
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/modify: Stripe error refunding price difference")
+                                )
+                            }
+                        }
+
+                        let reward_hours_delta = body.hours_using_reward - booked_reward_hours;
+                        if reward_hours_delta != Decimal::zero() {
+                            let new_reward_trans = model::NewRewardTransaction {
+                                agreement_id: Some(updated_agreement.id),
+                                renter_id: user_in_request.id,
+                                duration: reward_hours_delta,
+                            };
+
+                            let result = diesel::insert_into(reward_q::reward_transactions)
+                                .values(&new_reward_trans)
+                                .execute(&mut pool);
+                            if result.is_err() {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/modify: Failed to insert reward transaction record error")
+                                )
+                            }
+                        }
+
+                        let resp = helper_model::ModifyAgreementResponse {
+                            agreement: updated_agreement,
+                            previous_total: paid_amount,
+                            new_total: total_stripe_amount_2dp,
+                            difference,
+                            is_confirmed: true,
+                        };
+                        methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                    }
+                };
+            },
+        )
+}
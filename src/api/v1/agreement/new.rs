@@ -1,4 +1,3 @@
-use std::cmp::max;
 use crate::{integration, methods, model, proj_config, helper_model, schema, helper_model::VeygoError, connection_pool};
 use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
 use diesel::prelude::*;
@@ -6,7 +5,6 @@ use diesel::result::Error;
 use stripe_core::{PaymentIntentCaptureMethod};
 use warp::{Filter, Reply, http::{Method, StatusCode}};
 use rust_decimal::prelude::*;
-use warp::reply::with_status;
 
 pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
     warp::path("new")
@@ -29,25 +27,11 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                 }
 
                 let now = Utc::now();
-                let next_quarter = {
-                    let minute = now.minute();
-                    let next_minute = ((minute / 15) + 1) * 15;
-                    let base = now
-                        .with_second(0)
-                        .and_then(|dt| dt.with_nanosecond(0))
-                        .unwrap();
-
-                    if next_minute == 60 {
-                        (base + Duration::hours(1)).with_minute(0).unwrap()
-                    } else {
-                        base.with_minute(next_minute).unwrap()
-                    }
-                };
-                let min_start_time = next_quarter + Duration::minutes(15);
-                let max_start_time = min_start_time + Duration::weeks(6);
+                let min_start_time = methods::availability::earliest_start_time(now);
+                let max_start_time = methods::availability::latest_start_time(now);
                 let trip_duration = body.end_time - body.start_time;
-                let min_trip_duration = Duration::minutes(30);
-                let max_trip_duration = Duration::weeks(4);
+                let min_trip_duration = methods::availability::MIN_TRIP_DURATION;
+                let max_trip_duration = methods::availability::MAX_TRIP_DURATION;
 
                 let is_quarter_aligned = |dt: DateTime<Utc>| {
                     dt.second() == 0 && dt.nanosecond() == 0 && dt.minute() % 15 == 0
@@ -294,6 +278,7 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             .filter(location_query::is_operational)
                             .filter(vehicle_query::id.eq(&body.vehicle_id))
                             .filter(vehicle_query::available)
+                            .filter(vehicle_query::maintenance_hold.eq(false))
                             .select(
                                 (
                                     vehicle_query::vehicles::all_columns(),
@@ -326,83 +311,18 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                         let verified_promo: Option<model::Promo> = match body.promo_code.clone() {
                             None => { None }
                             Some(code) => {
-                                use crate::schema::promos::dsl as promos_query;
-                                let promo = promos_query::promos
-                                    .filter(promos_query::code.eq(&code))
-                                    .filter(promos_query::is_enabled)
-                                    .filter(promos_query::exp.gt(&body.start_time))
-                                    .get_result::<model::Promo>(&mut pool);
-                                let promo: model::Promo = match promo {
-                                    Ok(promo) => { promo }
-                                    Err(err) => {
-                                        return match err {
-                                            Error::NotFound => {
-                                                methods::standard_replies::promo_code_not_allowed_response(&code)
-                                            }
-                                            _ => {
-                                                methods::standard_replies::internal_server_error_response_500(
-                                                    String::from("agreement/new: Database error loading promo")
-                                                )
-                                            }
-                                        }
+                                let promo = methods::agreement::verify_promo(&code, access_token.user_id, &req_apt, body.start_time).await;
+                                match promo {
+                                    Ok(Some(promo)) => Some(promo),
+                                    Ok(None) => {
+                                        return methods::standard_replies::promo_code_not_allowed_response(&code);
                                     }
-                                };
-
-                                // check if this renter already uses the promo code
-                                use crate::schema::agreements::dsl as agreement_query;
-                                let count_of_this_renter_usage = agreement_query::agreements
-                                    .filter(agreement_query::promo_id.eq(Some(&promo.code)))
-                                    .filter(agreement_query::status.ne(model::AgreementStatus::Canceled))
-                                    .filter(agreement_query::renter_id.eq(&access_token.user_id))
-                                    .count()
-                                    .get_result::<i64>(&mut pool);
-                                let Ok(count_of_this_renter_usage) = count_of_this_renter_usage else {
-                                    return methods::standard_replies::internal_server_error_response_500(
-                                        String::from("agreement/new: Database error counting renter promo usage")
-                                    )
-                                };
-                                if count_of_this_renter_usage >= 1 {
-                                    return methods::standard_replies::promo_code_not_allowed_response(&code);
-                                }
-
-                                // check if someone else already uses the promo code when it's one-time only
-                                if promo.is_one_time {
-                                    let count_of_agreements = agreement_query::agreements
-                                        .filter(agreement_query::promo_id.eq(Some(&promo.code)))
-                                        .filter(agreement_query::status.ne(model::AgreementStatus::Canceled))
-                                        .count()
-                                        .get_result::<i64>(&mut pool);
-                                    let Ok(count_of_agreements) = count_of_agreements else {
+                                    Err(_) => {
                                         return methods::standard_replies::internal_server_error_response_500(
-                                            String::from("agreement/new: Database error counting promo usage")
+                                            String::from("agreement/new: Database error verifying promo")
                                         )
-                                    };
-                                    if count_of_agreements >= 1 {
-                                        return methods::standard_replies::promo_code_not_allowed_response(&code);
                                     }
                                 }
-
-                                // check if the promo code is for a specific renter
-                                if let Some(specified_user_id) = promo.user_id &&
-                                    access_token.user_id != specified_user_id
-                                {
-                                    return methods::standard_replies::promo_code_not_allowed_response(&code);
-                                }
-                                // check if the promo code is for a specific apartment
-                                if let Some(specified_apartment_id) = promo.apt_id &&
-                                    !(req_apt.id == specified_apartment_id && req_apt.uni_id != 1)
-                                {
-                                    return methods::standard_replies::promo_code_not_allowed_response(&code);
-                                }
-                                // check if the promo code is for a specific university
-                                if let Some(specified_uni_id) = promo.uni_id &&
-                                    !(req_apt.id == specified_uni_id && req_apt.uni_id == 1 ||
-                                        req_apt.uni_id == specified_uni_id)
-                                {
-                                    return methods::standard_replies::promo_code_not_allowed_response(&code);
-                                }
-
-                                Some(promo)
                             }
                         };
 
@@ -509,56 +429,21 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             }
                         };
 
-                        if body.hours_using_reward > Decimal::ZERO {
-
-                            let total_duration = body.end_time - body.start_time;
-                            let total_duration_in_hours = Decimal::new(total_duration.num_minutes(), 0)
-                                / Decimal::new(60, 0);
-
-                            if body.hours_using_reward > total_duration_in_hours {
-                                let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
-                                    title: String::from("Booking Not Allowed"),
-                                    message: String::from("You are trying to redeem more hours than the rental period."),
-                                };
-                                // RETURN: FORBIDDEN
-                                return Ok((with_status(warp::reply::json(&err_msg), StatusCode::FORBIDDEN).into_response(),));
-                            }
-
-                            let init_allowance = user_in_request.plan_total_availability;
-
-                            let (week_start, week_end_exclusive) = {
-                                let days_from_monday = current_naive_date.weekday().num_days_from_monday() as i64;
-                                let monday = current_naive_date - Duration::days(days_from_monday);
-                                let monday_start = DateTime::<Utc>::from_naive_utc_and_offset(
-                                    monday.and_hms_opt(0, 0, 0).unwrap(),
-                                    Utc,
-                                );
-                                let next_monday = monday_start + Duration::days(7);
-                                (monday_start, next_monday)
+                        let reward_check = methods::agreement::check_reward_hours(
+                            &user_in_request, body.hours_using_reward, body.end_time - body.start_time, current_time, None,
+                        ).await;
+                        let Ok(reward_check) = reward_check else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/new: Database error summing used reward hours"),
+                            )
+                        };
+                        if let Some(message) = reward_check.rejection_message() {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Booking Not Allowed"),
+                                message: String::from(message),
                             };
-
-                            use crate::schema::reward_transactions::dsl as reward_q;
-                            let used_free_hours = reward_q::reward_transactions
-                                .filter(reward_q::renter_id.eq(&access_token.user_id))
-                                .filter(reward_q::transaction_time.ge(week_start))
-                                .filter(reward_q::transaction_time.lt(week_end_exclusive))
-                                .select(diesel::dsl::sum(reward_q::duration))
-                                .first::<Option<Decimal>>(&mut pool);
-                            if used_free_hours.is_err() {
-                                return methods::standard_replies::internal_server_error_response_500(
-                                    String::from("agreement/new: Database error summing used reward hours"),
-                                )
-                            }
-                            let used_free_hours = used_free_hours.unwrap().unwrap_or(Decimal::zero());
-
-                            if init_allowance - used_free_hours < body.hours_using_reward {
-                                let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
-                                    title: String::from("Booking Not Allowed"),
-                                    message: String::from("You have exceeded your free hours limit. Please upgrade your plan. "),
-                                };
-                                // RETURN: FORBIDDEN
-                                return Ok((with_status(warp::reply::json(&err_msg), StatusCode::FORBIDDEN).into_response(),));
-                            }
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
                         }
 
                         use schema::payment_methods::dsl as payment_method_query;
@@ -615,7 +500,20 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             )
                         };
 
-                        if is_conflict {
+                        use crate::schema::vehicle_blackouts::dsl as vb_q;
+                        let is_blacked_out = diesel::select(diesel::dsl::exists(
+                            vb_q::vehicle_blackouts
+                                .filter(vb_q::vehicle_id.eq(&body.vehicle_id))
+                                .filter(vb_q::start_time.lt(end_time_buffered))
+                                .filter(vb_q::end_time.gt(start_time_buffered))
+                        )).get_result::<bool>(&mut pool);
+                        let Ok(is_blacked_out) = is_blacked_out else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/new: Database error checking vehicle blackouts")
+                            )
+                        };
+
+                        if is_conflict || is_blacked_out {
                             let err_msg = helper_model::ErrorResponse {
                                 title: "Vehicle Unavailable".to_string(),
                                 message: "Please try again later. ".to_string(),
@@ -657,41 +555,7 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             }
                         };
 
-                        // 1. total rental revenue
-                        let total_hours_reserved = Decimal::new(trip_duration.num_minutes(), 0) / Decimal::new(60, 0);
-                        let mut total_hours_reserved_round_up = total_hours_reserved.round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
-                        total_hours_reserved_round_up.rescale(0);
-                        let total_hours_reserved_round_up_int = total_hours_reserved_round_up.mantissa() as i32;
-
-                        let billable_days_count: i32 = methods::rental_rate::billable_days_count(trip_duration);
-                        let billable_duration_hours: Decimal = methods::rental_rate::calculate_billable_duration_hours(trip_duration);
-
-                        let duration_revenue_before_reward = billable_duration_hours * req_apt.duration_rate * req_vehicle.msrp_factor * rate_offer;
-
-                        let avg_hourly_rate = duration_revenue_before_reward / total_hours_reserved;
-
-                        let duration_revenue = duration_revenue_before_reward - avg_hourly_rate * body.hours_using_reward;
-
-                        let duration_revenue_after_promo = match verified_promo.clone() {
-                            None => { duration_revenue }
-                            Some(promo) => {
-                                max(Decimal::zero(), duration_revenue - promo.amount)
-                            }
-                        };
-
-                        // 2. total insurance revenue
-
-                        let insurance_revenue = {
-                            total_hours_reserved_round_up * (
-                                ag_lia.unwrap_or(Decimal::zero())
-                                + ag_pcdw.unwrap_or(Decimal::zero())
-                                + ag_pcdw_ext.unwrap_or(Decimal::zero())
-                                + ag_rsa.unwrap_or(Decimal::zero())
-                                + ag_pai.unwrap_or(Decimal::zero())
-                            )
-                        };
-
-                        // 3. mileage package revenue
+                        // 1. mileage package revenue
 
                         let mileage_package_cost = match body.mileage_package_id {
                             None => {
@@ -707,13 +571,10 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                                     .get_result::<(i32, i32)>(&mut pool);
                                 match mp_result {
                                     Ok((mileage, discount_rate)) => {
-                                        let base_rate_for_mp = if let Some(overwrite) = req_apt.mileage_package_overwrite {
-                                            overwrite
-                                        } else {
-                                            req_apt.duration_rate * req_vehicle.msrp_factor * req_apt.mileage_conversion
-                                        };
-
-                                        base_rate_for_mp * Decimal::new(mileage as i64, 0) * Decimal::new(discount_rate as i64, 2)
+                                        methods::pricing::mileage_package_cost(
+                                            mileage, discount_rate, req_apt.mileage_package_overwrite,
+                                            req_apt.duration_rate, req_vehicle.msrp_factor, req_apt.mileage_conversion,
+                                        )
                                     }
                                     Err(_) => {
                                         return methods::standard_replies::internal_server_error_response_500(
@@ -724,7 +585,7 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             }
                         };
 
-                        // 4. taxes
+                        // 2. taxes
 
                         use crate::schema::apartments_taxes::dsl as apartments_taxes_query;
                         use crate::schema::taxes::dsl as t_q;
@@ -741,62 +602,26 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             )
                         };
 
-                        let mut local_tax_rate_percent_sales = Decimal::zero();
-                        let mut local_tax_rate_percent_non_sales = Decimal::zero();
-                        let mut local_tax_rate_daily = Decimal::zero();
-                        let mut local_tax_rate_fixed = Decimal::zero();
-                        let mut certain_taxes_needed_to_apply_sales_tax = Decimal::zero();
-
-                        for tax_obj in &taxes {
-                            let mut tax_needed_to_be_counted = false;
-                            if let Some(threshold) = tax_obj.threshold && let Some(is_lower) = tax_obj.is_lower {
-                                if is_lower && total_hours_reserved_round_up_int < threshold || !is_lower && total_hours_reserved_round_up_int >= threshold {
-                                    tax_needed_to_be_counted = true;
-                                }
-                            } else {
-                                tax_needed_to_be_counted = true;
-                            }
-
-                            if tax_needed_to_be_counted {
-                                match tax_obj.tax_type {
-                                    model::TaxType::Percent => {
-                                        if tax_obj.is_sales_tax {
-                                            local_tax_rate_percent_sales += tax_obj.multiplier;
-                                        } else {
-                                            local_tax_rate_percent_non_sales += tax_obj.multiplier;
-                                        }
-                                    },
-                                    model::TaxType::Daily => {
-                                        if tax_obj.is_sales_tax {
-                                            certain_taxes_needed_to_apply_sales_tax += tax_obj.multiplier * Decimal::new(billable_days_count as i64, 0);
-                                        }
-                                        local_tax_rate_daily += tax_obj.multiplier;
-                                    }
-                                    model::TaxType::Fixed => {
-                                        if tax_obj.is_sales_tax {
-                                            certain_taxes_needed_to_apply_sales_tax += tax_obj.multiplier;
-                                        }
-                                        local_tax_rate_fixed += tax_obj.multiplier;
-                                    }
-                                }
-                            }
-                        }
-
-                        // 5. subtotals
-                        let total_subject_to_rental_tax = duration_revenue_after_promo
-                            + insurance_revenue + mileage_package_cost;
-                        let total_subject_to_sales_tax = total_subject_to_rental_tax + certain_taxes_needed_to_apply_sales_tax;
-
-                        let total_percentage_tax = total_subject_to_rental_tax * local_tax_rate_percent_non_sales
-                            + total_subject_to_sales_tax * local_tax_rate_percent_sales;
-                        let total_daily_tax = Decimal::new(billable_days_count as i64, 0) * local_tax_rate_daily;
-                        let total_fixed_tax = local_tax_rate_fixed;
-
-                        let total_stripe_amount = total_subject_to_rental_tax
-                            + total_percentage_tax + total_daily_tax + total_fixed_tax;
-                        let mut total_stripe_amount_2dp = total_stripe_amount.round_dp(2);
-                        (&mut total_stripe_amount_2dp).rescale(2);
-                        let total_stripe_amount_cent = total_stripe_amount_2dp.mantissa();
+                        // 3. price
+
+                        let mut pricing_input = methods::pricing::PricingInput::for_reservation(
+                            trip_duration, req_apt.duration_rate, req_vehicle.msrp_factor, rate_offer, &taxes,
+                        );
+                        pricing_input.protection_rates = methods::pricing::ProtectionRates {
+                            liability: ag_lia,
+                            pcdw: ag_pcdw,
+                            pcdw_ext: ag_pcdw_ext,
+                            rsa: ag_rsa,
+                            pai: ag_pai,
+                        };
+                        pricing_input.hours_using_reward = body.hours_using_reward;
+                        pricing_input.promo_amount = verified_promo.as_ref().map(|promo| promo.amount);
+                        pricing_input.mileage_package_cost = mileage_package_cost;
+
+                        let price = methods::pricing::calculate(&pricing_input);
+                        let total_subject_to_rental_tax = price.total_subject_to_rental_tax;
+                        let total_stripe_amount_2dp = price.total;
+                        let total_stripe_amount_cent = price.total_cents();
 
                         // insert agreement
 
@@ -0,0 +1,335 @@
+use crate::{methods, model, helper_model, schema, helper_model::VeygoError, connection_pool};
+use chrono::{DateTime, Timelike, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use warp::{Filter, Reply, http::{Method, StatusCode}};
+use rust_decimal::prelude::*;
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("quote")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::QuoteRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+
+                if body.hours_using_reward < Decimal::zero() {
+                    return methods::standard_replies::bad_request_400("Invalid reward hour request")
+                }
+
+                let trip_duration = body.end_time - body.start_time;
+                let min_trip_duration = methods::availability::MIN_TRIP_DURATION;
+                let max_trip_duration = methods::availability::MAX_TRIP_DURATION;
+
+                let is_quarter_aligned = |dt: DateTime<Utc>| {
+                    dt.second() == 0 && dt.nanosecond() == 0 && dt.minute() % 15 == 0
+                };
+
+                if !is_quarter_aligned(body.start_time)
+                    || !is_quarter_aligned(body.end_time)
+                    || trip_duration < min_trip_duration
+                    || trip_duration > max_trip_duration
+                {
+                    // RETURN: BAD_REQUEST
+                    return methods::standard_replies::bad_request_400("Time is invalid")
+                }
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    // RETURN: UNAUTHORIZED
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                let user_id = match user_id_parsed_result {
+                    Ok(int) => {
+                        int
+                    }
+                    Err(_) => {
+                        // RETURN: UNAUTHORIZED
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: String::from(token_and_id[0]),
+                };
+                let if_token_valid_result = methods::tokens::verify_user_token(&access_token.user_id, &access_token.token).await;
+
+                match if_token_valid_result {
+                    Err(e) => {
+                        match e {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/quote: Token verification unexpected error")
+                                )
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/quote: Token extension failed (returned false)")
+                                    );
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/quote: Token extension error")
+                                );
+                            }
+                        }
+
+                        let user_in_request = methods::user::get_user_by_id(&access_token.user_id).await;
+                        let Ok(user_in_request) = user_in_request else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/quote: Database error loading renter")
+                            )
+                        };
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        let current_time = Utc::now();
+
+                        use schema::apartments::dsl as apartment_query;
+                        use schema::vehicles::dsl as vehicle_query;
+                        use schema::locations::dsl as location_query;
+                        let vehicle_result = vehicle_query::vehicles
+                            .inner_join(location_query::locations
+                                .inner_join(apartment_query::apartments)
+                            )
+                            .filter(apartment_query::id.ne(1))
+                            .filter(apartment_query::is_operating)
+                            .filter(location_query::is_operational)
+                            .filter(vehicle_query::id.eq(&body.vehicle_id))
+                            .filter(vehicle_query::available)
+                            .filter(vehicle_query::maintenance_hold.eq(false))
+                            .select(
+                                (
+                                    vehicle_query::vehicles::all_columns(),
+                                    apartment_query::apartments::all_columns()
+                                )
+                            )
+                            .get_result::<(model::Vehicle, model::Apartment)>(&mut pool);
+
+                        let (req_vehicle, req_apt) = match vehicle_result {
+                            Ok(result) => {
+                                result
+                            }
+                            Err(err) => {
+                                return match err {
+                                    Error::NotFound => {
+                                        let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                            title: String::from("Booking Not Allowed"),
+                                            message: String::from("Booking this vehicle is currently not allowed. Please try again later. "),
+                                        };
+                                        methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                                    }
+                                    _ => {
+                                        methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/quote: Database error loading vehicle")
+                                        )
+                                    }
+                                }
+                            }
+                        };
+
+                        let is_auth = user_in_request.is_authorized_for(&req_apt).await;
+                        let is_auth = match is_auth {
+                            Ok(is_auth) => { is_auth }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/quote: Database error loading authorization")
+                                )
+                            }
+                        };
+
+                        if !is_auth {
+                            return methods::standard_replies::apartment_not_allowed_response(req_apt.id);
+                        }
+
+                        let verified_promo: Option<model::Promo> = match body.promo_code.clone() {
+                            None => { None }
+                            Some(code) => {
+                                let promo = methods::agreement::verify_promo(&code, access_token.user_id, &req_apt, body.start_time).await;
+                                match promo {
+                                    Ok(Some(promo)) => Some(promo),
+                                    Ok(None) => {
+                                        return methods::standard_replies::promo_code_not_allowed_response(&code);
+                                    }
+                                    Err(_) => {
+                                        return methods::standard_replies::internal_server_error_response_500(
+                                            String::from("agreement/quote: Database error verifying promo")
+                                        )
+                                    }
+                                }
+                            }
+                        };
+
+                        let protection_not_offered = |name: &str| {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Options Not Allowed"),
+                                message: format!("{} is not offered at this location. ", name),
+                            };
+                            methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        };
+
+                        if body.liability && req_apt.liability_protection_rate.is_none() {
+                            return protection_not_offered("Liability insurance");
+                        }
+                        if body.rsa && req_apt.rsa_protection_rate.is_none() {
+                            return protection_not_offered("Roadside Assistance");
+                        }
+                        if body.pcdw && req_apt.pcdw_protection_rate.is_none() {
+                            return protection_not_offered("Partial Collision Damage Waiver");
+                        }
+                        if body.pcdw_ext && req_apt.pcdw_ext_protection_rate.is_none() {
+                            return protection_not_offered("Limited Collision Damage Waiver");
+                        }
+                        if body.pai && req_apt.pai_protection_rate.is_none() {
+                            return protection_not_offered("Personal Accident Insurance");
+                        }
+
+                        let reward_check = methods::agreement::check_reward_hours(
+                            &user_in_request, body.hours_using_reward, trip_duration, current_time, None,
+                        ).await;
+                        let Ok(reward_check) = reward_check else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/quote: Database error summing used reward hours"),
+                            )
+                        };
+                        if let Some(message) = reward_check.rejection_message() {
+                            let err_msg: helper_model::ErrorResponse = helper_model::ErrorResponse {
+                                title: String::from("Booking Not Allowed"),
+                                message: String::from(message),
+                            };
+                            // RETURN: FORBIDDEN
+                            return methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                        }
+
+                        // Calculate total cost, same inputs agreement/new will use at booking
+
+                        // 0. rate offer
+                        let rate_offer: Decimal = {
+                            use schema::rate_offers::dsl as ro_q;
+                            let non_expired_rate_offer_dec = ro_q::rate_offers
+                                .filter(ro_q::exp.gt(current_time))
+                                .filter(ro_q::renter_id.eq(user_in_request.id))
+                                .find(body.rate_offer_id)
+                                .select(ro_q::multiplier)
+                                .get_result::<Decimal>(&mut pool);
+
+                            match non_expired_rate_offer_dec {
+                                Ok(offer) => { offer }
+                                Err(err) => {
+                                    return match err {
+                                        Error::NotFound => {
+                                            // rate offer not valid or not found
+                                            let err_msg = helper_model::ErrorResponse {
+                                                title: "Rate Not Available".to_string(),
+                                                message: "Please get a new quote".to_string(),
+                                            };
+                                            methods::standard_replies::response_with_obj(err_msg, StatusCode::FORBIDDEN)
+                                        }
+                                        _ => {
+                                            // diesel db error
+                                            methods::standard_replies::internal_server_error_response_500(String::from("agreement/quote: Database error loading rate offer"))
+                                        }
+                                    }
+                                }
+                            }
+                        };
+
+                        // 1. mileage package revenue
+
+                        let mileage_package_cost = match body.mileage_package_id {
+                            None => {
+                                // didn't select mp
+                                Decimal::zero()
+                            }
+                            Some(mp_id) => {
+                                use schema::mileage_packages::dsl as mp_q;
+                                let mp_result = mp_q::mileage_packages
+                                    .filter(mp_q::is_active)
+                                    .find(mp_id)
+                                    .select((mp_q::miles, mp_q::discounted_rate))
+                                    .get_result::<(i32, i32)>(&mut pool);
+                                match mp_result {
+                                    Ok((mileage, discount_rate)) => {
+                                        methods::pricing::mileage_package_cost(
+                                            mileage, discount_rate, req_apt.mileage_package_overwrite,
+                                            req_apt.duration_rate, req_vehicle.msrp_factor, req_apt.mileage_conversion,
+                                        )
+                                    }
+                                    Err(err) => {
+                                        return match err {
+                                            Error::NotFound => {
+                                                methods::standard_replies::bad_request_400("Mileage package is not available")
+                                            }
+                                            _ => {
+                                                methods::standard_replies::internal_server_error_response_500(
+                                                    String::from("agreement/quote: Database error loading mileage package"),
+                                                )
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        };
+
+                        // 2. taxes
+
+                        use crate::schema::apartments_taxes::dsl as apartments_taxes_query;
+                        use crate::schema::taxes::dsl as t_q;
+
+                        let taxes = apartments_taxes_query::apartments_taxes
+                            .inner_join(t_q::taxes)
+                            .filter(apartments_taxes_query::apartment_id.eq(&req_apt.id))
+                            .select(t_q::taxes::all_columns())
+                            .get_results::<model::Tax>(&mut pool);
+
+                        let Ok(taxes) = taxes else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/quote: Database error loading apartment taxes")
+                            )
+                        };
+
+                        // 3. price
+
+                        let mut pricing_input = methods::pricing::PricingInput::for_reservation(
+                            trip_duration, req_apt.duration_rate, req_vehicle.msrp_factor, rate_offer, &taxes,
+                        );
+                        pricing_input.protection_rates = methods::pricing::ProtectionRates {
+                            liability: req_apt.liability_protection_rate.filter(|_| body.liability),
+                            pcdw: req_apt.pcdw_protection_rate.filter(|_| body.pcdw),
+                            pcdw_ext: req_apt.pcdw_ext_protection_rate.filter(|_| body.pcdw_ext),
+                            rsa: req_apt.rsa_protection_rate.filter(|_| body.rsa),
+                            pai: req_apt.pai_protection_rate.filter(|_| body.pai),
+                        };
+                        pricing_input.hours_using_reward = body.hours_using_reward;
+                        pricing_input.promo_amount = verified_promo.as_ref().map(|promo| promo.amount);
+                        pricing_input.mileage_package_cost = mileage_package_cost;
+
+                        let price = methods::pricing::calculate(&pricing_input);
+                        methods::standard_replies::response_with_obj(price, StatusCode::OK)
+                    }
+                }
+            },
+        )
+}
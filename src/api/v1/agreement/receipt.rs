@@ -0,0 +1,133 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use warp::{Filter, Rejection, Reply};
+use warp::http::{Method, StatusCode};
+use crate::{helper_model, methods, model, schema, connection_pool};
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!(String / "receipt")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(async move |conf_id: String, method: Method, auth: String, user_agent: String| {
+            if method != Method::GET {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            let token_and_id = auth.split("$").collect::<Vec<&str>>();
+            if token_and_id.len() != 2 {
+                // RETURN: UNAUTHORIZED
+                return methods::tokens::token_invalid_return();
+            }
+            let user_id_parsed_result = token_and_id[1].parse::<i32>();
+            let user_id = match user_id_parsed_result {
+                Ok(int) => {
+                    int
+                }
+                Err(_) => {
+                    // RETURN: UNAUTHORIZED
+                    return methods::tokens::token_invalid_return();
+                }
+            };
+
+            let access_token = model::RequestToken { user_id, token: token_and_id[0].parse().unwrap() };
+            let if_token_valid_result = methods::tokens::verify_user_token(&access_token.user_id, &access_token.token).await;
+
+            match if_token_valid_result {
+                Err(e) => {
+                    match e {
+                        helper_model::VeygoError::TokenFormatError => {
+                            methods::tokens::token_not_hex_warp_return()
+                        }
+                        helper_model::VeygoError::InvalidToken => {
+                            methods::tokens::token_invalid_return()
+                        }
+                        _ => {
+                            methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/receipt: Token verification unexpected error"),
+                            )
+                        }
+                    }
+                }
+                Ok(valid_token) => {
+                    // token is valid
+                    let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                    match ext_result {
+                        Ok(bool) => {
+                            if !bool {
+                                return methods::standard_replies::internal_server_error_response_500(
+                                    String::from("agreement/receipt: Token extension failed (returned false)"),
+                                );
+                            }
+                        }
+                        Err(_) => {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/receipt: Token extension error"),
+                            );
+                        }
+                    }
+
+                    let mut pool = connection_pool().await.get().unwrap();
+
+                    use schema::agreements::dsl as ag_q;
+                    use schema::locations::dsl as loc_q;
+                    let agreement = ag_q::agreements
+                        .inner_join(loc_q::locations)
+                        .filter(ag_q::confirmation.eq(conf_id.to_uppercase()))
+                        .select((ag_q::agreements::all_columns(), loc_q::apartment_id))
+                        .get_result::<(model::Agreement, i32)>(&mut pool);
+
+                    let (agreement, apt_id) = match agreement {
+                        Ok(agreement) => agreement,
+                        Err(e) => {
+                            return match e {
+                                Error::NotFound => {
+                                    methods::standard_replies::agreement_not_allowed_response()
+                                }
+                                _ => {
+                                    methods::standard_replies::internal_server_error_response_500(
+                                        String::from("agreement/receipt: Database error loading agreement"),
+                                    )
+                                }
+                            };
+                        }
+                    };
+
+                    if agreement.renter_id != user_id {
+                        let admin = methods::user::get_user_by_id(&user_id).await;
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(
+                                String::from("agreement/receipt: Database error loading admin"),
+                            );
+                        };
+                        if !(admin.is_operational_admin() || admin.is_operational_manager() && admin.apartment_id == apt_id) {
+                            return methods::standard_replies::agreement_not_allowed_response()
+                        }
+                    }
+
+                    // Receipts are only issued once the trip has been checked in
+                    if agreement.vehicle_snapshot_after.is_none() || agreement.actual_drop_off_time.is_none() {
+                        let err_msg = helper_model::ErrorResponse {
+                            title: String::from("Receipt Not Available"),
+                            message: String::from("A receipt will be available after the trip is completed. "),
+                        };
+                        return methods::standard_replies::response_with_obj(err_msg, StatusCode::NOT_FOUND)
+                    }
+
+                    let receipt = methods::receipt::build_receipt(&agreement).await;
+                    let Ok(receipt) = receipt else {
+                        return methods::standard_replies::internal_server_error_response_500(
+                            String::from("agreement/receipt: Database error building receipt"),
+                        );
+                    };
+
+                    let pdf = methods::receipt::render_pdf(&receipt);
+                    let file_name = format!("attachment; filename=\"Veygo-Receipt-{}.pdf\"", agreement.confirmation);
+                    let reply = warp::reply::with_header(pdf, "content-type", "application/pdf");
+                    let reply = warp::reply::with_header(reply, "content-disposition", file_name);
+                    Ok((warp::reply::with_status(reply, StatusCode::OK).into_response(),))
+                }
+            }
+        })
+}
@@ -1,6 +1,6 @@
 use diesel::result::Error;
 use warp::{Filter, Reply, http::Method, http::StatusCode};
-use crate::{schema, helper_model, methods, model, connection_pool, integration};
+use crate::{schema, helper_model, methods, model, connection_pool};
 use diesel::prelude::*;
 
 pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
@@ -127,40 +127,15 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                         )
                     };
 
-                    match vehicle_remote_mgmt {
-                        model::RemoteMgmtType::Tesla => {
-                            let _handler = tokio::spawn(async move {
-                                // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                                let status_path = format!("/api/1/vehicles/{}", mgmt_id);
-
-                                for i in 0..16 {
-                                    if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                        if let Ok(body_text) = response.text().await {
-                                            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                                let state = json
-                                                    .get("response")
-                                                    .and_then(|r| r.get("state"))
-                                                    .and_then(|s| s.as_str())
-                                                    .unwrap_or("");
-                                                if state == "online" {
-                                                    break;
-                                                }
-                                                // Only on the first iteration, if offline, send wake_up once
-                                                if i == 0 {
-                                                    let wake_path = format!("/api/1/vehicles/{}/wake_up", mgmt_id);
-                                                    let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                }
-                                // 2) Proceed to lock/unlock once online (or after timeout anyway)
-                                let cmd_path = format!("/api/1/vehicles/{}/command/door_unlock", mgmt_id);
-                                let _result = integration::tesla_veygo::tesla_make_request(Method::POST, &cmd_path, None).await;
-                            });
-                        }
-                        _ => {}
+                    if let Ok(remote) = methods::remote_vehicle::for_vehicle(vehicle_remote_mgmt, &mgmt_id) {
+                        let origin = methods::remote_command::CommandOrigin {
+                            vehicle_id: agreement.vehicle_id,
+                            agreement_id: Some(agreement.id),
+                            triggered_by: Some(user_id),
+                        };
+                        let _handler = tokio::spawn(async move {
+                            let _result = methods::remote_command::send_logged(remote.as_ref(), model::RemoteCommandType::Unlock, origin, 1).await;
+                        });
                     }
 
                     methods::standard_replies::response_with_obj(agreement, StatusCode::OK)
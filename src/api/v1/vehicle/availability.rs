@@ -17,6 +17,29 @@ struct AvailabilityData {
     #[serde(with = "chrono::serde::ts_seconds")]
     end_time: DateTime<Utc>,
     apartment_id: i32,
+    // the vehicle the renter wants, used to rank suggestions when it is taken
+    #[serde(default)]
+    vehicle_id: Option<i32>,
+    // optional feature filters
+    #[serde(default)]
+    min_capacity: Option<i32>,
+    #[serde(default)]
+    min_doors: Option<i32>,
+    #[serde(default)]
+    min_small_bags: Option<i32>,
+    #[serde(default)]
+    min_large_bags: Option<i32>,
+    #[serde(default)]
+    carplay: Option<bool>,
+    #[serde(default)]
+    lane_keep: Option<bool>,
+    #[serde(default)]
+    requires_own_insurance: Option<bool>,
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    max_price: Option<Decimal>,
+    // "price" (lowest first) or "seats" (most first), available vehicles always come first
+    #[serde(default)]
+    sort_by: Option<String>,
 }
 
 pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
@@ -162,48 +185,95 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
 
                         use crate::schema::locations::dsl as locations_query;
                         use crate::schema::vehicles::dsl as vehicles_query;
-                        let all_vehicles = vehicles_query::vehicles
+                        let mut vehicles_filtered = vehicles_query::vehicles
                             .filter(vehicles_query::available)
+                            .filter(vehicles_query::maintenance_hold.eq(false))
                             .inner_join(locations_query::locations)
                             .filter(locations_query::apartment_id.eq(&body.apartment_id))
                             .select((vehicles_query::vehicles::all_columns(), locations_query::locations::all_columns()))
+                            .into_boxed();
+                        if let Some(min_capacity) = body.min_capacity {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::capacity.ge(min_capacity));
+                        }
+                        if let Some(min_doors) = body.min_doors {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::doors.ge(min_doors));
+                        }
+                        if let Some(min_small_bags) = body.min_small_bags {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::small_bags.ge(min_small_bags));
+                        }
+                        if let Some(min_large_bags) = body.min_large_bags {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::large_bags.ge(min_large_bags));
+                        }
+                        if let Some(carplay) = body.carplay {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::carplay.eq(carplay));
+                        }
+                        if let Some(lane_keep) = body.lane_keep {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::lane_keep.eq(lane_keep));
+                        }
+                        if let Some(requires_own_insurance) = body.requires_own_insurance {
+                            vehicles_filtered = vehicles_filtered.filter(vehicles_query::requires_own_insurance.eq(requires_own_insurance));
+                        }
+                        let all_vehicles = vehicles_filtered
                             .get_results::<(model::Vehicle, model::Location)>(&mut pool);
                         let Ok(all_vehicles) = all_vehicles else {
                             return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading vehicles and locations"))
                         };
 
+                        let rate_offer = methods::availability::current_rate_offer(user_id, body.apartment_id).await;
+                        let Ok(rate_offer) = rate_offer else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading rate offer"))
+                        };
+
+                        use crate::schema::apartments_taxes::dsl as at_q;
+                        use crate::schema::taxes::dsl as t_q;
+
+                        let taxes = at_q::apartments_taxes
+                            .inner_join(t_q::taxes)
+                            .filter(at_q::apartment_id.eq(body.apartment_id))
+                            .select(t_q::taxes::all_columns())
+                            .get_results::<Tax>(&mut pool);
+
+                        let Ok(taxes) = taxes else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading taxes"))
+                        };
+
+                        // Estimated trip total per vehicle, from the apartment rate, the vehicle's MSRP factor and the offer
+                        let trip_duration = body.end_time - body.start_time;
+                        let estimate_for = |vehicle: &model::Vehicle| {
+                            let pricing_input = methods::pricing::PricingInput::for_reservation(
+                                trip_duration, apt.duration_rate, vehicle.msrp_factor, rate_offer.multiplier, &taxes,
+                            );
+                            methods::pricing::calculate(&pricing_input).total
+                        };
+                        let all_vehicles = all_vehicles
+                            .into_iter()
+                            .map(|(vehicle, location)| {
+                                let estimated_price = estimate_for(&vehicle);
+                                (vehicle, location, estimated_price)
+                            })
+                            .filter(|(_, _, estimated_price)| body.max_price.is_none_or(|max_price| *estimated_price <= max_price))
+                            .collect::<Vec<_>>();
+
                         let time_delta = body.end_time - body.start_time;
                         let start_time = body.start_time - Duration::hours(1);
                         let end_time = body.start_time + Duration::days(time_delta.num_days() + 1);
 
-                        let start_time_buffered = start_time - Duration::minutes(proj_config::RSVP_BUFFER);
-                        let end_time_buffered = end_time + Duration::minutes(proj_config::RSVP_BUFFER);
+                        let vehicle_ids = all_vehicles.iter().map(|(vehicle, _, _)| vehicle.id).collect::<Vec<i32>>();
+                        let schedules = methods::availability::load_vehicle_schedules(&vehicle_ids, start_time, end_time).await;
+                        let Ok(mut schedules) = schedules else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading vehicle blocking agreements"))
+                        };
 
-                        #[derive(
-                            Serialize, Deserialize,
-                        )]
-                        struct BlockedRange {
-                            #[serde(with = "chrono::serde::ts_seconds")]
-                            start_time: DateTime<Utc>,
-                            #[serde(with = "chrono::serde::ts_seconds")]
-                            end_time: DateTime<Utc>,
-                        }
                         #[derive(
                             Serialize, Deserialize,
                         )]
                         struct VehicleWithBlockedDurations {
                             vehicle: model::PublishRenterVehicle,
-                            blocked_durations: Vec<BlockedRange>,
-                        }
-                        impl VehicleWithBlockedDurations {
-                            fn is_vehicle_available(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> bool {
-                                for blocked in &self.blocked_durations {
-                                    if start_time < blocked.end_time && end_time > blocked.start_time {
-                                        return false;
-                                    }
-                                }
-                                true
-                            }
+                            blocked_durations: Vec<methods::availability::BlockedRange>,
+                            #[serde(with = "rust_decimal::serde::str")]
+                            estimated_price: Decimal,
+                            #[serde(skip)]
+                            is_available: bool,
                         }
                         #[derive(
                             Serialize, Deserialize,
@@ -212,70 +282,66 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             location: model::Location,
                             vehicles: Vec<VehicleWithBlockedDurations>,
                         }
-                        let mut vehicles_by_location: HashMap<i32, LocationWithVehicles> = HashMap::new();
-                        for (vehicle, location) in all_vehicles {
-
-                            let mut blocked_durations: Vec<BlockedRange> = Vec::new();
-
-                            let agreements_blocking = agreements_query::agreements
-                                .filter(agreements_query::vehicle_id.eq(&vehicle.id))
-                                .filter(agreements_query::status.eq(model::AgreementStatus::Rental))
-                                .filter(
-                                    methods::diesel_fn::coalesce(agreements_query::actual_pickup_time, agreements_query::rsvp_pickup_time)
-                                        .lt(end_time_buffered)
-                                        .and(
-                                            methods::diesel_fn::coalesce(
-                                                agreements_query::actual_drop_off_time,
-                                                methods::diesel_fn::greatest(agreements_query::rsvp_drop_off_time, diesel::dsl::now)
-                                            )
-                                                .gt(start_time_buffered)
-                                        )
-                                )
-                                .get_results::<model::Agreement>(&mut pool);
-
-                            let Ok(agreements_blocking) = agreements_blocking else {
-                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading vehicle blocking agreements"))
-                            };
-
-                            for agreement in agreements_blocking {
-                                let pickup_time = agreement.actual_pickup_time.unwrap_or(agreement.rsvp_pickup_time) - Duration::minutes(proj_config::RSVP_BUFFER);
-                                let drop_off_time = agreement.actual_drop_off_time.unwrap_or(agreement.rsvp_drop_off_time) + Duration::minutes(proj_config::RSVP_BUFFER);
-
-                                let blocking_start_time = {
-                                    if pickup_time >= start_time {
-                                        pickup_time
-                                    } else {
-                                        start_time
-                                    }
-                                };
-                                let blocking_end_time = {
-                                    if drop_off_time <= end_time {
-                                        drop_off_time
-                                    } else {
-                                        end_time
-                                    }
-                                };
+                        let vehicle_fits = all_vehicles.iter()
+                            .map(|(vehicle, _, _)| methods::availability::VehicleFit::from(vehicle))
+                            .collect::<Vec<_>>();
+                        let mut available_vehicle_ids: Vec<i32> = Vec::new();
 
-                                (&mut blocked_durations).push(BlockedRange{ start_time: blocking_start_time, end_time: blocking_end_time });
+                        let mut vehicles_by_location: HashMap<i32, LocationWithVehicles> = HashMap::new();
+                        for (vehicle, location, estimated_price) in all_vehicles {
+                            let schedule = schedules.remove(&vehicle.id).unwrap_or_default();
+                            let is_available = schedule.is_available(body.start_time, body.end_time);
+                            if is_available {
+                                available_vehicle_ids.push(vehicle.id);
                             }
+
                             let entry = (&mut vehicles_by_location).entry(location.id).or_insert_with(|| LocationWithVehicles {
                                 location,
                                 vehicles: Vec::new(),
                             });
-                            (&mut entry.vehicles).push(VehicleWithBlockedDurations { vehicle: vehicle.into(), blocked_durations });
+                            (&mut entry.vehicles).push(VehicleWithBlockedDurations { vehicle: vehicle.into(), blocked_durations: schedule.blocked, estimated_price, is_available });
                         }
                         let mut locations_with_vehicles: Vec<LocationWithVehicles> = vehicles_by_location.into_values().collect();
 
                         for location_with_vehicles in &mut locations_with_vehicles {
                             location_with_vehicles.vehicles.sort_by(|a, b| {
-                                let a_available = a.is_vehicle_available(body.start_time, body.end_time);
-                                let b_available = b.is_vehicle_available(body.start_time, body.end_time);
-
-                                b_available
-                                    .cmp(&a_available)
+                                let by_requested = match body.sort_by.as_deref() {
+                                    Some("price") => a.estimated_price.cmp(&b.estimated_price),
+                                    Some("seats") => b.vehicle.capacity.cmp(&a.vehicle.capacity),
+                                    _ => std::cmp::Ordering::Equal,
+                                };
+                                b.is_available
+                                    .cmp(&a.is_available)
+                                    .then(by_requested)
                                     .then_with(|| a.vehicle.id.cmp(&b.vehicle.id))
                             });
                         }
+
+                        // Suggest alternatives when the picked vehicle, or every vehicle, is taken
+                        let needs_suggestions = match body.vehicle_id {
+                            Some(vehicle_id) => !available_vehicle_ids.contains(&vehicle_id),
+                            None => available_vehicle_ids.is_empty(),
+                        };
+                        let suggestions = if needs_suggestions && !vehicle_fits.is_empty() {
+                            let search_span = Duration::hours(methods::availability::SUGGESTION_SEARCH_HOURS);
+                            let wide_schedules = methods::availability::load_vehicle_schedules(
+                                &vehicle_ids, body.start_time - search_span, body.end_time + search_span
+                            ).await;
+                            let Ok(wide_schedules) = wide_schedules else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading schedules for suggestions"))
+                            };
+                            methods::availability::suggest(
+                                body.vehicle_id,
+                                &vehicle_fits,
+                                &wide_schedules,
+                                body.start_time,
+                                body.end_time,
+                                methods::availability::earliest_start_time(now),
+                                methods::availability::latest_start_time(now),
+                            )
+                        } else {
+                            Vec::new()
+                        };
                         // RETURN: OK
                         #[derive(
                             Serialize, Deserialize,
@@ -284,35 +350,9 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> +
                             offer: model::RateOffer,
                             vehicles: Vec<LocationWithVehicles>,
                             taxes: Vec<Tax>,
+                            suggestions: Vec<methods::availability::Suggestion>,
                         }
-                        let new_rate_offer = model::NewRateOffer{
-                            renter_id: user_id,
-                            apartment_id: body.apartment_id,
-                            multiplier: Decimal::new(80, 2),
-                        };
-                        use crate::schema::rate_offers::dsl as rate_offers_query;
-                        let rate_offer_add_result = diesel::insert_into(rate_offers_query::rate_offers)
-                            .values(&new_rate_offer)
-                            .get_result::<model::RateOffer>(&mut pool);
-
-                        let Ok(rate_offer) = rate_offer_add_result else {
-                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error inserting rate offer"))
-                        };
-
-                        use crate::schema::apartments_taxes::dsl as at_q;
-                        use crate::schema::taxes::dsl as t_q;
-
-                        let taxes = at_q::apartments_taxes
-                            .inner_join(t_q::taxes)
-                            .filter(at_q::apartment_id.eq(body.apartment_id))
-                            .select(t_q::taxes::all_columns())
-                            .get_results::<Tax>(&mut pool);
-
-                        let Ok(taxes) = taxes else {
-                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/availability: Database error loading taxes"))
-                        };
-
-                        let resp = Availability{ offer: rate_offer, vehicles: locations_with_vehicles, taxes };
+                        let resp = Availability{ offer: rate_offer, vehicles: locations_with_vehicles, taxes, suggestions };
 
                         methods::standard_replies::response_with_obj(resp, StatusCode::OK)
                     }
@@ -0,0 +1,100 @@
+use crate::{connection_pool, methods, model, helper_model, schema};
+use diesel::prelude::*;
+use warp::{Filter, http::Method, http::StatusCode};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("command-history")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::query::<helper_model::RemoteCommandQuery>())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        params: helper_model::RemoteCommandQuery,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::GET {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+                if params.vehicle_id <= 0 || params.limit.is_some_and(|limit| limit <= 0) {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id;
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                user_id = match user_id_parsed_result {
+                    Ok(int) => int,
+                    Err(_) => {
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: token_and_id[0].parse().unwrap(),
+                };
+                let if_token_valid =
+                    methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                        .await;
+                return match if_token_valid {
+                    Err(err) => {
+                        match err {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/command-history: Token verification unexpected error"))
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/command-history: Token extension failed (returned false)"));
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/command-history: Token extension error"));
+                            }
+                        }
+
+                        let admin = methods::user::get_user_by_id(&access_token.user_id)
+                            .await;
+
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/command-history: Database error loading admin user"));
+                        };
+
+                        if !admin.is_operational_admin() {
+                            return methods::standard_replies::admin_not_verified()
+                        }
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        use schema::remote_commands::dsl as rc_q;
+                        let limit = params.limit.unwrap_or(100).clamp(1, 500);
+                        let commands = rc_q::remote_commands
+                            .filter(rc_q::vehicle_id.eq(params.vehicle_id))
+                            .order((rc_q::sent_at.desc(), rc_q::id.desc()))
+                            .limit(limit)
+                            .get_results::<model::RemoteCommandLog>(&mut pool);
+                        let Ok(commands) = commands else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/command-history: Database error loading remote commands"));
+                        };
+                        methods::standard_replies::response_with_obj(commands, StatusCode::OK)
+                    }
+                }
+            }
+        )
+}
@@ -0,0 +1,111 @@
+use crate::{connection_pool, methods, model, helper_model, schema};
+use diesel::prelude::*;
+use diesel::result::Error;
+use warp::{Filter, http::Method, http::StatusCode};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("delete-blackout")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::BlackoutIdRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::DELETE {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+                if body.blackout_id <= 0 {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id;
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                user_id = match user_id_parsed_result {
+                    Ok(int) => int,
+                    Err(_) => {
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: token_and_id[0].parse().unwrap(),
+                };
+                let if_token_valid =
+                    methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                        .await;
+                return match if_token_valid {
+                    Err(err) => {
+                        match err {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/delete-blackout: Token verification unexpected error"))
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/delete-blackout: Token extension failed (returned false)"));
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/delete-blackout: Token extension error"));
+                            }
+                        }
+
+                        let admin = methods::user::get_user_by_id(&access_token.user_id)
+                            .await;
+
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/delete-blackout: Database error loading admin user"));
+                        };
+
+                        if !admin.is_operational_admin() {
+                            return methods::standard_replies::admin_not_verified()
+                        }
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        use schema::vehicle_blackouts::dsl as vb_q;
+                        let deleted = diesel::delete(vb_q::vehicle_blackouts.find(body.blackout_id))
+                            .get_result::<model::VehicleBlackout>(&mut pool);
+                        match deleted {
+                            Ok(deleted) => {
+                                let _ = methods::audit::record(
+                                    Some(admin.id), model::AuditActionType::Delete, format!("vehicle/delete-blackout/{}", deleted.id)
+                                ).await;
+                                methods::standard_replies::response_with_obj(deleted, StatusCode::OK)
+                            }
+                            Err(Error::NotFound) => {
+                                let err_msg = helper_model::ErrorResponse {
+                                    title: "Blackout Not Found".to_string(),
+                                    message: "Cannot find a blackout with this id. ".to_string()
+                                };
+                                methods::standard_replies::response_with_obj(err_msg, StatusCode::NOT_FOUND)
+                            }
+                            Err(_) => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/delete-blackout: SQL error deleting blackout"))
+                            }
+                        }
+                    }
+                }
+            }
+        )
+}
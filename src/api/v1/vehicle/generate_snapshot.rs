@@ -130,67 +130,18 @@ pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> +
                         return methods::standard_replies::user_email_not_verified();
                     }
 
-                    let (fuel, odo) = match vehicle.remote_mgmt {
-                        model::RemoteMgmtType::Tesla => {
-                            // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                            let status_path = format!("/api/1/vehicles/{}", vehicle.remote_mgmt_id);
-
-                            for i in 0..16 { // up to ~10s total
-                                if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                    if let Ok(body_text) = response.text().await {
-                                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                            let state = json
-                                                .get("response")
-                                                .and_then(|r| r.get("state"))
-                                                .and_then(|s| s.as_str())
-                                                .unwrap_or("");
-                                            if state == "online" {
-                                                break;
-                                            }
-                                            // Only on the first iteration, if offline, send wake_up once
-                                            if i == 0 {
-                                                let wake_path = format!("/api/1/vehicles/{}/wake_up", vehicle.remote_mgmt_id);
-                                                let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                            }
-                                        }
-                                    }
-                                }
-                                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                            }
-
-                            // Fetch live Tesla vehicle data (odometer + battery level)
-                            let vehicle_tag = &vehicle.remote_mgmt_id;
-                            let tesla_path = format!("/api/1/vehicles/{}/vehicle_data?endpoints=location_data%3Bcharge_state%3Bvehicle_state", vehicle_tag);
-
-                            let tesla_resp = match integration::tesla_veygo::tesla_make_request(Method::GET, &tesla_path, None).await {
-                                Ok(r) => r,
-                                Err(_) => {
-                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/generate-snapshot: Tesla API error fetching vehicle_data"));
-                                }
+                    let (fuel, odo) = match methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id) {
+                        Ok(remote) => {
+                            let telemetry = methods::remote_vehicle::refresh_readings(remote.as_ref(), &mut vehicle).await;
+                            let Ok(telemetry) = telemetry else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/generate-snapshot: Remote management error fetching vehicle data"));
                             };
 
-                            if !tesla_resp.status().is_success() {
-                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/generate-snapshot: Tesla API returned non-success for vehicle_data"));
-                            }
-
-                            let tesla_body: helper_model::TeslaVehicleDataEnvelope = match tesla_resp.json().await {
-                                Ok(b) => b,
-                                Err(_) => {
-                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/generate-snapshot: Tesla API response JSON decode error"));
-                                }
-                            };
-
-                            let odometer_i32: i32 = tesla_body.response.vehicle_state.odometer.round() as i32;
-                            let battery_level_i32: i32 = tesla_body.response.charge_state.battery_level;
-
-                            vehicle.odometer = odometer_i32;
-                            vehicle.tank_level_percentage = battery_level_i32;
-
                             let _ = diesel::update(v_q::vehicles.find(vehicle.id)).set(&vehicle).execute(&mut pool);
 
-                            (battery_level_i32, odometer_i32)
+                            (telemetry.level, telemetry.odometer)
                         }
-                        _ => {
+                        Err(_) => {
                             (0, 0)
                         }
                     };
@@ -0,0 +1,25 @@
+use crate::{methods, model, connection_pool};
+use diesel::prelude::*;
+use warp::{Filter, Reply, http::{Method, StatusCode}};
+
+pub fn main() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("services")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and_then(async move |method: Method| {
+            if method != Method::GET {
+                return methods::standard_replies::method_not_allowed_response_405();
+            }
+            use crate::schema::services::dsl as service_query;
+            let mut pool = connection_pool().await.get().unwrap();
+            let services = service_query::services
+                .order(service_query::id)
+                .get_results::<model::Service>(&mut pool);
+
+            let Ok(services) = services else {
+                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/get-services: Database error loading services"));
+            };
+
+            methods::standard_replies::response_with_obj(services, StatusCode::OK)
+        })
+}
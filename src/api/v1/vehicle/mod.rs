@@ -5,6 +5,18 @@ mod get_mileage_packages;
 mod user_identify;
 mod upload_image;
 mod generate_snapshot;
+mod update;
+mod retire;
+mod reactivate;
+mod reassign;
+mod get_services;
+mod new_service;
+mod record_service;
+mod get_blackouts;
+mod new_blackout;
+mod update_blackout;
+mod delete_blackout;
+mod command_history;
 
 use warp::Filter;
 
@@ -17,6 +29,18 @@ pub fn api_v1_vehicle()
         .or(user_identify::main())
         .or(upload_image::main())
         .or(generate_snapshot::main())
+        .or(update::main())
+        .or(retire::main())
+        .or(reactivate::main())
+        .or(reassign::main())
+        .or(get_services::main())
+        .or(new_service::main())
+        .or(record_service::main())
+        .or(get_blackouts::main())
+        .or(new_blackout::main())
+        .or(update_blackout::main())
+        .or(delete_blackout::main())
+        .or(command_history::main())
         .boxed();
 
     warp::path("vehicle")
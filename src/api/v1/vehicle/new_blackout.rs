@@ -0,0 +1,129 @@
+use chrono::Utc;
+use crate::{connection_pool, methods, model, helper_model, schema};
+use diesel::prelude::*;
+use warp::{Filter, http::Method, http::StatusCode};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("new-blackout")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::NewBlackoutRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+                if body.vehicle_id <= 0 || body.end_time <= body.start_time || body.end_time <= Utc::now()
+                    || body.reason.trim().is_empty() || body.reason.len() > 255 {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id;
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                user_id = match user_id_parsed_result {
+                    Ok(int) => int,
+                    Err(_) => {
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: token_and_id[0].parse().unwrap(),
+                };
+                let if_token_valid =
+                    methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                        .await;
+                return match if_token_valid {
+                    Err(err) => {
+                        match err {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: Token verification unexpected error"))
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: Token extension failed (returned false)"));
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: Token extension error"));
+                            }
+                        }
+
+                        let admin = methods::user::get_user_by_id(&access_token.user_id)
+                            .await;
+
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: Database error loading admin user"));
+                        };
+
+                        if !admin.is_operational_admin() {
+                            return methods::standard_replies::admin_not_verified()
+                        }
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        use schema::vehicles::dsl as v_q;
+                        use schema::vehicle_blackouts::dsl as vb_q;
+                        let vehicle_exists = diesel::select(diesel::dsl::exists(v_q::vehicles.find(body.vehicle_id)))
+                            .get_result::<bool>(&mut pool);
+                        let Ok(vehicle_exists) = vehicle_exists else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: Database error checking vehicle"));
+                        };
+                        if !vehicle_exists {
+                            return methods::standard_replies::bad_request_400("Vehicle does not exist")
+                        }
+
+                        let new_blackout = model::NewVehicleBlackout {
+                            vehicle_id: body.vehicle_id,
+                            start_time: body.start_time,
+                            end_time: body.end_time,
+                            reason: body.reason.trim().to_string(),
+                            created_by: admin.id,
+                        };
+                        let blackout = diesel::insert_into(vb_q::vehicle_blackouts)
+                            .values(&new_blackout)
+                            .get_result::<model::VehicleBlackout>(&mut pool);
+                        let Ok(blackout) = blackout else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: SQL error inserting blackout"));
+                        };
+                        let _ = methods::audit::record(
+                            Some(admin.id), model::AuditActionType::Create, format!("vehicle/new-blackout/{}", blackout.id)
+                        ).await;
+
+                        // the blackout is saved either way, overlapping reservations come back as a warning
+                        let overlapping_agreements = methods::availability::agreements_overlapping(
+                            blackout.vehicle_id, blackout.start_time, blackout.end_time
+                        ).await;
+                        let Ok(overlapping_agreements) = overlapping_agreements else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/new-blackout: Database error loading overlapping agreements"));
+                        };
+
+                        let resp = helper_model::BlackoutResponse { blackout, overlapping_agreements };
+                        methods::standard_replies::response_with_obj(resp, StatusCode::CREATED)
+                    }
+                }
+            }
+        )
+}
@@ -0,0 +1,217 @@
+use crate::{connection_pool, integration, methods, model, helper_model, schema};
+use diesel::prelude::*;
+use diesel::result::Error;
+use warp::{Filter, http::Method, http::StatusCode};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("reassign")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::ResolveAffectedAgreementRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+                let agreement_id = match &body {
+                    helper_model::ResolveAffectedAgreementRequest::Reassign { agreement_id, vehicle_id } => {
+                        if *vehicle_id <= 0 {
+                            return methods::standard_replies::bad_request_400("wrong parameters. ")
+                        }
+                        *agreement_id
+                    }
+                    helper_model::ResolveAffectedAgreementRequest::Notify { agreement_id, message } => {
+                        if message.trim().is_empty() {
+                            return methods::standard_replies::bad_request_400("wrong parameters. ")
+                        }
+                        *agreement_id
+                    }
+                };
+                if agreement_id <= 0 {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id;
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                user_id = match user_id_parsed_result {
+                    Ok(int) => int,
+                    Err(_) => {
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: token_and_id[0].parse().unwrap(),
+                };
+                let if_token_valid =
+                    methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                        .await;
+                return match if_token_valid {
+                    Err(err) => {
+                        match err {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Token verification unexpected error"))
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Token extension failed (returned false)"));
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Token extension error"));
+                            }
+                        }
+
+                        let admin = methods::user::get_user_by_id(&access_token.user_id)
+                            .await;
+
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Database error loading admin user"));
+                        };
+
+                        if !admin.is_operational_admin() {
+                            return methods::standard_replies::admin_not_verified()
+                        }
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        use schema::agreements::dsl as ag_q;
+                        use schema::locations::dsl as l_q;
+                        use schema::renters::dsl as r_q;
+                        use schema::vehicles::dsl as v_q;
+                        // only bookings that have not been picked up can still be moved
+                        let agreement = ag_q::agreements
+                            .find(agreement_id)
+                            .inner_join(l_q::locations)
+                            .inner_join(r_q::renters)
+                            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                            .filter(ag_q::actual_pickup_time.is_null())
+                            .select((ag_q::agreements::all_columns(), l_q::apartment_id, r_q::apple_apns))
+                            .get_result::<(model::Agreement, i32, Option<String>)>(&mut pool);
+
+                        let (mut agreement, apartment_id, apple_apns) = match agreement {
+                            Ok(result) => result,
+                            Err(Error::NotFound) => {
+                                let err_msg = helper_model::ErrorResponse {
+                                    title: "Agreement Not Found".to_string(),
+                                    message: "Agreement not found or already picked up. ".to_string()
+                                };
+                                return methods::standard_replies::response_with_obj(err_msg, StatusCode::NOT_FOUND)
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Database error loading agreement"));
+                            }
+                        };
+
+                        let audit_path = format!("vehicle/reassign/{}", agreement.confirmation);
+                        let (title, subject, message) = match body {
+                            helper_model::ResolveAffectedAgreementRequest::Reassign { vehicle_id, .. } => {
+                                let current_vehicle = v_q::vehicles
+                                    .find(agreement.vehicle_id)
+                                    .get_result::<model::Vehicle>(&mut pool);
+                                let Ok(current_vehicle) = current_vehicle else {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Database error loading current vehicle"));
+                                };
+                                let new_vehicle = v_q::vehicles
+                                    .find(vehicle_id)
+                                    .inner_join(l_q::locations)
+                                    .select((v_q::vehicles::all_columns(), l_q::apartment_id, l_q::name))
+                                    .get_result::<(model::Vehicle, i32, String)>(&mut pool)
+                                    .optional();
+                                let Ok(new_vehicle) = new_vehicle else {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Database error loading new vehicle"));
+                                };
+
+                                // the replacement has to be a car the renter could have booked for the same price
+                                let eligible = new_vehicle.filter(|(new_vehicle, new_apartment_id, _)| {
+                                    new_vehicle.id != current_vehicle.id
+                                        && new_vehicle.available
+                                        && !new_vehicle.maintenance_hold
+                                        && *new_apartment_id == apartment_id
+                                        && methods::availability::VehicleFit::from(new_vehicle)
+                                            .similarity_to(&methods::availability::VehicleFit::from(&current_vehicle))
+                                            .is_some()
+                                });
+                                let Some((new_vehicle, _, location_name)) = eligible else {
+                                    let err_msg = helper_model::ErrorResponse {
+                                        title: "Vehicle Not Eligible".to_string(),
+                                        message: "This vehicle is not a comparable replacement at the same apartment. ".to_string()
+                                    };
+                                    return methods::standard_replies::response_with_obj(err_msg, StatusCode::NOT_ACCEPTABLE)
+                                };
+
+                                let schedules = methods::availability::load_vehicle_schedules(
+                                    &[new_vehicle.id], agreement.rsvp_pickup_time, agreement.rsvp_drop_off_time
+                                ).await;
+                                let Ok(schedules) = schedules else {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Database error loading vehicle schedule"));
+                                };
+                                let is_free = schedules.get(&new_vehicle.id)
+                                    .is_none_or(|schedule| schedule.is_available(agreement.rsvp_pickup_time, agreement.rsvp_drop_off_time));
+                                if !is_free {
+                                    return methods::standard_replies::double_booking_not_allowed()
+                                }
+
+                                // the booked price is kept, only the car and pickup spot change
+                                agreement.vehicle_id = new_vehicle.id;
+                                agreement.location_id = new_vehicle.location_id;
+                                let saved = agreement.save_changes::<model::Agreement>(&mut pool);
+                                let Ok(saved) = saved else {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/reassign: Database error saving agreement"));
+                                };
+                                agreement = saved;
+                                let _ = methods::audit::record(
+                                    Some(admin.id), model::AuditActionType::Update,
+                                    format!("{}: moved from vehicle #{} to vehicle #{}", audit_path, current_vehicle.id, new_vehicle.id)
+                                ).await;
+
+                                let message = format!(
+                                    "Your reservation {} has been moved to {} at {}. Your trip times and price are unchanged. ",
+                                    agreement.confirmation, new_vehicle.name, location_name
+                                );
+                                ("Reservation Updated", "Your reservation has been updated", message)
+                            }
+                            helper_model::ResolveAffectedAgreementRequest::Notify { message, .. } => {
+                                let _ = methods::audit::record(
+                                    Some(admin.id), model::AuditActionType::Update, format!("{}: renter notified", audit_path)
+                                ).await;
+                                ("About Your Reservation", "About your reservation", message.trim().to_string())
+                            }
+                        };
+
+                        if let Some(renter_app_apns) = &apple_apns {
+                            let _ = integration::apns_veygo::send_notification(
+                                renter_app_apns, title, &message, false
+                            ).await;
+                        }
+                        let renter_email = integration::mailgun_veygo::make_email_obj(&agreement.user_email, &agreement.user_name);
+                        let _ = integration::mailgun_veygo::send_email(None, vec![renter_email], subject, &message, None).await;
+
+                        methods::standard_replies::response_with_obj(agreement, StatusCode::OK)
+                    }
+                }
+            }
+        )
+}
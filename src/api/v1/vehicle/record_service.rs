@@ -0,0 +1,176 @@
+use crate::{connection_pool, integration, methods, model, schema, helper_model};
+use bytes::{Bytes};
+use diesel::prelude::*;
+use warp::Filter;
+use warp::http::{StatusCode, Method};
+use sha2::{Sha256, Digest};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("record-service")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::bytes())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<i32>("vehicle-id"))
+        .and(warp::header::<i32>("service-id"))
+        .and(warp::header::<i32>("odometer"))
+        .and(warp::header::<String>("file-name"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method, body: Bytes, auth: String, vehicle_id: i32, service_id: i32, odometer: i32, file_name: String, user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+                if vehicle_id <= 0 || service_id <= 0 || odometer < 0 || body.is_empty() {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id;
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                user_id = match user_id_parsed_result {
+                    Ok(int) => int,
+                    Err(_) => {
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: token_and_id[0].parse().unwrap(),
+                };
+                let if_token_valid =
+                    methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                        .await;
+                return match if_token_valid {
+                    Err(err) => {
+                        match err {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Token verification unexpected error"))
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Token extension failed (returned false)"));
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Token extension error"));
+                            }
+                        }
+
+                        let admin = methods::user::get_user_by_id(&access_token.user_id)
+                            .await;
+
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Database error loading admin user"));
+                        };
+
+                        if !admin.is_operational_admin() {
+                            return methods::standard_replies::admin_not_verified()
+                        }
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        use schema::vehicles::dsl as v_q;
+                        use schema::services::dsl as s_q;
+                        use schema::vehicles_services::dsl as vs_q;
+                        let vehicle = v_q::vehicles
+                            .find(vehicle_id)
+                            .get_result::<model::Vehicle>(&mut pool)
+                            .optional();
+                        let service = s_q::services
+                            .find(service_id)
+                            .get_result::<model::Service>(&mut pool)
+                            .optional();
+                        let (Ok(vehicle), Ok(service)) = (vehicle, service) else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Database error loading vehicle or service"));
+                        };
+                        let (Some(vehicle), Some(service)) = (vehicle, service) else {
+                            return methods::standard_replies::bad_request_400("Vehicle or service does not exist")
+                        };
+
+                        let last_service = vs_q::vehicles_services
+                            .find((vehicle.id, service.id))
+                            .get_result::<model::VehicleService>(&mut pool)
+                            .optional();
+                        let Ok(last_service) = last_service else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Database error loading last service"));
+                        };
+                        if odometer > vehicle.odometer || last_service.is_some_and(|last| odometer < last.odometer) {
+                            return methods::standard_replies::bad_request_400("Odometer reading is out of range")
+                        }
+
+                        let mut hasher = Sha256::new();
+                        let data = vehicle.vin.clone().into_bytes();
+                        (&mut hasher).update(data);
+                        let result = hasher.finalize();
+                        let object_path: String = format!("vehicle_services/{}/", hex::encode_upper(result));
+
+                        let file_bytes = body.to_vec();
+                        let document = integration::gcloud_storage_veygo::upload_file(
+                            object_path,
+                            file_name,
+                            file_bytes,
+                        ).await;
+
+                        let record = model::VehicleService {
+                            vehicle_id: vehicle.id,
+                            service_id: service.id,
+                            odometer,
+                            document,
+                        };
+                        let saved = diesel::insert_into(vs_q::vehicles_services)
+                            .values(&record)
+                            .on_conflict((vs_q::vehicle_id, vs_q::service_id))
+                            .do_update()
+                            .set(&record)
+                            .get_result::<model::VehicleService>(&mut pool);
+                        let Ok(saved) = saved else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Database error saving service record"));
+                        };
+                        let _ = methods::audit::record(
+                            Some(admin.id), model::AuditActionType::Create,
+                            format!("vehicle/record-service/{}: {} at {} miles", vehicle.id, service.note, odometer)
+                        ).await;
+
+                        // lift the booking hold right away once nothing else is overdue
+                        let mut maintenance_hold = vehicle.maintenance_hold;
+                        if maintenance_hold {
+                            let overdue = methods::maintenance::overdue_services_by_vehicle(Some(&[vehicle.id])).await;
+                            let Ok(overdue) = overdue else {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Database error checking overdue services"));
+                            };
+                            if !overdue.contains_key(&vehicle.id) {
+                                let released = diesel::update(v_q::vehicles.find(vehicle.id))
+                                    .set(v_q::maintenance_hold.eq(false))
+                                    .execute(&mut pool);
+                                if released.is_err() {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/record-service: Database error releasing maintenance hold"));
+                                }
+                                maintenance_hold = false;
+                            }
+                        }
+
+                        let msg = helper_model::RecordServiceResponse { service: saved, maintenance_hold };
+                        methods::standard_replies::response_with_obj(msg, StatusCode::CREATED)
+                    }
+                };
+            },
+        )
+}
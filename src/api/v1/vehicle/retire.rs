@@ -0,0 +1,133 @@
+use crate::{connection_pool, methods, model, helper_model, schema};
+use diesel::prelude::*;
+use diesel::result::Error;
+use warp::{Filter, http::Method, http::StatusCode};
+use crate::helper_model::VeygoError;
+
+pub fn main() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("retire")
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(warp::body::json())
+        .and(warp::header::<String>("auth"))
+        .and(warp::header::<String>("user-agent"))
+        .and_then(
+            async move |method: Method,
+                        body: helper_model::VehicleIdRequest,
+                        auth: String,
+                        user_agent: String| {
+                if method != Method::POST {
+                    return methods::standard_replies::method_not_allowed_response_405();
+                }
+                if body.vehicle_id <= 0 {
+                    return methods::standard_replies::bad_request_400("wrong parameters. ")
+                }
+                let token_and_id = auth.split("$").collect::<Vec<&str>>();
+                if token_and_id.len() != 2 {
+                    return methods::tokens::token_invalid_return();
+                }
+                let user_id;
+                let user_id_parsed_result = token_and_id[1].parse::<i32>();
+                user_id = match user_id_parsed_result {
+                    Ok(int) => int,
+                    Err(_) => {
+                        return methods::tokens::token_invalid_return();
+                    }
+                };
+
+                let access_token = model::RequestToken {
+                    user_id,
+                    token: token_and_id[0].parse().unwrap(),
+                };
+                let if_token_valid =
+                    methods::tokens::verify_user_token(&access_token.user_id, &access_token.token)
+                        .await;
+                return match if_token_valid {
+                    Err(err) => {
+                        match err {
+                            VeygoError::TokenFormatError => {
+                                methods::tokens::token_not_hex_warp_return()
+                            }
+                            VeygoError::InvalidToken => {
+                                methods::tokens::token_invalid_return()
+                            }
+                            _ => {
+                                methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: Token verification unexpected error"))
+                            }
+                        }
+                    }
+                    Ok(valid_token) => {
+                        // token is valid
+                        let ext_result = methods::tokens::extend_token(valid_token.1, &user_agent).await;
+
+                        match ext_result {
+                            Ok(bool) => {
+                                if !bool {
+                                    return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: Token extension failed (returned false)"));
+                                }
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: Token extension error"));
+                            }
+                        }
+
+                        let admin = methods::user::get_user_by_id(&access_token.user_id)
+                            .await;
+
+                        let Ok(admin) = admin else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: Database error loading admin user"));
+                        };
+
+                        if !admin.is_operational_admin() {
+                            return methods::standard_replies::admin_not_verified()
+                        }
+
+                        let mut pool = connection_pool().await.get().unwrap();
+                        use schema::vehicles::dsl as v_q;
+                        use schema::locations::dsl as l_q;
+                        let vehicle = v_q::vehicles
+                            .find(&body.vehicle_id)
+                            .inner_join(l_q::locations)
+                            .select((v_q::vehicles::all_columns(), l_q::apartment_id))
+                            .get_result::<(model::Vehicle, i32)>(&mut pool);
+
+                        let (vehicle, apartment_id) = match vehicle {
+                            Ok(result) => result,
+                            Err(Error::NotFound) => {
+                                let err_msg = helper_model::ErrorResponse {
+                                    title: "Vehicle Not Found".to_string(),
+                                    message: "Cannot find a vehicle with this id. ".to_string()
+                                };
+                                return methods::standard_replies::response_with_obj(err_msg, StatusCode::NOT_FOUND)
+                            }
+                            Err(_) => {
+                                return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: Database error loading vehicle"));
+                            }
+                        };
+
+                        let affected_agreements = methods::availability::affected_agreements(&vehicle, apartment_id).await;
+                        let Ok(affected_agreements) = affected_agreements else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: Database error loading affected agreements"));
+                        };
+
+                        let update_result = diesel::update(v_q::vehicles.find(vehicle.id))
+                            .set(v_q::available.eq(false))
+                            .get_result::<model::Vehicle>(&mut pool);
+                        let Ok(updated) = update_result else {
+                            return methods::standard_replies::internal_server_error_response_500(String::from("vehicle/retire: SQL error updating vehicle"));
+                        };
+                        let _ = methods::audit::record(
+                            Some(admin.id), model::AuditActionType::Update, format!("vehicle/retire/{}", updated.id)
+                        ).await;
+
+                        // existing bookings are kept until ops reassign them or contact the renter
+                        let resp = helper_model::VehicleChangeResponse {
+                            vehicle: updated.into(),
+                            affected_agreements,
+                        };
+                        methods::standard_replies::response_with_obj(resp, StatusCode::OK)
+                    }
+                }
+            }
+        )
+}
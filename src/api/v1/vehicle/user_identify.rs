@@ -1,4 +1,4 @@
-use crate::{connection_pool, methods, model, integration, proj_config};
+use crate::{connection_pool, methods, model, proj_config};
 use diesel::prelude::*;
 use warp::{Filter, Rejection, Reply};
 use warp::http::{Method, StatusCode};
@@ -113,43 +113,15 @@ pub fn main() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone
                                 return methods::standard_replies::response_with_obj(msg, StatusCode::NOT_FOUND)
                             }
 
-                            match vehicle.remote_mgmt {
-                                model::RemoteMgmtType::Tesla => {
-                                    let _handler = tokio::spawn(async move {
-                                        // 1) Check online state via GET /api/1/vehicles/{vehicle_tag}
-                                        let status_path = format!("/api/1/vehicles/{}", vehicle.remote_mgmt_id);
-
-                                        for i in 0..16 { // up to ~10s total
-                                            if let Ok(response) = integration::tesla_veygo::tesla_make_request(Method::GET, &status_path, None).await {
-                                                if let Ok(body_text) = response.text().await {
-                                                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-                                                        let state = json
-                                                            .get("response")
-                                                            .and_then(|r| r.get("state"))
-                                                            .and_then(|s| s.as_str())
-                                                            .unwrap_or("");
-                                                        if state == "online" {
-                                                            break;
-                                                        }
-                                                        // Only on the first iteration, if offline, send wake_up once
-                                                        if i == 0 {
-                                                            let wake_path = format!("/api/1/vehicles/{}/wake_up", vehicle.remote_mgmt_id);
-                                                            let _ = integration::tesla_veygo::tesla_make_request(Method::POST, &wake_path, None).await;
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                        }
-
-                                        // 2) Proceed to honk once online (or after timeout anyway)
-                                        let cmd_path = format!("/api/1/vehicles/{}/command/honk_horn", vehicle.remote_mgmt_id);
-                                        let _result = integration::tesla_veygo::tesla_make_request(Method::POST, &cmd_path, None).await;
-                                    });
-                                },
-                                _ => {
-
-                                }
+                            if let Ok(remote) = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id) {
+                                let origin = methods::remote_command::CommandOrigin {
+                                    vehicle_id: vehicle.id,
+                                    agreement_id: None,
+                                    triggered_by: Some(user_in_request.id),
+                                };
+                                let _handler = tokio::spawn(async move {
+                                    let _result = methods::remote_command::send_logged(remote.as_ref(), model::RemoteCommandType::Honk, origin, 1).await;
+                                });
                             }
                             let msg = serde_json::json!({});
                             methods::standard_replies::response_with_obj(msg, StatusCode::OK)
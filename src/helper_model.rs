@@ -222,6 +222,270 @@ pub struct NewAgreementRequest {
     pub hours_using_reward: Decimal,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QuoteRequest {
+    pub vehicle_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub liability: bool,
+    pub pcdw: bool,
+    pub pcdw_ext: bool,
+    pub rsa: bool,
+    pub pai: bool,
+    pub rate_offer_id: i32,
+    pub mileage_package_id: Option<i32>,
+    pub promo_code: Option<String>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub hours_using_reward: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ModifyAgreementRequest {
+    pub agreement_id: i32,
+    pub vehicle_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub liability: bool,
+    pub pcdw: bool,
+    pub pcdw_ext: bool,
+    pub rsa: bool,
+    pub pai: bool,
+    pub mileage_package_id: Option<i32>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub hours_using_reward: Decimal,
+    pub confirm: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModifyAgreementResponse {
+    pub agreement: model::Agreement,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub previous_total: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub new_total: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub difference: Decimal,
+    pub is_confirmed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ExtendAgreementRequest {
+    pub agreement_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub confirm: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ExtendAgreementResponse {
+    pub agreement: model::Agreement,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub estimated_additional_cost: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub amount_authorized: Decimal,
+    pub is_confirmed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CancelAgreementRequest {
+    pub agreement_id: i32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CancelAgreementResponse {
+    pub agreement: model::Agreement,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub cancellation_fee: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub refunded_amount: Decimal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VoidAgreementRequest {
+    pub agreement_id: i32,
+    pub reason: String,
+    // `None` refunds everything captured, zero keeps all payments
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    pub refund_amount: Option<Decimal>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VoidAgreementResponse {
+    pub agreement: model::Agreement,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub refunded_amount: Decimal,
+    pub auth_hold_released: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AdminRemoteCommandRequest {
+    pub vehicle_id: i32,
+    pub command: model::RemoteCommandType,
+    // checked against `vehicles.admin_pin` for door commands
+    pub admin_pin: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AdminRemoteCommandResponse {
+    pub vehicle_id: i32,
+    pub command: model::RemoteCommandType,
+    pub status: model::RemoteCommandStatus,
+    pub detail: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ManualDiscountRequest {
+    pub agreement_id: i32,
+    // `None` removes the discount
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    pub manual_discount: Option<Decimal>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ManualDiscountResponse {
+    pub agreement: model::Agreement,
+    pub price: Option<crate::methods::pricing::PriceBreakdown>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub refunded_amount: Decimal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AdminAgreementSearchQuery {
+    pub apartment_id: Option<i32>,
+    pub location_id: Option<i32>,
+    pub vehicle_id: Option<i32>,
+    pub renter_id: Option<i32>,
+    pub status: Option<model::AgreementStatus>,
+    // reserved pickup range, unix seconds
+    pub pickup_from: Option<i64>,
+    pub pickup_to: Option<i64>,
+    pub checked_out: Option<bool>,
+    pub overdue: Option<bool>,
+    // only closed agreements have a settled balance
+    pub balance_due: Option<bool>,
+    // rsvp_pickup_time (default), rsvp_drop_off_time or date_of_creation
+    pub sort_by: Option<String>,
+    // desc (default) or asc
+    pub sort_order: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AdminAgreementSearchResponse {
+    pub agreements: Vec<model::Agreement>,
+    pub next_cursor: Option<String>,
+    // the balance scan limit was hit before the page filled, more matches may follow `next_cursor`
+    pub truncated: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VehicleUpdateRequest {
+    pub vehicle_id: i32,
+    #[serde(flatten)]
+    pub update: model::VehicleUpdate,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VehicleIdRequest {
+    pub vehicle_id: i32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VehicleChangeResponse {
+    pub vehicle: model::PublishAdminVehicle,
+    pub affected_agreements: Vec<crate::methods::availability::AffectedAgreement>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BlackoutQuery {
+    pub vehicle_id: Option<i32>,
+    // ended blackouts are left out unless asked for
+    pub include_past: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteCommandQuery {
+    pub vehicle_id: i32,
+    // newest first, 100 unless asked for, capped at 500
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NewBlackoutRequest {
+    pub vehicle_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UpdateBlackoutRequest {
+    pub blackout_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BlackoutIdRequest {
+    pub blackout_id: i32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BlackoutResponse {
+    pub blackout: model::VehicleBlackout,
+    // reservations the blackout runs into, left in place for ops to move
+    pub overlapping_agreements: Vec<model::Agreement>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LowChargeAlertQuery {
+    pub agreement_id: Option<i32>,
+    pub vehicle_id: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FleetStateQuery {
+    pub apartment_id: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FleetStateEntry {
+    pub vehicle: model::PublishAdminVehicle,
+    // None until the poller has caught the car awake
+    pub telemetry: Option<model::VehicleTelemetry>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RecordServiceResponse {
+    pub service: model::VehicleService,
+    pub maintenance_hold: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ResolveAffectedAgreementRequest {
+    #[serde(rename = "reassign")]
+    Reassign {
+        agreement_id: i32,
+        vehicle_id: i32,
+    },
+    #[serde(rename = "notify")]
+    Notify {
+        agreement_id: i32,
+        message: String,
+    },
+}
+
 #[derive(Serialize, Debug, Clone)]
 pub struct RewardHoursSummaryResponse {
     #[serde(with = "rust_decimal::serde::str")]
@@ -237,6 +501,41 @@ pub struct DocumentRejectionTemplate<'a> {
     pub reason: &'a str,
 }
 
+#[derive(Debug, Clone)]
+pub struct ReceiptLine {
+    pub label: String,
+    pub amount: String,
+}
+
+#[derive(Template)]
+#[template(path = "receipt.html")]
+pub struct ReceiptTemplate {
+    pub confirmation: String,
+    pub renter_name: String,
+    pub vehicle_name: String,
+    pub location_name: String,
+    pub pickup_time: String,
+    pub drop_off_time: String,
+    pub rental_lines: Vec<ReceiptLine>,
+    pub charge_lines: Vec<ReceiptLine>,
+    pub tax_lines: Vec<ReceiptLine>,
+    pub total: String,
+    pub payment_lines: Vec<ReceiptLine>,
+    pub amount_paid: String,
+}
+
+#[derive(Template)]
+#[template(path = "late_charges.html")]
+pub struct LateChargesTemplate {
+    pub confirmation: String,
+    pub renter_name: String,
+    pub vehicle_name: String,
+    pub charge_lines: Vec<ReceiptLine>,
+    pub tax_lines: Vec<ReceiptLine>,
+    pub total: String,
+    pub payment_note: String,
+}
+
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum VeygoError {
@@ -0,0 +1,512 @@
+use std::env;
+use std::fmt;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::{OnceCell, RwLock};
+
+const DEFAULT_SERVER: &str = "https://my.geotab.com";
+const METERS_PER_MILE: f64 = 1609.344;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeotabError {
+    MissingConfig(&'static str),
+    InvalidConfig(&'static str),
+    Unauthorized,
+    DeviceNotFound,
+    DeviceOffline,
+    // no relay channel is wired for the horn
+    NoHornChannel,
+    MissingData(&'static str),
+    CommandRejected(String),
+    Api { name: String, message: String },
+    Http { status: u16, body: String },
+    Transport(String),
+    InvalidResponse(String),
+}
+
+impl GeotabError {
+    fn is_session_expired(&self) -> bool {
+        matches!(self, GeotabError::Api { name, .. } if name == "InvalidUserException" || name == "DbUnavailableException")
+    }
+}
+
+impl fmt::Display for GeotabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeotabError::MissingConfig(key) => write!(f, "Environment variable {} is not set", key),
+            GeotabError::InvalidConfig(key) => write!(f, "{} is not a relay channel", key),
+            GeotabError::Unauthorized => write!(f, "Geotab rejected the credentials"),
+            GeotabError::DeviceNotFound => write!(f, "Geotab does not know this device"),
+            GeotabError::DeviceOffline => write!(f, "Geotab device is not communicating"),
+            GeotabError::NoHornChannel => write!(f, "GEOTAB_HORN_CHANNEL is not set"),
+            GeotabError::MissingData(what) => write!(f, "Geotab device has no {}", what),
+            GeotabError::CommandRejected(reason) => write!(f, "Geotab command rejected: {}", reason),
+            GeotabError::Api { name, message } => write!(f, "Geotab API error {}: {}", name, message),
+            GeotabError::Http { status, body } => write!(f, "Geotab returned status {}: {}", status, body),
+            GeotabError::Transport(err) => write!(f, "Geotab request failed: {}", err),
+            GeotabError::InvalidResponse(err) => write!(f, "Geotab response could not be parsed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for GeotabError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeotabCredentials {
+    pub database: String,
+    pub user_name: String,
+    pub session_id: String,
+}
+
+#[derive(Debug, Clone)]
+struct GeotabSession {
+    credentials: GeotabCredentials,
+    api_url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeotabConfig {
+    pub server: String,
+    pub database: String,
+    pub user_name: String,
+    pub password: String,
+    // IOX relay channels wired to the keyless module
+    pub lock_channel: u8,
+    pub unlock_channel: u8,
+    pub horn_channel: Option<u8>,
+}
+
+impl GeotabConfig {
+    pub fn from_env() -> Result<GeotabConfig, GeotabError> {
+        Ok(GeotabConfig {
+            server: env_or_default("GEOTAB_SERVER", DEFAULT_SERVER),
+            database: env_or_err("GEOTAB_DATABASE")?,
+            user_name: env_or_err("GEOTAB_USERNAME")?,
+            password: env_or_err("GEOTAB_PASSWORD")?,
+            lock_channel: env_or_default("GEOTAB_LOCK_CHANNEL", "1").parse()
+                .map_err(|_| GeotabError::InvalidConfig("GEOTAB_LOCK_CHANNEL"))?,
+            unlock_channel: env_or_default("GEOTAB_UNLOCK_CHANNEL", "2").parse()
+                .map_err(|_| GeotabError::InvalidConfig("GEOTAB_UNLOCK_CHANNEL"))?,
+            horn_channel: env::var("GEOTAB_HORN_CHANNEL").ok().and_then(|value| value.parse().ok()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeotabVehicleData {
+    pub odometer: i32,
+    pub fuel_level: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+pub struct GeotabClient {
+    config: GeotabConfig,
+    http: reqwest::Client,
+    session: RwLock<Option<GeotabSession>>,
+}
+
+impl GeotabClient {
+    pub fn new(config: GeotabConfig) -> GeotabClient {
+        GeotabClient { config, http: reqwest::Client::new(), session: RwLock::new(None) }
+    }
+
+    async fn post(&self, api_url: &str, method: &str, params: Value) -> Result<Value, GeotabError> {
+        let body = json!({ "method": method, "params": params });
+        let resp = self.http
+            .post(api_url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|err| GeotabError::Transport(err.to_string()))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status().as_u16();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(GeotabError::Http { status, body });
+        }
+
+        let mut envelope: Value = resp
+            .json()
+            .await
+            .map_err(|err| GeotabError::InvalidResponse(err.to_string()))?;
+        if let Some(error) = envelope.get("error") {
+            return Err(api_error(error));
+        }
+        Ok(envelope.get_mut("result").map(Value::take).unwrap_or(Value::Null))
+    }
+
+    async fn authenticate(&self) -> Result<GeotabSession, GeotabError> {
+        let api_url = format!("{}/apiv1", self.config.server.trim_end_matches('/'));
+        let params = json!({
+            "database": self.config.database,
+            "userName": self.config.user_name,
+            "password": self.config.password,
+        });
+        let mut result = match self.post(&api_url, "Authenticate", params).await {
+            Err(GeotabError::Api { name, .. }) if name == "InvalidUserException" => return Err(GeotabError::Unauthorized),
+            result => result?,
+        };
+
+        let credentials: GeotabCredentials = serde_json::from_value(result.get_mut("credentials").map(Value::take).unwrap_or_default())
+            .map_err(|err| GeotabError::InvalidResponse(format!("Authenticate returned no credentials: {}", err)))?;
+        // the database may live on another federation server, later calls must go there
+        let api_url = match result["path"].as_str() {
+            Some(path) if path != "ThisServer" => format!("https://{}/apiv1", path),
+            _ => api_url,
+        };
+
+        let session = GeotabSession { credentials, api_url };
+        *self.session.write().await = Some(session.clone());
+        Ok(session)
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, GeotabError> {
+        let cached = self.session.read().await.clone();
+        let session = match cached {
+            Some(session) => session,
+            None => self.authenticate().await?,
+        };
+        match self.post(&session.api_url, method, with_credentials(&params, &session)).await {
+            Err(err) if err.is_session_expired() => {
+                // sessions expire server side, sign in again once and retry
+                let session = self.authenticate().await?;
+                self.post(&session.api_url, method, with_credentials(&params, &session)).await
+            }
+            result => result,
+        }
+    }
+
+    // latest recorded value of a diagnostic, Geotab returns it when fromDate equals toDate
+    async fn latest_status_data(&self, device_id: &str, diagnostic_id: &str) -> Result<Option<f64>, GeotabError> {
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let params = json!({
+            "typeName": "StatusData",
+            "search": {
+                "deviceSearch": { "id": device_id },
+                "diagnosticSearch": { "id": diagnostic_id },
+                "fromDate": now,
+                "toDate": now,
+            },
+        });
+        let result = self.call("Get", params).await?;
+        Ok(result.as_array()
+            .and_then(|records| records.last())
+            .and_then(|record| record["data"].as_f64()))
+    }
+
+    async fn device_status(&self, device_id: &str) -> Result<Value, GeotabError> {
+        let params = json!({
+            "typeName": "DeviceStatusInfo",
+            "search": { "deviceSearch": { "id": device_id } },
+        });
+        let mut result = self.call("Get", params).await?;
+        result.as_array_mut()
+            .and_then(|statuses| statuses.first_mut())
+            .map(Value::take)
+            .ok_or(GeotabError::DeviceNotFound)
+    }
+
+    pub async fn vehicle_data(&self, device_id: &str) -> Result<GeotabVehicleData, GeotabError> {
+        let odometer_meters = self.latest_status_data(device_id, "DiagnosticOdometerId").await?
+            .ok_or(GeotabError::MissingData("odometer reading"))?;
+
+        // electric vehicles report state of charge instead of a fuel level
+        let fuel_level = match self.latest_status_data(device_id, "DiagnosticFuelLevelId").await? {
+            Some(level) => level,
+            None => self.latest_status_data(device_id, "DiagnosticStateOfChargeId").await?
+                .ok_or(GeotabError::MissingData("fuel or charge level"))?,
+        };
+
+        let status = self.device_status(device_id).await?;
+        let (Some(latitude), Some(longitude)) = (status["latitude"].as_f64(), status["longitude"].as_f64()) else {
+            return Err(GeotabError::MissingData("GPS position"));
+        };
+
+        Ok(GeotabVehicleData {
+            odometer: (odometer_meters / METERS_PER_MILE).round() as i32,
+            fuel_level: fuel_level.round().clamp(0.0, 100.0) as i32,
+            latitude,
+            longitude,
+        })
+    }
+
+    async fn pulse_relay(&self, device_id: &str, channel: u8) -> Result<(), GeotabError> {
+        // Geotab queues messages for devices that are not connected, the relay would fire whenever it reconnects
+        let status = self.device_status(device_id).await?;
+        if status["isDeviceCommunicating"].as_bool() == Some(false) {
+            return Err(GeotabError::DeviceOffline);
+        }
+
+        let params = json!({
+            "typeName": "TextMessage",
+            "entity": {
+                "device": { "id": device_id },
+                "isDirectionToVehicle": true,
+                "messageContent": {
+                    "contentType": "IoxOutput",
+                    "channel": channel,
+                    "isRelayOn": true,
+                },
+            },
+        });
+        match self.call("Add", params).await {
+            Ok(_) => Ok(()),
+            // the device refused the message, e.g. no IOX relay is installed
+            Err(GeotabError::Api { message, .. }) => Err(GeotabError::CommandRejected(message)),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn lock_doors(&self, device_id: &str) -> Result<(), GeotabError> {
+        self.pulse_relay(device_id, self.config.lock_channel).await
+    }
+
+    pub async fn unlock_doors(&self, device_id: &str) -> Result<(), GeotabError> {
+        self.pulse_relay(device_id, self.config.unlock_channel).await
+    }
+
+    pub async fn honk_horn(&self, device_id: &str) -> Result<(), GeotabError> {
+        let channel = self.config.horn_channel.ok_or(GeotabError::NoHornChannel)?;
+        self.pulse_relay(device_id, channel).await
+    }
+}
+
+fn api_error(error: &Value) -> GeotabError {
+    // the specific exception is nested under errors, the outer one is a generic JSONRPCError
+    let inner = error["errors"].as_array().and_then(|errors| errors.first()).unwrap_or(error);
+    GeotabError::Api {
+        name: inner["name"].as_str().unwrap_or("UnknownError").to_string(),
+        message: inner["message"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn with_credentials(params: &Value, session: &GeotabSession) -> Value {
+    let mut params = params.clone();
+    params["credentials"] = json!(session.credentials);
+    params
+}
+
+fn env_or_err(key: &'static str) -> Result<String, GeotabError> {
+    env::var(key).map_err(|_| GeotabError::MissingConfig(key))
+}
+
+fn env_or_default(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_string())
+}
+
+// Process-wide client so the Geotab session is shared between requests
+static GEOTAB_CLIENT: OnceCell<GeotabClient> = OnceCell::const_new();
+
+async fn geotab_client() -> Result<&'static GeotabClient, GeotabError> {
+    GEOTAB_CLIENT
+        .get_or_try_init(|| async { GeotabConfig::from_env().map(GeotabClient::new) })
+        .await
+}
+
+pub async fn geotab_vehicle_data(device_id: &str) -> Result<GeotabVehicleData, GeotabError> {
+    geotab_client().await?.vehicle_data(device_id).await
+}
+
+pub async fn geotab_lock_doors(device_id: &str) -> Result<(), GeotabError> {
+    geotab_client().await?.lock_doors(device_id).await
+}
+
+pub async fn geotab_unlock_doors(device_id: &str) -> Result<(), GeotabError> {
+    geotab_client().await?.unlock_doors(device_id).await
+}
+
+pub async fn geotab_honk_horn(device_id: &str) -> Result<(), GeotabError> {
+    geotab_client().await?.honk_horn(device_id).await
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::integration::mock_http;
+
+    // my.geotab.com answers every JSON-RPC call with 200, failures are reported in the body
+    async fn mock_geotab(handler: impl Fn(&Value) -> Value + Send + Sync + 'static) -> mock_http::MockServer {
+        mock_http::serve(move |request| (200, handler(&request.body))).await
+    }
+
+    fn client(server: &mock_http::MockServer) -> GeotabClient {
+        GeotabClient::new(GeotabConfig {
+            server: server.url.clone(),
+            database: String::from("veygo"),
+            user_name: String::from("fleet@veygo.rent"),
+            password: String::from("secret"),
+            lock_channel: 1,
+            unlock_channel: 2,
+            horn_channel: None,
+        })
+    }
+
+    fn calls(server: &mock_http::MockServer) -> Vec<Value> {
+        server.requests().into_iter().map(|request| request.body).collect()
+    }
+
+    fn authenticated(session_id: &str) -> Value {
+        json!({ "result": {
+            "credentials": { "database": "veygo", "userName": "fleet@veygo.rent", "sessionId": session_id },
+            "path": "ThisServer",
+        }})
+    }
+
+    fn session_of(request: &Value) -> Option<&str> {
+        request["params"]["credentials"]["sessionId"].as_str()
+    }
+
+    fn fleet_handler(request: &Value) -> Value {
+        match request["method"].as_str() {
+            Some("Authenticate") => authenticated("session-1"),
+            Some("Get") => match request["params"]["search"]["diagnosticSearch"]["id"].as_str() {
+                Some("DiagnosticOdometerId") => json!({ "result": [{ "data": 19_312.128 }] }),
+                Some("DiagnosticFuelLevelId") => json!({ "result": [{ "data": 62.6 }] }),
+                Some(_) => json!({ "result": [] }),
+                None => json!({ "result": [{ "latitude": 40.4237, "longitude": -86.9212, "isDeviceCommunicating": true }] }),
+            },
+            _ => json!({ "result": "message-1" }),
+        }
+    }
+
+    #[tokio::test]
+    async fn authenticates_once_and_reads_vehicle_data() {
+        let server = mock_geotab(fleet_handler).await;
+
+        let data = client(&server).vehicle_data("b12").await.unwrap();
+        assert_eq!(data, GeotabVehicleData { odometer: 12, fuel_level: 63, latitude: 40.4237, longitude: -86.9212 });
+
+        let requests = calls(&server);
+        let sign_ins = requests.iter().filter(|r| r["method"] == "Authenticate").count();
+        assert_eq!(sign_ins, 1);
+        assert_eq!(requests[0]["params"]["userName"], "fleet@veygo.rent");
+        assert!(requests.iter()
+            .filter(|r| r["method"] == "Get")
+            .all(|r| session_of(r) == Some("session-1") && r["params"]["search"]["deviceSearch"]["id"] == "b12"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_state_of_charge() {
+        let server = mock_geotab(|request| {
+            match request["params"]["search"]["diagnosticSearch"]["id"].as_str() {
+                Some("DiagnosticFuelLevelId") => json!({ "result": [] }),
+                Some("DiagnosticStateOfChargeId") => json!({ "result": [{ "data": 81.0 }] }),
+                _ => fleet_handler(request),
+            }
+        }).await;
+
+        let data = client(&server).vehicle_data("b12").await.unwrap();
+        assert_eq!(data.fuel_level, 81);
+    }
+
+    #[tokio::test]
+    async fn signs_in_again_when_session_expires() {
+        let sign_ins = Arc::new(Mutex::new(0));
+        let counter = sign_ins.clone();
+        let server = mock_geotab(move |request| {
+            if request["method"] == "Authenticate" {
+                let mut count = counter.lock().unwrap();
+                *count += 1;
+                return authenticated(&format!("session-{}", count));
+            }
+            if session_of(request) == Some("session-1") {
+                return json!({ "error": {
+                    "name": "JSONRPCError",
+                    "message": "Incorrect login credentials",
+                    "errors": [{ "name": "InvalidUserException", "message": "Incorrect login credentials" }],
+                }});
+            }
+            fleet_handler(request)
+        }).await;
+
+        client(&server).unlock_doors("b12").await.unwrap();
+
+        assert_eq!(*sign_ins.lock().unwrap(), 2);
+        let requests = calls(&server);
+        let sessions = requests.iter()
+            .filter(|r| r["method"] != "Authenticate")
+            .map(|r| (r["method"].as_str(), session_of(r)))
+            .collect::<Vec<_>>();
+        assert_eq!(sessions, vec![
+            (Some("Get"), Some("session-1")),
+            (Some("Get"), Some("session-2")),
+            (Some("Add"), Some("session-2")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn lock_and_unlock_pulse_their_relay() {
+        let server = mock_geotab(fleet_handler).await;
+        let client = client(&server);
+
+        client.lock_doors("b12").await.unwrap();
+        client.unlock_doors("b12").await.unwrap();
+
+        let requests = calls(&server);
+        let messages = requests.iter().filter(|r| r["method"] == "Add").collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        for (message, channel) in messages.iter().zip([1, 2]) {
+            let entity = &message["params"]["entity"];
+            assert_eq!(message["params"]["typeName"], "TextMessage");
+            assert_eq!(entity["device"]["id"], "b12");
+            assert_eq!(entity["messageContent"]["contentType"], "IoxOutput");
+            assert_eq!(entity["messageContent"]["channel"], channel);
+        }
+    }
+
+    #[tokio::test]
+    async fn surfaces_api_errors() {
+        let server = mock_geotab(|request| {
+            if request["method"] == "Authenticate" {
+                return authenticated("session-1");
+            }
+            json!({ "error": { "name": "JSONRPCError", "errors": [{ "name": "ArgumentException", "message": "Device not found" }] } })
+        }).await;
+        let client = client(&server);
+
+        let err = client.vehicle_data("missing").await.unwrap_err();
+        assert_eq!(err, GeotabError::Api { name: String::from("ArgumentException"), message: String::from("Device not found") });
+        assert_eq!(client.honk_horn("b12").await, Err(GeotabError::NoHornChannel));
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_credentials() {
+        let server = mock_geotab(|_| {
+            json!({ "error": { "name": "JSONRPCError", "errors": [{ "name": "InvalidUserException", "message": "Incorrect login credentials" }] } })
+        }).await;
+
+        assert_eq!(client(&server).lock_doors("b12").await, Err(GeotabError::Unauthorized));
+    }
+
+    #[tokio::test]
+    async fn does_not_queue_commands_for_offline_devices() {
+        let server = mock_geotab(|request| {
+            if request["params"]["typeName"] == "DeviceStatusInfo" {
+                return json!({ "result": [{ "latitude": 40.4237, "longitude": -86.9212, "isDeviceCommunicating": false }] });
+            }
+            fleet_handler(request)
+        }).await;
+
+        assert_eq!(client(&server).lock_doors("b12").await, Err(GeotabError::DeviceOffline));
+        assert!(calls(&server).iter().all(|r| r["method"] != "Add"));
+    }
+
+    #[tokio::test]
+    async fn refused_messages_are_rejections() {
+        let server = mock_geotab(|request| {
+            if request["method"] == "Add" {
+                return json!({ "error": { "name": "JSONRPCError", "errors": [{ "name": "ArgumentException", "message": "No IOX relay installed" }] } });
+            }
+            fleet_handler(request)
+        }).await;
+
+        let err = client(&server).unlock_doors("b12").await.unwrap_err();
+        assert_eq!(err, GeotabError::CommandRejected(String::from("No IOX relay installed")));
+    }
+}
@@ -0,0 +1,100 @@
+// Local HTTP server standing in for vendor APIs in the integration tests
+use std::sync::{Arc, Mutex};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+type Handler = Arc<dyn Fn(&MockRequest) -> (u16, Value) + Send + Sync>;
+
+// every request gets its own connection and the reply closes it, so no keep-alive handling is needed
+pub async fn serve(handler: impl Fn(&MockRequest) -> (u16, Value) + Send + Sync + 'static) -> MockServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let handler: Handler = Arc::new(handler);
+
+    let recorded = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else { break };
+            let handler = handler.clone();
+            let recorded = recorded.clone();
+            tokio::spawn(async move {
+                let (mut stream, request) = read_request(stream).await;
+                let (status, body) = handler(&request);
+                recorded.lock().unwrap().push(request);
+                let body = if body.is_null() { String::new() } else { body.to_string() };
+                let reply = format!(
+                    "HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            });
+        }
+    });
+    MockServer { url, requests }
+}
+
+async fn read_request(mut stream: TcpStream) -> (TcpStream, MockRequest) {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = stream.read(&mut chunk).await.unwrap();
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(header_end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+            let mut lines = head.lines();
+            let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+            let method = request_line.next().unwrap_or_default().to_string();
+            let path = request_line.next().unwrap_or_default().to_string();
+            let headers = lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect::<Vec<_>>();
+            let content_length = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.parse::<usize>().ok())
+                .unwrap_or(0);
+
+            let body_start = header_end + 4;
+            if buffer.len() >= body_start + content_length {
+                let body = &buffer[body_start..body_start + content_length];
+                let body = serde_json::from_slice(body).unwrap_or(Value::Null);
+                return (stream, MockRequest { method, path, headers, body });
+            }
+        }
+        if read == 0 {
+            panic!("connection closed before the request arrived");
+        }
+    }
+}
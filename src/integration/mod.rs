@@ -3,4 +3,8 @@ pub mod gcloud_storage_veygo;
 pub mod stripe_veygo;
 pub mod twilio_veygo;
 pub mod tesla_veygo;
+pub mod geotab_veygo;
+pub mod revers_veygo;
 pub mod mailgun_veygo;
+#[cfg(test)]
+mod mock_http;
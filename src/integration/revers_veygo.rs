@@ -0,0 +1,360 @@
+use std::env;
+use std::fmt;
+use std::time::Duration;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::OnceCell;
+
+const DEFAULT_API_URL: &str = "https://api.revers.io";
+const KILOMETERS_PER_MILE: f64 = 1.609344;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReversError {
+    MissingConfig(&'static str),
+    Unauthorized,
+    VehicleNotFound,
+    VehicleOffline,
+    RateLimited,
+    CommandFailed(String),
+    CommandTimedOut,
+    Api { status: u16, code: String, message: String },
+    Transport(String),
+    InvalidResponse(String),
+}
+
+impl fmt::Display for ReversError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReversError::MissingConfig(key) => write!(f, "Environment variable {} is not set", key),
+            ReversError::Unauthorized => write!(f, "Revers rejected the API key"),
+            ReversError::VehicleNotFound => write!(f, "Revers does not know this vehicle"),
+            ReversError::VehicleOffline => write!(f, "Vehicle is offline"),
+            ReversError::RateLimited => write!(f, "Revers rate limit reached"),
+            ReversError::CommandFailed(reason) => write!(f, "Revers command failed: {}", reason),
+            ReversError::CommandTimedOut => write!(f, "Revers command did not finish in time"),
+            ReversError::Api { status, code, message } => write!(f, "Revers API error {} {}: {}", status, code, message),
+            ReversError::Transport(err) => write!(f, "Revers request failed: {}", err),
+            ReversError::InvalidResponse(err) => write!(f, "Revers response could not be parsed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReversError {}
+
+#[derive(Debug, Clone)]
+pub struct ReversConfig {
+    pub api_url: String,
+    pub api_key: String,
+    // commands are queued by Revers and confirmed once the car acknowledges them
+    pub command_poll_interval: Duration,
+    pub command_poll_attempts: u32,
+}
+
+impl ReversConfig {
+    pub fn from_env() -> Result<ReversConfig, ReversError> {
+        Ok(ReversConfig {
+            api_url: env::var("REVERS_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+            api_key: env::var("REVERS_API_KEY").map_err(|_| ReversError::MissingConfig("REVERS_API_KEY"))?,
+            command_poll_interval: Duration::from_secs(1),
+            command_poll_attempts: 15,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReversVehicleStatus {
+    pub odometer: i32,
+    pub fuel_level: i32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct StatusBody {
+    odometer: OdometerBody,
+    fuel: FuelBody,
+    location: Option<LocationBody>,
+}
+
+#[derive(Deserialize)]
+struct OdometerBody {
+    value: f64,
+    unit: String,
+}
+
+#[derive(Deserialize)]
+struct FuelBody {
+    level_percent: f64,
+}
+
+#[derive(Deserialize)]
+struct LocationBody {
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Deserialize)]
+struct CommandBody {
+    id: String,
+    status: String,
+    error: Option<ErrorDetail>,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    code: String,
+    message: String,
+}
+
+pub struct ReversClient {
+    config: ReversConfig,
+    http: reqwest::Client,
+}
+
+impl ReversClient {
+    pub fn new(config: ReversConfig) -> ReversClient {
+        ReversClient { config, http: reqwest::Client::new() }
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<T, ReversError> {
+        let url = format!("{}{}", self.config.api_url.trim_end_matches('/'), path);
+        let mut req = self.http
+            .request(method, &url)
+            .header(reqwest::header::AUTHORIZATION, format!("Bearer {}", self.config.api_key));
+        if let Some(b) = body {
+            req = req.header(reqwest::header::CONTENT_TYPE, "application/json");
+            req = req.body(b);
+        }
+
+        let resp = req.send().await.map_err(|err| ReversError::Transport(err.to_string()))?;
+        let status = resp.status();
+        let text = resp.text().await.map_err(|err| ReversError::Transport(err.to_string()))?;
+
+        if !status.is_success() {
+            let detail = serde_json::from_str::<ErrorBody>(&text).ok().map(|body| body.error);
+            return Err(match (status.as_u16(), detail) {
+                (401 | 403, _) => ReversError::Unauthorized,
+                (404, _) => ReversError::VehicleNotFound,
+                (429, _) => ReversError::RateLimited,
+                (_, Some(detail)) if detail.code == "vehicle_offline" => ReversError::VehicleOffline,
+                (status, Some(detail)) => ReversError::Api { status, code: detail.code, message: detail.message },
+                (status, None) => ReversError::Api { status, code: String::from("unknown"), message: text },
+            });
+        }
+        serde_json::from_str(&text).map_err(|err| ReversError::InvalidResponse(err.to_string()))
+    }
+
+    pub async fn vehicle_status(&self, vehicle_id: &str) -> Result<ReversVehicleStatus, ReversError> {
+        let path = format!("/v1/vehicles/{}/status", vehicle_id);
+        let body: StatusBody = self.request(reqwest::Method::GET, &path, None).await?;
+
+        let odometer_miles = match body.odometer.unit.as_str() {
+            "mi" => body.odometer.value,
+            "km" => body.odometer.value / KILOMETERS_PER_MILE,
+            unit => return Err(ReversError::InvalidResponse(format!("unknown odometer unit {}", unit))),
+        };
+        Ok(ReversVehicleStatus {
+            odometer: odometer_miles.round() as i32,
+            fuel_level: body.fuel.level_percent.round().clamp(0.0, 100.0) as i32,
+            latitude: body.location.as_ref().map(|location| location.latitude),
+            longitude: body.location.as_ref().map(|location| location.longitude),
+        })
+    }
+
+    async fn send_command(&self, vehicle_id: &str, command: &str) -> Result<(), ReversError> {
+        let path = format!("/v1/vehicles/{}/commands", vehicle_id);
+        let body = json!({ "type": command }).to_string();
+        let mut command: CommandBody = self.request(reqwest::Method::POST, &path, Some(body)).await?;
+
+        let mut attempts = 0;
+        loop {
+            match command.status.as_str() {
+                "succeeded" => return Ok(()),
+                "failed" => {
+                    let reason = command.error.map(|error| error.message).unwrap_or_default();
+                    return Err(ReversError::CommandFailed(reason));
+                }
+                _ => {}
+            }
+            if attempts >= self.config.command_poll_attempts {
+                return Err(ReversError::CommandTimedOut);
+            }
+            attempts += 1;
+            tokio::time::sleep(self.config.command_poll_interval).await;
+            let path = format!("/v1/commands/{}", command.id);
+            command = self.request(reqwest::Method::GET, &path, None).await?;
+        }
+    }
+
+    pub async fn lock_doors(&self, vehicle_id: &str) -> Result<(), ReversError> {
+        self.send_command(vehicle_id, "lock").await
+    }
+
+    pub async fn unlock_doors(&self, vehicle_id: &str) -> Result<(), ReversError> {
+        self.send_command(vehicle_id, "unlock").await
+    }
+}
+
+// Process-wide client so connections are reused between requests
+static REVERS_CLIENT: OnceCell<ReversClient> = OnceCell::const_new();
+
+async fn revers_client() -> Result<&'static ReversClient, ReversError> {
+    REVERS_CLIENT
+        .get_or_try_init(|| async { ReversConfig::from_env().map(ReversClient::new) })
+        .await
+}
+
+pub async fn revers_vehicle_status(vehicle_id: &str) -> Result<ReversVehicleStatus, ReversError> {
+    revers_client().await?.vehicle_status(vehicle_id).await
+}
+
+pub async fn revers_lock_doors(vehicle_id: &str) -> Result<(), ReversError> {
+    revers_client().await?.lock_doors(vehicle_id).await
+}
+
+pub async fn revers_unlock_doors(vehicle_id: &str) -> Result<(), ReversError> {
+    revers_client().await?.unlock_doors(vehicle_id).await
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use crate::integration::mock_http;
+
+    fn client(server: &mock_http::MockServer) -> ReversClient {
+        ReversClient::new(ReversConfig {
+            api_url: server.url.clone(),
+            api_key: String::from("test-key"),
+            command_poll_interval: Duration::from_millis(5),
+            command_poll_attempts: 3,
+        })
+    }
+
+    fn status_body(unit: &str, value: f64) -> Value {
+        json!({
+            "vehicle_id": "rv-1",
+            "odometer": { "value": value, "unit": unit },
+            "fuel": { "level_percent": 47.5 },
+            "location": { "latitude": 40.4237, "longitude": -86.9212 },
+        })
+    }
+
+    #[tokio::test]
+    async fn reads_vehicle_status() {
+        let server = mock_http::serve(|_| (200, status_body("mi", 20_431.6))).await;
+
+        let status = client(&server).vehicle_status("rv-1").await.unwrap();
+        assert_eq!(status, ReversVehicleStatus {
+            odometer: 20_432,
+            fuel_level: 48,
+            latitude: Some(40.4237),
+            longitude: Some(-86.9212),
+        });
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/v1/vehicles/rv-1/status");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer test-key"));
+    }
+
+    #[tokio::test]
+    async fn converts_kilometers_to_miles() {
+        let server = mock_http::serve(|_| (200, status_body("km", 16_093.44))).await;
+
+        let status = client(&server).vehicle_status("rv-1").await.unwrap();
+        assert_eq!(status.odometer, 10_000);
+    }
+
+    #[tokio::test]
+    async fn status_without_location_is_allowed() {
+        let server = mock_http::serve(|_| {
+            (200, json!({ "odometer": { "value": 10.0, "unit": "mi" }, "fuel": { "level_percent": 100.0 } }))
+        }).await;
+
+        let status = client(&server).vehicle_status("rv-1").await.unwrap();
+        assert_eq!((status.latitude, status.longitude), (None, None));
+    }
+
+    #[tokio::test]
+    async fn lock_waits_for_the_command_to_finish() {
+        let server = mock_http::serve(|request| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("POST", "/v1/vehicles/rv-1/commands") => (202, json!({ "id": "cmd-9", "status": "pending" })),
+                ("GET", "/v1/commands/cmd-9") => (200, json!({ "id": "cmd-9", "status": "succeeded" })),
+                _ => (404, json!({ "error": { "code": "not_found", "message": "Unknown path" } })),
+            }
+        }).await;
+
+        client(&server).lock_doors("rv-1").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body, json!({ "type": "lock" }));
+        assert_eq!(requests[1].path, "/v1/commands/cmd-9");
+    }
+
+    #[tokio::test]
+    async fn failed_command_is_reported() {
+        let server = mock_http::serve(|_| {
+            (200, json!({ "id": "cmd-1", "status": "failed", "error": { "code": "door_open", "message": "Driver door is open" } }))
+        }).await;
+
+        let err = client(&server).lock_doors("rv-1").await.unwrap_err();
+        assert_eq!(err, ReversError::CommandFailed(String::from("Driver door is open")));
+    }
+
+    #[tokio::test]
+    async fn pending_command_times_out() {
+        let server = mock_http::serve(|_| (200, json!({ "id": "cmd-1", "status": "pending" }))).await;
+
+        let err = client(&server).unlock_doors("rv-1").await.unwrap_err();
+        assert_eq!(err, ReversError::CommandTimedOut);
+        // the initial request plus one poll per attempt
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn maps_http_errors() {
+        let server = mock_http::serve(|request| {
+            match request.path.as_str() {
+                "/v1/vehicles/unauthorized/status" => (401, Value::Null),
+                "/v1/vehicles/missing/status" => (404, json!({ "error": { "code": "not_found", "message": "No such vehicle" } })),
+                "/v1/vehicles/busy/status" => (429, Value::Null),
+                "/v1/vehicles/asleep/commands" => (409, json!({ "error": { "code": "vehicle_offline", "message": "Vehicle is not connected" } })),
+                _ => (500, json!({ "error": { "code": "internal", "message": "Upstream failure" } })),
+            }
+        }).await;
+        let client = client(&server);
+
+        assert_eq!(client.vehicle_status("unauthorized").await.unwrap_err(), ReversError::Unauthorized);
+        assert_eq!(client.vehicle_status("missing").await.unwrap_err(), ReversError::VehicleNotFound);
+        assert_eq!(client.vehicle_status("busy").await.unwrap_err(), ReversError::RateLimited);
+        assert_eq!(client.unlock_doors("asleep").await.unwrap_err(), ReversError::VehicleOffline);
+        assert_eq!(client.vehicle_status("broken").await.unwrap_err(), ReversError::Api {
+            status: 500,
+            code: String::from("internal"),
+            message: String::from("Upstream failure"),
+        });
+    }
+
+    #[tokio::test]
+    async fn malformed_status_is_an_invalid_response() {
+        let server = mock_http::serve(|_| (200, json!({ "odometer": { "value": 1.0, "unit": "furlong" }, "fuel": { "level_percent": 5.0 } }))).await;
+
+        let err = client(&server).vehicle_status("rv-1").await.unwrap_err();
+        assert!(matches!(err, ReversError::InvalidResponse(_)));
+    }
+}
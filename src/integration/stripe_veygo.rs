@@ -2,11 +2,12 @@ use crate::{model, helper_model};
 use std::env;
 use tokio::sync::OnceCell;
 
-use stripe::{Client, StripeError, ApiErrorsType, ApiErrorsCode};
+use stripe::{Client, StripeError, StripeRequest, ApiErrorsType, ApiErrorsCode, IdempotencyKey, RequestStrategy};
 use stripe_types::Currency;
 
 use stripe_core::payment_intent::{
     CapturePaymentIntent, CancelPaymentIntent, CreatePaymentIntent, CreatePaymentIntentOffSession,
+    IncrementAuthorizationPaymentIntent,
     CreatePaymentIntentPaymentMethodOptions, CreatePaymentIntentPaymentMethodOptionsCardRequestExtendedAuthorization,
     CreatePaymentIntentPaymentMethodOptionsCardRequestIncrementalAuthorization,
     CreatePaymentIntentPaymentMethodOptionsCardRequestMulticapture, CreatePaymentIntentPaymentMethodOptionsCard
@@ -151,7 +152,6 @@ pub async fn update_stripe_customer_address(
     }
 }
 
-#[allow(dead_code)]
 pub async fn create_stripe_refund(
     customer_id: &String,
     payment_intent_id: &String,
@@ -244,15 +244,14 @@ pub async fn attach_payment_method_to_stripe_customer(
     }
 }
 
-pub async fn create_payment_intent(
+fn payment_intent_request(
     customer_id_data: &String,
     payment_id_data: &String,
     amount: i64,
     capture_method: PaymentIntentCaptureMethod,
     description: &String,
-) -> Result<PaymentIntent, helper_model::VeygoError> {
-    let client = stripe_client().await;
-    let result = CreatePaymentIntent::new(amount, Currency::USD)
+) -> CreatePaymentIntent {
+    CreatePaymentIntent::new(amount, Currency::USD)
         .expand(vec![String::from("latest_charge")])
         .capture_method(capture_method)
         .confirm(true)
@@ -270,9 +269,9 @@ pub async fn create_payment_intent(
             }),
             ..Default::default()
         })
-        .send(client)
-        .await;
+}
 
+fn payment_intent_result(result: Result<PaymentIntent, StripeError>) -> Result<PaymentIntent, helper_model::VeygoError> {
     match result {
         Ok(pi) => {
             let pi_status: model::PaymentType = pi.status.clone().into();
@@ -299,7 +298,42 @@ pub async fn create_payment_intent(
     }
 }
 
-#[allow(dead_code)]
+pub async fn create_payment_intent(
+    customer_id_data: &String,
+    payment_id_data: &String,
+    amount: i64,
+    capture_method: PaymentIntentCaptureMethod,
+    description: &String,
+) -> Result<PaymentIntent, helper_model::VeygoError> {
+    let client = stripe_client().await;
+    let result = payment_intent_request(customer_id_data, payment_id_data, amount, capture_method, description)
+        .send(client)
+        .await;
+    payment_intent_result(result)
+}
+
+// For charges a scheduled job may retry, Stripe answers a repeated key with the first attempt's
+// result instead of charging again
+pub async fn create_payment_intent_idempotent(
+    customer_id_data: &String,
+    payment_id_data: &String,
+    amount: i64,
+    capture_method: PaymentIntentCaptureMethod,
+    description: &String,
+    idempotency_key: &str,
+) -> Result<PaymentIntent, helper_model::VeygoError> {
+    let Ok(idempotency_key) = IdempotencyKey::new(idempotency_key) else {
+        return Err(helper_model::VeygoError::InputDataError)
+    };
+    let client = stripe_client().await;
+    let result = payment_intent_request(customer_id_data, payment_id_data, amount, capture_method, description)
+        .customize()
+        .request_strategy(RequestStrategy::Idempotent(idempotency_key))
+        .send(client)
+        .await;
+    payment_intent_result(result)
+}
+
 pub async fn drop_auth(intent_id: &str) -> Result<PaymentIntent, helper_model::VeygoError> {
     let client = stripe_client().await;
     let result = CancelPaymentIntent::new(intent_id).send(client).await;
@@ -356,3 +390,29 @@ pub async fn capture_payment(intent_id: &str, amount_final: Option<(i64, bool)>)
         }
     }
 }
+
+pub async fn increment_authorization(intent_id: &str, new_amount: i64) -> Result<PaymentIntent, helper_model::VeygoError> {
+    // `new_amount` is the updated total of the hold, not the increment
+    let client = stripe_client().await;
+    let result = IncrementAuthorizationPaymentIntent::new(intent_id, new_amount).send(client).await;
+
+    match result {
+        Ok(result) => { Ok(result) }
+        Err(e) => {
+            match e {
+                StripeError::Stripe(api_err, _) => {
+                    let api_error = api_err.type_;
+                    if api_error == ApiErrorsType::CardError {
+                        return Err(helper_model::VeygoError::CardDeclined)
+                    } else if api_error == ApiErrorsType::InvalidRequestError {
+                        return Err(helper_model::VeygoError::CanNotCapture)
+                    }
+                    Err(helper_model::VeygoError::InternalServerError)
+                }
+                _ => {
+                    Err(helper_model::VeygoError::InternalServerError)
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,6 @@
 use std::env;
 use twilio::{Call, Client, Message, OutboundCall, OutboundMessage, TwilioError};
 
-#[allow(dead_code)]
 pub async fn send_text(to: &str, msg: &str) -> Result<Message, TwilioError> {
     let tw_acc_sid = env::var("TWILIO_SID").expect("TWILIO_SID must be set");
     let tw_token = env::var("TWILIO_AUTH_TOKEN").expect("TWILIO_AUTH_TOKEN must be set");
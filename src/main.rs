@@ -100,6 +100,8 @@ async fn main() {
     let addr = IpAddr::from_str("::0").unwrap();
     // add routines
     spawn(scheduled_tasks::nightly_task());
+    spawn(scheduled_tasks::frequent_task());
+    spawn(scheduled_tasks::telemetry_task());
     // starting the server
     warp::serve(httpd)
         .run((addr, port))
@@ -1,6 +1,9 @@
-use crate::connection_pool;
+use std::cmp::max;
+use crate::{connection_pool, integration, methods, model, proj_config, schema};
 use crate::helper_model::VeygoError;
+use chrono::{DateTime, Datelike, TimeDelta, Utc};
 use diesel::prelude::*;
+use rust_decimal::prelude::*;
 use rand::{RngExt};
 use rand::seq::SliceRandom;
 
@@ -47,3 +50,558 @@ pub async fn generate_unique_agreement_confirmation() -> Result<String, VeygoErr
         }
     }
 }
+
+pub async fn cancellation_fee_for(apartment_id: i32, time_before_pickup: TimeDelta) -> Result<Decimal, VeygoError> {
+    // Each tier reads as "cancelling less than `hours_before` hours ahead costs `fee`".
+    // The tightest tier the cancellation falls into wins.
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::cancellation_fees::dsl as cf_q;
+    let tiers = cf_q::cancellation_fees
+        .filter(cf_q::apartment_id.eq(apartment_id))
+        .order(cf_q::hours_before.asc())
+        .get_results::<model::CancellationFee>(&mut conn);
+
+    let Ok(tiers) = tiers else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    let fee = tiers
+        .iter()
+        .find(|tier| time_before_pickup < TimeDelta::hours(tier.hours_before as i64))
+        .map(|tier| tier.fee)
+        .unwrap_or(Decimal::zero());
+
+    Ok(fee)
+}
+
+// Whether a promo's renter, apartment and university limits allow it for this booking.
+// An apartment with `uni_id` 1 is a university itself.
+pub fn promo_applies_to(promo: &model::Promo, renter_id: i32, apartment_id: i32, apartment_uni_id: i32) -> bool {
+    if let Some(specified_user_id) = promo.user_id && renter_id != specified_user_id {
+        return false;
+    }
+    if let Some(specified_apartment_id) = promo.apt_id &&
+        !(apartment_id == specified_apartment_id && apartment_uni_id != 1)
+    {
+        return false;
+    }
+    if let Some(specified_uni_id) = promo.uni_id &&
+        !(apartment_id == specified_uni_id && apartment_uni_id == 1 || apartment_uni_id == specified_uni_id)
+    {
+        return false;
+    }
+    true
+}
+
+// The promo behind `code` when this renter may use it for a booking starting at `start_time`, None otherwise.
+// agreement/quote and agreement/new both go through here so a quoted promo is the one honored at booking.
+pub async fn verify_promo(
+    code: &str,
+    renter_id: i32,
+    apartment: &model::Apartment,
+    start_time: DateTime<Utc>,
+) -> Result<Option<model::Promo>, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::promos::dsl as promos_query;
+    let promo = promos_query::promos
+        .filter(promos_query::code.eq(code))
+        .filter(promos_query::is_enabled)
+        .filter(promos_query::exp.gt(start_time))
+        .get_result::<model::Promo>(&mut conn)
+        .optional();
+    let promo = match promo {
+        Ok(Some(promo)) => promo,
+        Ok(None) => return Ok(None),
+        Err(_) => return Err(VeygoError::InternalServerError),
+    };
+
+    // a renter uses each promo once
+    use schema::agreements::dsl as agreement_query;
+    let count_of_this_renter_usage = agreement_query::agreements
+        .filter(agreement_query::promo_id.eq(Some(&promo.code)))
+        .filter(agreement_query::status.ne(model::AgreementStatus::Canceled))
+        .filter(agreement_query::renter_id.eq(renter_id))
+        .count()
+        .get_result::<i64>(&mut conn);
+    let Ok(count_of_this_renter_usage) = count_of_this_renter_usage else {
+        return Err(VeygoError::InternalServerError);
+    };
+    if count_of_this_renter_usage >= 1 {
+        return Ok(None);
+    }
+
+    // and a one-time promo is used once overall
+    if promo.is_one_time {
+        let count_of_agreements = agreement_query::agreements
+            .filter(agreement_query::promo_id.eq(Some(&promo.code)))
+            .filter(agreement_query::status.ne(model::AgreementStatus::Canceled))
+            .count()
+            .get_result::<i64>(&mut conn);
+        let Ok(count_of_agreements) = count_of_agreements else {
+            return Err(VeygoError::InternalServerError);
+        };
+        if count_of_agreements >= 1 {
+            return Ok(None);
+        }
+    }
+
+    if !promo_applies_to(&promo, renter_id, apartment.id, apartment.uni_id) {
+        return Ok(None);
+    }
+    Ok(Some(promo))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewardHoursCheck {
+    Allowed,
+    // more hours than the trip is long
+    ExceedsTrip,
+    // more than is left of the plan's weekly allowance
+    ExceedsAllowance,
+}
+
+impl RewardHoursCheck {
+    pub fn rejection_message(&self) -> Option<&'static str> {
+        match self {
+            RewardHoursCheck::Allowed => None,
+            RewardHoursCheck::ExceedsTrip => Some("You are trying to redeem more hours than the rental period."),
+            RewardHoursCheck::ExceedsAllowance => Some("You have exceeded your free hours limit. Please upgrade your plan. "),
+        }
+    }
+}
+
+// Reward hours are counted per week, Monday 00:00 UTC up to the next Monday
+pub fn reward_week(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let current_naive_date = now.date_naive();
+    let days_from_monday = current_naive_date.weekday().num_days_from_monday() as i64;
+    let monday = current_naive_date - TimeDelta::days(days_from_monday);
+    let monday_start = DateTime::<Utc>::from_naive_utc_and_offset(monday.and_hms_opt(0, 0, 0).unwrap(), Utc);
+    (monday_start, monday_start + TimeDelta::days(7))
+}
+
+pub fn reward_hours_check(hours_using_reward: Decimal, trip_duration: TimeDelta, allowance: Decimal, used_this_week: Decimal) -> RewardHoursCheck {
+    let trip_hours = Decimal::new(trip_duration.num_minutes(), 0) / Decimal::new(60, 0);
+    if hours_using_reward > trip_hours {
+        RewardHoursCheck::ExceedsTrip
+    } else if allowance - used_this_week < hours_using_reward {
+        RewardHoursCheck::ExceedsAllowance
+    } else {
+        RewardHoursCheck::Allowed
+    }
+}
+
+// Shared by agreement/quote, agreement/new and agreement/modify, nothing is loaded when no hours are redeemed.
+// Hours already held by `excluded_agreement_id` are not counted against the renter
+pub async fn check_reward_hours(
+    renter: &model::Renter,
+    hours_using_reward: Decimal,
+    trip_duration: TimeDelta,
+    now: DateTime<Utc>,
+    excluded_agreement_id: Option<i32>,
+) -> Result<RewardHoursCheck, VeygoError> {
+    if hours_using_reward <= Decimal::zero() {
+        return Ok(RewardHoursCheck::Allowed);
+    }
+    let check = reward_hours_check(hours_using_reward, trip_duration, renter.plan_total_availability, Decimal::zero());
+    if check != RewardHoursCheck::Allowed {
+        return Ok(check);
+    }
+
+    let mut conn = connection_pool().await.get().unwrap();
+    let (week_start, week_end_exclusive) = reward_week(now);
+    use schema::reward_transactions::dsl as reward_q;
+    let mut query = reward_q::reward_transactions
+        .filter(reward_q::renter_id.eq(renter.id))
+        .filter(reward_q::transaction_time.ge(week_start))
+        .filter(reward_q::transaction_time.lt(week_end_exclusive))
+        .into_boxed();
+    if let Some(agreement_id) = excluded_agreement_id {
+        query = query.filter(reward_q::agreement_id.is_null().or(reward_q::agreement_id.ne(agreement_id)));
+    }
+    let used_free_hours = query
+        .select(diesel::dsl::sum(reward_q::duration))
+        .first::<Option<Decimal>>(&mut conn);
+    let Ok(used_free_hours) = used_free_hours else {
+        return Err(VeygoError::InternalServerError);
+    };
+    Ok(reward_hours_check(
+        hours_using_reward, trip_duration, renter.plan_total_availability, used_free_hours.unwrap_or(Decimal::zero()),
+    ))
+}
+
+// Writes the agreement and replaces its tax records together, so neither is saved without the other
+pub fn save_with_taxes(conn: &mut PgConnection, agreement: &model::Agreement, tax_ids: &[i32]) -> QueryResult<model::Agreement> {
+    conn.transaction(|conn| {
+        let saved = agreement.save_changes::<model::Agreement>(conn)?;
+
+        use schema::agreements_taxes::dsl as ag_tx_q;
+        diesel::delete(ag_tx_q::agreements_taxes.filter(ag_tx_q::agreement_id.eq(saved.id)))
+            .execute(conn)?;
+        let agreement_taxes = tax_ids.iter().map(|tax_id| model::AgreementTax {
+            agreement_id: saved.id,
+            tax_id: *tax_id,
+        }).collect::<Vec<model::AgreementTax>>();
+        diesel::insert_into(ag_tx_q::agreements_taxes)
+            .values(&agreement_taxes)
+            .execute(conn)?;
+        Ok(saved)
+    })
+}
+
+pub async fn reverse_reward_transactions(agreement_id: i32) -> Result<(), VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::reward_transactions::dsl as rt_q;
+    let transactions = rt_q::reward_transactions
+        .filter(rt_q::agreement_id.eq(agreement_id))
+        .order(rt_q::transaction_time.asc())
+        .get_results::<model::RewardTransaction>(&mut conn);
+
+    let Ok(transactions) = transactions else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    let net_duration: Decimal = transactions.iter().map(|rt| rt.duration).sum();
+    let Some(original) = transactions.iter().find(|rt| rt.duration > Decimal::zero()) else {
+        return Ok(());
+    };
+    if net_duration <= Decimal::zero() {
+        return Ok(());
+    }
+
+    // Credit back at the original transaction time, so it nets out within the same reward week
+    let result = diesel::insert_into(rt_q::reward_transactions)
+        .values((
+            rt_q::agreement_id.eq(Some(agreement_id)),
+            rt_q::duration.eq(-net_duration),
+            rt_q::renter_id.eq(original.renter_id),
+            rt_q::transaction_time.eq(original.transaction_time),
+        ))
+        .execute(&mut conn);
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(VeygoError::InternalServerError),
+    }
+}
+
+// Cancels every authorization on the agreement that was never captured, captured payments are left alone
+pub async fn release_auth_holds(agreement_id: i32) -> Result<usize, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::payments::dsl as pmt_q;
+    let holds = pmt_q::payments
+        .filter(pmt_q::agreement_id.eq(agreement_id))
+        .filter(pmt_q::payment_type.eq(model::PaymentType::RequiresCapture))
+        .get_results::<model::Payment>(&mut conn);
+    let Ok(holds) = holds else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    let mut released = 0;
+    for mut hold in holds {
+        let Some(intent_id) = hold.reference_number.clone() else {
+            continue
+        };
+        integration::stripe_veygo::drop_auth(&intent_id).await?;
+        hold.capture_before = None;
+        hold.payment_type = model::PaymentType::Canceled;
+        if hold.save_changes::<model::Payment>(&mut conn).is_err() {
+            return Err(VeygoError::InternalServerError);
+        }
+        released += 1;
+    }
+    Ok(released)
+}
+
+pub async fn refund_agreement_payments(agreement_id: i32, stripe_customer_id: &String, amount_limit: Option<Decimal>) -> Result<Decimal, VeygoError> {
+    // Refunds the most recent payments first; `None` refunds everything still refundable
+    refund_payments(agreement_id, stripe_customer_id, amount_limit, None).await
+}
+
+pub async fn refund_agreement_payments_except(agreement_id: i32, stripe_customer_id: &String, kept_reference: &str) -> Result<Decimal, VeygoError> {
+    // Refunds everything except the payment collected under `kept_reference`, e.g. a cancellation fee
+    refund_payments(agreement_id, stripe_customer_id, None, Some(kept_reference)).await
+}
+
+async fn refund_payments(agreement_id: i32, stripe_customer_id: &String, amount_limit: Option<Decimal>, kept_reference: Option<&str>) -> Result<Decimal, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::payments::dsl as pmt_q;
+    let mut query = pmt_q::payments
+        .filter(pmt_q::agreement_id.eq(agreement_id))
+        .filter(pmt_q::payment_type.eq(model::PaymentType::Succeeded))
+        .filter(pmt_q::amount.gt(pmt_q::refund_amount))
+        .filter(pmt_q::reference_number.is_not_null())
+        .into_boxed();
+    if let Some(kept_reference) = kept_reference {
+        query = query.filter(pmt_q::reference_number.ne(kept_reference));
+    }
+    let payments = query
+        .order(pmt_q::time.desc())
+        .get_results::<model::Payment>(&mut conn);
+
+    let Ok(payments) = payments else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    let mut total_refunded = Decimal::zero();
+    for mut payment in payments {
+        let mut refundable_2dp = (payment.amount - payment.refund_amount).round_dp(2);
+        if let Some(limit) = amount_limit {
+            refundable_2dp = refundable_2dp.min(limit - total_refunded).round_dp(2);
+        }
+        refundable_2dp.rescale(2);
+        if refundable_2dp <= Decimal::zero() {
+            break;
+        }
+
+        let intent_id = payment.reference_number.clone().unwrap();
+        integration::stripe_veygo::create_stripe_refund(stripe_customer_id, &intent_id, refundable_2dp.mantissa() as i64).await?;
+
+        payment.refund_amount += refundable_2dp;
+        if payment.save_changes::<model::Payment>(&mut conn).is_err() {
+            return Err(VeygoError::InternalServerError);
+        }
+        total_refunded += refundable_2dp;
+    }
+
+    Ok(total_refunded)
+}
+
+pub async fn settled_price(agreement: &model::Agreement) -> Result<methods::pricing::PriceBreakdown, VeygoError> {
+    // Final price of a returned trip, from the rates stored on the agreement and both snapshots
+    let (Some(actual_drop_off_time), Some(check_out_snapshot_id), Some(check_in_snapshot_id)) =
+        (agreement.actual_drop_off_time, agreement.vehicle_snapshot_before, agreement.vehicle_snapshot_after) else {
+        return Err(VeygoError::InputDataError);
+    };
+
+    let mut conn = connection_pool().await.get().unwrap();
+
+    // 1. durations, late return is billed on top of the reserved window
+    let trip_duration = agreement.rsvp_drop_off_time - agreement.rsvp_pickup_time;
+    let trip_duration_including_late_return =
+        max(actual_drop_off_time, agreement.rsvp_drop_off_time) - agreement.rsvp_pickup_time;
+
+    // net of any reward hours returned when the reservation was modified
+    use schema::reward_transactions::dsl as re_q;
+    let reward_used_sum = re_q::reward_transactions
+        .filter(re_q::renter_id.eq(agreement.renter_id))
+        .filter(re_q::agreement_id.eq(agreement.id))
+        .select(diesel::dsl::sum(re_q::duration))
+        .get_result::<Option<Decimal>>(&mut conn);
+    let Ok(reward_used_sum) = reward_used_sum else {
+        return Err(VeygoError::InternalServerError);
+    };
+    let reward_hours = max(reward_used_sum.unwrap_or(Decimal::ZERO), Decimal::ZERO);
+
+    let promo_amount = match agreement.promo_id.clone() {
+        None => { None }
+        Some(promo) => {
+            use schema::promos::dsl as p_q;
+            let discount = p_q::promos
+                .find(&promo)
+                .select(p_q::amount)
+                .get_result::<Decimal>(&mut conn);
+            let Ok(discount) = discount else {
+                return Err(VeygoError::InternalServerError);
+            };
+            Some(discount)
+        }
+    };
+
+    // 2. mileage package revenue
+    let (mileage_package_cost, miles_allowed) = match agreement.mileage_package_id {
+        None => {
+            // didn't select mp
+            (Decimal::zero(), 10)
+        }
+        Some(mp_id) => {
+            use schema::mileage_packages::dsl as mp_q;
+            let mp_result = mp_q::mileage_packages
+                .find(mp_id)
+                .select((mp_q::miles, mp_q::discounted_rate))
+                .get_result::<(i32, i32)>(&mut conn);
+            let Ok((mileage, discount_rate)) = mp_result else {
+                return Err(VeygoError::InternalServerError);
+            };
+            (
+                methods::pricing::mileage_package_cost(
+                    mileage, discount_rate, agreement.mileage_package_overwrite,
+                    agreement.duration_rate, agreement.msrp_factor, agreement.mileage_conversion,
+                ),
+                mileage + 10
+            )
+        }
+    };
+
+    // 3. charges
+    use schema::charges::dsl as c_q;
+
+    // eg. toll road, tesla supercharging
+    let taxed_charges = c_q::charges
+        .filter(c_q::agreement_id.eq(agreement.id))
+        .filter(c_q::is_taxed)
+        .select(diesel::dsl::sum(c_q::amount))
+        .get_result::<Option<Decimal>>(&mut conn);
+    let Ok(taxed_charges) = taxed_charges else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    // eg. parking citations and fines
+    let non_taxed_charges = c_q::charges
+        .filter(c_q::agreement_id.eq(agreement.id))
+        .filter(c_q::is_taxed.eq(false))
+        .select(diesel::dsl::sum(c_q::amount))
+        .get_result::<Option<Decimal>>(&mut conn);
+    let Ok(non_taxed_charges) = non_taxed_charges else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    // 4. low fuel & over mileage
+    use schema::vehicle_snapshots::dsl as v_s_q;
+    let check_out_snapshot = v_s_q::vehicle_snapshots
+        .find(check_out_snapshot_id)
+        .select((v_s_q::level, v_s_q::odometer))
+        .get_result::<(i32, i32)>(&mut conn);
+    let check_in_snapshot = v_s_q::vehicle_snapshots
+        .find(check_in_snapshot_id)
+        .select((v_s_q::level, v_s_q::odometer))
+        .get_result::<(i32, i32)>(&mut conn);
+    let (Ok((check_out_percent, check_out_odo)), Ok((check_in_level, check_in_odo))) = (check_out_snapshot, check_in_snapshot) else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    let check_in_percent = check_in_level + 10;
+    let missing_fuel_level = max(0, check_out_percent - check_in_percent) as i64;
+    let low_fuel_fee = Decimal::new(missing_fuel_level, 0) * proj_config::PRICE_PER_CENT_ON_GAS;
+
+    let total_driven = check_in_odo - check_out_odo;
+    let over_mileage_fee = if total_driven <= miles_allowed {
+        Decimal::ZERO
+    } else {
+        let over_mileage = total_driven - miles_allowed;
+        let mileage_rate: Decimal = if let Some(overwrite) = agreement.mileage_rate_overwrite {
+            overwrite
+        } else {
+            agreement.duration_rate * agreement.msrp_factor * agreement.mileage_conversion
+        };
+        Decimal::new(over_mileage as i64, 0) * mileage_rate
+    };
+
+    // 5. taxes
+    use schema::agreements_taxes::dsl as agreements_taxes_query;
+    use schema::taxes::dsl as t_q;
+    let taxes = agreements_taxes_query::agreements_taxes
+        .inner_join(t_q::taxes)
+        .filter(agreements_taxes_query::agreement_id.eq(&agreement.id))
+        .select(t_q::taxes::all_columns())
+        .get_results::<model::Tax>(&mut conn);
+    let Ok(taxes) = taxes else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    // 6. summarize
+    let pricing_input = methods::pricing::PricingInput {
+        reserved_duration: trip_duration,
+        actual_duration: trip_duration_including_late_return,
+        duration_rate: agreement.duration_rate,
+        msrp_factor: agreement.msrp_factor,
+        rate_offer: agreement.utilization_factor,
+        protection_rates: methods::pricing::ProtectionRates::from(agreement),
+        hours_using_reward: reward_hours,
+        promo_amount,
+        manual_discount: agreement.manual_discount,
+        mileage_package_cost,
+        low_fuel_fee,
+        over_mileage_fee,
+        taxed_charges: taxed_charges.unwrap_or(Decimal::ZERO),
+        non_taxed_charges: non_taxed_charges.unwrap_or(Decimal::ZERO),
+        taxes: &taxes,
+    };
+    Ok(methods::pricing::calculate(&pricing_input))
+}
+
+pub async fn balance_due(agreement: &model::Agreement) -> Result<Decimal, VeygoError> {
+    // Settled total minus what the renter has paid and not been refunded, negative when overpaid
+    let price = settled_price(agreement).await?;
+
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::payments::dsl as pmt_q;
+    let payments = pmt_q::payments
+        .filter(pmt_q::agreement_id.eq(agreement.id))
+        .filter(pmt_q::payment_type.eq(model::PaymentType::Succeeded))
+        .get_results::<model::Payment>(&mut conn);
+    let Ok(payments) = payments else {
+        return Err(VeygoError::InternalServerError);
+    };
+    let paid_amount: Decimal = payments.iter().map(|payment| payment.amount - payment.refund_amount).sum();
+
+    Ok(price.total - paid_amount)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn promo(user_id: Option<i32>, apt_id: Option<i32>, uni_id: Option<i32>) -> model::Promo {
+        model::Promo {
+            code: String::from("WELCOME"),
+            name: String::from("Welcome"),
+            amount: Decimal::new(10, 0),
+            is_enabled: true,
+            is_one_time: false,
+            exp: Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap(),
+            user_id,
+            apt_id,
+            uni_id,
+        }
+    }
+
+    #[test]
+    fn open_promo_applies_everywhere() {
+        assert!(promo_applies_to(&promo(None, None, None), 7, 3, 2));
+    }
+
+    #[test]
+    fn promo_limits_are_enforced() {
+        assert!(promo_applies_to(&promo(Some(7), None, None), 7, 3, 2));
+        assert!(!promo_applies_to(&promo(Some(8), None, None), 7, 3, 2));
+
+        assert!(promo_applies_to(&promo(None, Some(3), None), 7, 3, 2));
+        assert!(!promo_applies_to(&promo(None, Some(4), None), 7, 3, 2));
+        // a university is not an apartment for apartment promos
+        assert!(!promo_applies_to(&promo(None, Some(2), None), 7, 2, 1));
+
+        // university promos cover the university and every apartment under it
+        assert!(promo_applies_to(&promo(None, None, Some(2)), 7, 2, 1));
+        assert!(promo_applies_to(&promo(None, None, Some(2)), 7, 3, 2));
+        assert!(!promo_applies_to(&promo(None, None, Some(5)), 7, 3, 2));
+    }
+
+    #[test]
+    fn reward_week_runs_monday_to_monday() {
+        // 2026-10-18 is a Sunday
+        let (start, end) = reward_week(Utc.with_ymd_and_hms(2026, 10, 18, 23, 30, 0).unwrap());
+        assert_eq!(start, Utc.with_ymd_and_hms(2026, 10, 12, 0, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap());
+
+        let (start, _) = reward_week(Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap());
+        assert_eq!(start, Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn reward_hours_fit_the_trip_and_the_allowance() {
+        let trip = TimeDelta::minutes(150);
+        let allowance = Decimal::new(10, 0);
+        assert_eq!(reward_hours_check(Decimal::new(25, 1), trip, allowance, Decimal::zero()), RewardHoursCheck::Allowed);
+        assert_eq!(reward_hours_check(Decimal::new(3, 0), trip, allowance, Decimal::zero()), RewardHoursCheck::ExceedsTrip);
+        assert_eq!(reward_hours_check(Decimal::new(2, 0), trip, allowance, Decimal::new(9, 0)), RewardHoursCheck::ExceedsAllowance);
+        assert_eq!(reward_hours_check(Decimal::new(1, 0), trip, allowance, Decimal::new(9, 0)), RewardHoursCheck::Allowed);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{connection_pool, model, schema};
+use crate::helper_model::VeygoError;
+use chrono::Utc;
+use diesel::prelude::*;
+
+pub async fn record(renter_id: Option<i32>, action: model::AuditActionType, path: String) -> Result<(), VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    let new_audit = model::NewAudit {
+        renter_id,
+        action,
+        path,
+        time: Utc::now(),
+    };
+    let result = diesel::insert_into(schema::audits::table)
+        .values(&new_audit)
+        .execute(&mut conn);
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(VeygoError::InternalServerError),
+    }
+}
@@ -0,0 +1,751 @@
+use std::collections::HashMap;
+use crate::{connection_pool, methods, model, proj_config, schema};
+use crate::helper_model::VeygoError;
+use chrono::{DateTime, Duration, Timelike, Utc};
+use diesel::prelude::*;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// a reused rate offer must stay valid this many minutes for the renter to finish booking
+const RATE_OFFER_REUSE_MARGIN: i64 = 5;
+// how far either side of the requested start to look for a free slot
+pub const SUGGESTION_SEARCH_HOURS: i64 = 72;
+const SUGGESTION_LIMIT: usize = 5;
+
+pub const MIN_TRIP_DURATION: Duration = Duration::minutes(30);
+pub const MAX_TRIP_DURATION: Duration = Duration::weeks(4);
+
+// bookings start on a quarter hour, at least 15 minutes after the next one
+pub fn earliest_start_time(now: DateTime<Utc>) -> DateTime<Utc> {
+    let next_quarter = {
+        let minute = now.minute();
+        let next_minute = ((minute / 15) + 1) * 15;
+        let base = now
+            .with_second(0)
+            .and_then(|dt| dt.with_nanosecond(0))
+            .unwrap();
+
+        if next_minute == 60 {
+            (base + Duration::hours(1)).with_minute(0).unwrap()
+        } else {
+            base.with_minute(next_minute).unwrap()
+        }
+    };
+    next_quarter + Duration::minutes(15)
+}
+
+pub fn latest_start_time(now: DateTime<Utc>) -> DateTime<Utc> {
+    earliest_start_time(now) + Duration::weeks(6)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockedRange {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VehicleSchedule {
+    // one range per blocking agreement, sorted by start time
+    pub blocked: Vec<BlockedRange>,
+    // overlapping ranges merged, used for the overlap check
+    merged: Vec<BlockedRange>,
+}
+
+impl VehicleSchedule {
+    pub fn new(mut blocked: Vec<BlockedRange>) -> Self {
+        blocked.sort_by(|a, b| a.start_time.cmp(&b.start_time).then(a.end_time.cmp(&b.end_time)));
+
+        let mut merged: Vec<BlockedRange> = Vec::with_capacity(blocked.len());
+        for range in &blocked {
+            match merged.last_mut() {
+                Some(last) if range.start_time <= last.end_time => {
+                    if range.end_time > last.end_time {
+                        last.end_time = range.end_time;
+                    }
+                }
+                _ => merged.push(*range),
+            }
+        }
+
+        VehicleSchedule { blocked, merged }
+    }
+
+    pub fn is_available(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> bool {
+        // first merged range that ends after the requested start is the only one that can overlap
+        let idx = self.merged.partition_point(|range| range.end_time <= start_time);
+        match self.merged.get(idx) {
+            Some(range) => range.start_time >= end_time,
+            None => true,
+        }
+    }
+}
+
+// time an agreement keeps the vehicle, buffered for turnaround and clipped to the search window
+pub fn blocked_range(agreement: &model::Agreement, window_start: DateTime<Utc>, window_end: DateTime<Utc>) -> BlockedRange {
+    let pickup_time = agreement.actual_pickup_time.unwrap_or(agreement.rsvp_pickup_time) - Duration::minutes(proj_config::RSVP_BUFFER);
+    let drop_off_time = agreement.actual_drop_off_time.unwrap_or(agreement.rsvp_drop_off_time) + Duration::minutes(proj_config::RSVP_BUFFER);
+
+    BlockedRange {
+        start_time: if pickup_time >= window_start { pickup_time } else { window_start },
+        end_time: if drop_off_time <= window_end { drop_off_time } else { window_end },
+    }
+}
+
+// blackouts get the same turnaround buffer, the car has to be back before it goes to the shop
+pub fn blackout_range(blackout: &model::VehicleBlackout, window_start: DateTime<Utc>, window_end: DateTime<Utc>) -> BlockedRange {
+    let start_time = blackout.start_time - Duration::minutes(proj_config::RSVP_BUFFER);
+    let end_time = blackout.end_time + Duration::minutes(proj_config::RSVP_BUFFER);
+
+    BlockedRange {
+        start_time: if start_time >= window_start { start_time } else { window_start },
+        end_time: if end_time <= window_end { end_time } else { window_end },
+    }
+}
+
+pub fn group_by_vehicle(
+    agreements: &[model::Agreement],
+    blackouts: &[model::VehicleBlackout],
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+) -> HashMap<i32, VehicleSchedule> {
+    let mut ranges_by_vehicle: HashMap<i32, Vec<BlockedRange>> = HashMap::new();
+    for agreement in agreements {
+        ranges_by_vehicle
+            .entry(agreement.vehicle_id)
+            .or_default()
+            .push(blocked_range(agreement, window_start, window_end));
+    }
+    for blackout in blackouts {
+        ranges_by_vehicle
+            .entry(blackout.vehicle_id)
+            .or_default()
+            .push(blackout_range(blackout, window_start, window_end));
+    }
+    ranges_by_vehicle
+        .into_iter()
+        .map(|(vehicle_id, ranges)| (vehicle_id, VehicleSchedule::new(ranges)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VehicleFit {
+    pub vehicle_id: i32,
+    pub location_id: i32,
+    pub capacity: i32,
+    pub small_bags: i32,
+    pub large_bags: i32,
+}
+
+impl From<&model::Vehicle> for VehicleFit {
+    fn from(vehicle: &model::Vehicle) -> Self {
+        VehicleFit {
+            vehicle_id: vehicle.id,
+            location_id: vehicle.location_id,
+            capacity: vehicle.capacity,
+            small_bags: vehicle.small_bags,
+            large_bags: vehicle.large_bags,
+        }
+    }
+}
+
+impl VehicleFit {
+    // None when the candidate is smaller than what the renter asked for, lower is a closer match
+    pub fn similarity_to(&self, target: &VehicleFit) -> Option<i32> {
+        let total_bags = self.small_bags + self.large_bags;
+        let target_total_bags = target.small_bags + target.large_bags;
+        if self.capacity < target.capacity || self.large_bags < target.large_bags || total_bags < target_total_bags {
+            return None;
+        }
+        Some(2 * (self.capacity - target.capacity) + (total_bags - target_total_bags))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    // the same vehicle at the closest free start
+    ShiftedTime,
+    // a comparable vehicle in the apartment, free for the requested window
+    SimilarVehicle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    pub vehicle_id: i32,
+    pub location_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+}
+
+// quarter-hour starts closest to the requested one that fit the whole trip, nearest first
+pub fn shifted_starts(
+    schedule: &VehicleSchedule,
+    requested_start: DateTime<Utc>,
+    trip_length: Duration,
+    earliest_start: DateTime<Utc>,
+    latest_start: DateTime<Utc>,
+    limit: usize,
+) -> Vec<DateTime<Utc>> {
+    let base = requested_start - Duration::minutes((requested_start.minute() % 15) as i64)
+        - Duration::seconds(requested_start.second() as i64)
+        - Duration::nanoseconds(requested_start.nanosecond() as i64);
+    let steps = SUGGESTION_SEARCH_HOURS * 4;
+
+    let mut starts = Vec::new();
+    for step in 1..=steps {
+        // earlier start wins a tie, renters rarely mind leaving a little sooner
+        for candidate in [base - Duration::minutes(15 * step), base + Duration::minutes(15 * step)] {
+            if candidate < earliest_start || candidate > latest_start {
+                continue;
+            }
+            if schedule.is_available(candidate, candidate + trip_length) {
+                starts.push(candidate);
+                if starts.len() == limit {
+                    return starts;
+                }
+            }
+        }
+    }
+    starts
+}
+
+// Ranked alternatives for a window that is not free. With a vehicle picked, comparable vehicles
+// at the requested time come first and then that vehicle at other times; without one, every
+// vehicle's nearest free start, closest first.
+pub fn suggest(
+    requested_vehicle_id: Option<i32>,
+    vehicles: &[VehicleFit],
+    schedules: &HashMap<i32, VehicleSchedule>,
+    requested_start: DateTime<Utc>,
+    requested_end: DateTime<Utc>,
+    earliest_start: DateTime<Utc>,
+    latest_start: DateTime<Utc>,
+) -> Vec<Suggestion> {
+    let trip_length = requested_end - requested_start;
+    let empty_schedule = VehicleSchedule::default();
+    let schedule_of = |vehicle_id: i32| schedules.get(&vehicle_id).unwrap_or(&empty_schedule);
+
+    let target = requested_vehicle_id.and_then(|id| vehicles.iter().find(|v| v.vehicle_id == id));
+    if let Some(target) = target {
+        let mut similar = vehicles.iter()
+            .filter(|v| v.vehicle_id != target.vehicle_id)
+            .filter(|v| schedule_of(v.vehicle_id).is_available(requested_start, requested_end))
+            .filter_map(|v| v.similarity_to(target).map(|score| (score, v)))
+            .collect::<Vec<_>>();
+        similar.sort_by_key(|(score, v)| (*score, v.location_id != target.location_id, v.vehicle_id));
+
+        let mut suggestions = similar.into_iter()
+            .take(SUGGESTION_LIMIT)
+            .map(|(_, v)| Suggestion {
+                kind: SuggestionKind::SimilarVehicle,
+                vehicle_id: v.vehicle_id,
+                location_id: v.location_id,
+                start_time: requested_start,
+                end_time: requested_end,
+            })
+            .collect::<Vec<_>>();
+        let starts = shifted_starts(
+            schedule_of(target.vehicle_id), requested_start, trip_length, earliest_start, latest_start, SUGGESTION_LIMIT
+        );
+        suggestions.extend(starts.into_iter().map(|start| Suggestion {
+            kind: SuggestionKind::ShiftedTime,
+            vehicle_id: target.vehicle_id,
+            location_id: target.location_id,
+            start_time: start,
+            end_time: start + trip_length,
+        }));
+        return suggestions;
+    }
+
+    let mut nearest = vehicles.iter()
+        .filter_map(|v| {
+            shifted_starts(schedule_of(v.vehicle_id), requested_start, trip_length, earliest_start, latest_start, 1)
+                .first()
+                .map(|start| Suggestion {
+                    kind: SuggestionKind::ShiftedTime,
+                    vehicle_id: v.vehicle_id,
+                    location_id: v.location_id,
+                    start_time: *start,
+                    end_time: *start + trip_length,
+                })
+        })
+        .collect::<Vec<_>>();
+    nearest.sort_by_key(|s| ((s.start_time - requested_start).num_minutes().abs(), s.start_time, s.vehicle_id));
+    nearest.truncate(SUGGESTION_LIMIT);
+    nearest
+}
+
+// every rental agreement and blackout on these vehicles that touches the buffered window
+pub async fn load_vehicle_schedules(
+    vehicle_ids: &[i32],
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+) -> Result<HashMap<i32, VehicleSchedule>, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    let window_start_buffered = window_start - Duration::minutes(proj_config::RSVP_BUFFER);
+    let window_end_buffered = window_end + Duration::minutes(proj_config::RSVP_BUFFER);
+
+    use schema::agreements::dsl as ag_q;
+    let agreements_blocking = ag_q::agreements
+        .filter(ag_q::vehicle_id.eq_any(vehicle_ids))
+        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+        .filter(
+            methods::diesel_fn::coalesce(ag_q::actual_pickup_time, ag_q::rsvp_pickup_time)
+                .lt(window_end_buffered)
+                .and(
+                    methods::diesel_fn::coalesce(
+                        ag_q::actual_drop_off_time,
+                        methods::diesel_fn::greatest(ag_q::rsvp_drop_off_time, diesel::dsl::now)
+                    )
+                        .gt(window_start_buffered)
+                )
+        )
+        .get_results::<model::Agreement>(&mut conn);
+
+    let Ok(agreements_blocking) = agreements_blocking else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    use schema::vehicle_blackouts::dsl as vb_q;
+    let blackouts_blocking = vb_q::vehicle_blackouts
+        .filter(vb_q::vehicle_id.eq_any(vehicle_ids))
+        .filter(vb_q::start_time.lt(window_end_buffered))
+        .filter(vb_q::end_time.gt(window_start_buffered))
+        .get_results::<model::VehicleBlackout>(&mut conn);
+
+    let Ok(blackouts_blocking) = blackouts_blocking else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    Ok(group_by_vehicle(&agreements_blocking, &blackouts_blocking, window_start, window_end))
+}
+
+// rental agreements on a vehicle that would run into a blackout, including the turnaround buffer
+pub async fn agreements_overlapping(
+    vehicle_id: i32,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Result<Vec<model::Agreement>, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    let start_time_buffered = start_time - Duration::minutes(proj_config::RSVP_BUFFER);
+    let end_time_buffered = end_time + Duration::minutes(proj_config::RSVP_BUFFER);
+
+    use schema::agreements::dsl as ag_q;
+    let overlapping = ag_q::agreements
+        .filter(ag_q::vehicle_id.eq(vehicle_id))
+        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+        .filter(
+            methods::diesel_fn::coalesce(ag_q::actual_pickup_time, ag_q::rsvp_pickup_time)
+                .lt(end_time_buffered)
+                .and(
+                    methods::diesel_fn::coalesce(
+                        ag_q::actual_drop_off_time,
+                        methods::diesel_fn::greatest(ag_q::rsvp_drop_off_time, diesel::dsl::now)
+                    )
+                        .gt(start_time_buffered)
+                )
+        )
+        .order(ag_q::rsvp_pickup_time.asc())
+        .get_results::<model::Agreement>(&mut conn);
+
+    overlapping.map_err(|_| VeygoError::InternalServerError)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AffectedAgreement {
+    pub agreement: model::Agreement,
+    // comparable vehicles in the apartment that are free for the whole trip, closest match first
+    pub replacement_vehicle_ids: Vec<i32>,
+}
+
+// upcoming reservations on a vehicle that is being retired or moved, with replacement options
+pub async fn affected_agreements(vehicle: &model::Vehicle, apartment_id: i32) -> Result<Vec<AffectedAgreement>, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::agreements::dsl as ag_q;
+    let upcoming = ag_q::agreements
+        .filter(ag_q::vehicle_id.eq(vehicle.id))
+        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+        .filter(ag_q::actual_pickup_time.is_null())
+        .filter(ag_q::rsvp_drop_off_time.gt(Utc::now()))
+        .order(ag_q::rsvp_pickup_time.asc())
+        .get_results::<model::Agreement>(&mut conn);
+    let Ok(upcoming) = upcoming else {
+        return Err(VeygoError::InternalServerError);
+    };
+    let (Some(window_start), Some(window_end)) = (
+        upcoming.iter().map(|ag| ag.rsvp_pickup_time).min(),
+        upcoming.iter().map(|ag| ag.rsvp_drop_off_time).max(),
+    ) else {
+        return Ok(Vec::new());
+    };
+
+    use schema::vehicles::dsl as v_q;
+    use schema::locations::dsl as l_q;
+    let candidates = v_q::vehicles
+        .inner_join(l_q::locations)
+        .filter(l_q::apartment_id.eq(apartment_id))
+        .filter(v_q::available)
+        .filter(v_q::maintenance_hold.eq(false))
+        .filter(v_q::id.ne(vehicle.id))
+        .select(v_q::vehicles::all_columns())
+        .get_results::<model::Vehicle>(&mut conn);
+    let Ok(candidates) = candidates else {
+        return Err(VeygoError::InternalServerError);
+    };
+    let candidate_ids = candidates.iter().map(|v| v.id).collect::<Vec<i32>>();
+    let schedules = load_vehicle_schedules(&candidate_ids, window_start, window_end).await?;
+    let empty_schedule = VehicleSchedule::default();
+
+    let target = VehicleFit::from(vehicle);
+    Ok(upcoming
+        .into_iter()
+        .map(|agreement| {
+            let mut replacements = candidates.iter()
+                .map(VehicleFit::from)
+                .filter(|v| schedules.get(&v.vehicle_id).unwrap_or(&empty_schedule)
+                    .is_available(agreement.rsvp_pickup_time, agreement.rsvp_drop_off_time))
+                .filter_map(|v| v.similarity_to(&target).map(|score| (score, v)))
+                .collect::<Vec<_>>();
+            replacements.sort_by_key(|(score, v)| (*score, v.location_id != agreement.location_id, v.vehicle_id));
+            AffectedAgreement {
+                replacement_vehicle_ids: replacements.into_iter().take(SUGGESTION_LIMIT).map(|(_, v)| v.vehicle_id).collect(),
+                agreement,
+            }
+        })
+        .collect())
+}
+
+// reuses the renter's live offer at this apartment instead of issuing a new one per search
+pub async fn current_rate_offer(renter_id: i32, apartment_id: i32) -> Result<model::RateOffer, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::rate_offers::dsl as ro_q;
+    let existing_offer = ro_q::rate_offers
+        .filter(ro_q::renter_id.eq(renter_id))
+        .filter(ro_q::apartment_id.eq(apartment_id))
+        .filter(ro_q::exp.gt(Utc::now() + Duration::minutes(RATE_OFFER_REUSE_MARGIN)))
+        .order(ro_q::exp.desc())
+        .first::<model::RateOffer>(&mut conn)
+        .optional();
+    match existing_offer {
+        Ok(Some(offer)) => return Ok(offer),
+        Ok(None) => {}
+        Err(_) => return Err(VeygoError::InternalServerError),
+    }
+
+    let new_rate_offer = model::NewRateOffer {
+        renter_id,
+        apartment_id,
+        multiplier: Decimal::new(80, 2),
+    };
+    let inserted = diesel::insert_into(ro_q::rate_offers)
+        .values(&new_rate_offer)
+        .get_result::<model::RateOffer>(&mut conn);
+
+    match inserted {
+        Ok(offer) => Ok(offer),
+        Err(_) => Err(VeygoError::InternalServerError),
+    }
+}
+
+// -------------------------------------------------------------------------
+// Tests
+// -------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::time::Instant;
+
+    fn at(hour: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap() + Duration::hours(hour)
+    }
+
+    fn agreement(id: i32, vehicle_id: i32, pickup: DateTime<Utc>, drop_off: DateTime<Utc>) -> model::Agreement {
+        model::Agreement {
+            id,
+            confirmation: format!("TEST{:04}", id),
+            status: model::AgreementStatus::Rental,
+            user_name: String::from("Test Renter"),
+            user_date_of_birth: chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+            user_email: String::from("renter@example.com"),
+            user_phone: String::from("5555555555"),
+            user_billing_address: model::UsAddress {
+                street_address: String::from("1 Main St"),
+                extended_address: None,
+                city: String::from("Town"),
+                state: String::from("IN"),
+                zipcode: String::from("47906"),
+            },
+            rsvp_pickup_time: pickup,
+            rsvp_drop_off_time: drop_off,
+            liability_protection_rate: None,
+            pcdw_protection_rate: None,
+            pcdw_ext_protection_rate: None,
+            rsa_protection_rate: None,
+            pai_protection_rate: None,
+            actual_pickup_time: None,
+            actual_drop_off_time: None,
+            msrp_factor: Decimal::ONE,
+            duration_rate: Decimal::TEN,
+            vehicle_id,
+            renter_id: 1,
+            payment_method_id: 1,
+            vehicle_snapshot_before: None,
+            vehicle_snapshot_after: None,
+            promo_id: None,
+            manual_discount: None,
+            location_id: 1,
+            mileage_package_id: None,
+            mileage_conversion: Decimal::ONE,
+            mileage_rate_overwrite: None,
+            mileage_package_overwrite: None,
+            utilization_factor: Decimal::ONE,
+            date_of_creation: pickup,
+            minimum_earning_rate: Decimal::ONE,
+            deposit_pmt_id: None,
+            void_reason: None,
+        }
+    }
+
+    // the old handler: a pass over the agreements per vehicle, overlap check over every range
+    fn per_vehicle_scan(
+        vehicle_ids: &[i32],
+        agreements: &[model::Agreement],
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> HashMap<i32, Vec<BlockedRange>> {
+        vehicle_ids.iter()
+            .map(|vehicle_id| {
+                let ranges = agreements.iter()
+                    .filter(|ag| ag.vehicle_id == *vehicle_id)
+                    .map(|ag| blocked_range(ag, window_start, window_end))
+                    .collect::<Vec<_>>();
+                (*vehicle_id, ranges)
+            })
+            .collect()
+    }
+
+    fn scan_is_available(ranges: &[BlockedRange], start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> bool {
+        ranges.iter().all(|blocked| !(start_time < blocked.end_time && end_time > blocked.start_time))
+    }
+
+    // small deterministic generator so the comparison covers many layouts without extra deps
+    fn pseudo_random_agreements(count: i32, vehicles: i32) -> Vec<model::Agreement> {
+        let mut seed: u64 = 0x2545F4914F6CDD1D;
+        let mut next = move |modulo: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % modulo
+        };
+        (0..count)
+            .map(|id| {
+                let vehicle_id = next(vehicles as u64) as i32 + 1;
+                let pickup = at(0) + Duration::minutes(next(60 * 24 * 14) as i64);
+                let drop_off = pickup + Duration::minutes(30 + next(60 * 24 * 3) as i64);
+                agreement(id + 1, vehicle_id, pickup, drop_off)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blocked_range_is_buffered_and_clipped() {
+        let buffer = Duration::minutes(proj_config::RSVP_BUFFER);
+        let inside = agreement(1, 1, at(10), at(12));
+        assert_eq!(blocked_range(&inside, at(0), at(24)), BlockedRange { start_time: at(10) - buffer, end_time: at(12) + buffer });
+
+        let spanning = agreement(2, 1, at(-5), at(30));
+        assert_eq!(blocked_range(&spanning, at(0), at(24)), BlockedRange { start_time: at(0), end_time: at(24) });
+    }
+
+    #[test]
+    fn actual_times_override_reserved_times() {
+        let mut late = agreement(1, 1, at(10), at(12));
+        late.actual_pickup_time = Some(at(9));
+        late.actual_drop_off_time = Some(at(14));
+        let range = blocked_range(&late, at(0), at(24));
+        assert_eq!(range.start_time, at(9) - Duration::minutes(proj_config::RSVP_BUFFER));
+        assert_eq!(range.end_time, at(14) + Duration::minutes(proj_config::RSVP_BUFFER));
+    }
+
+    #[test]
+    fn blackouts_block_alongside_agreements() {
+        let buffer = Duration::minutes(proj_config::RSVP_BUFFER);
+        let blackout = model::VehicleBlackout {
+            id: 1,
+            vehicle_id: 2,
+            start_time: at(20),
+            end_time: at(22),
+            reason: String::from("Tire shop"),
+            created_by: 1,
+        };
+        let agreements = vec![agreement(1, 2, at(10), at(12))];
+        let grouped = group_by_vehicle(&agreements, std::slice::from_ref(&blackout), at(0), at(48));
+
+        let schedule = &grouped[&2];
+        assert_eq!(schedule.blocked.len(), 2);
+        assert_eq!(schedule.blocked[1], BlockedRange { start_time: at(20) - buffer, end_time: at(22) + buffer });
+        assert!(!schedule.is_available(at(21), at(23)));
+        assert!(schedule.is_available(at(14), at(18)));
+    }
+
+    #[test]
+    fn touching_ranges_do_not_overlap() {
+        let schedule = VehicleSchedule::new(vec![
+            BlockedRange { start_time: at(2), end_time: at(4) },
+            BlockedRange { start_time: at(8), end_time: at(10) },
+        ]);
+        assert!(schedule.is_available(at(4), at(8)));
+        assert!(schedule.is_available(at(0), at(2)));
+        assert!(!schedule.is_available(at(3), at(5)));
+        assert!(!schedule.is_available(at(0), at(12)));
+        assert!(schedule.is_available(at(10), at(11)));
+    }
+
+    #[test]
+    fn merged_ranges_cover_nested_bookings() {
+        let schedule = VehicleSchedule::new(vec![
+            BlockedRange { start_time: at(5), end_time: at(6) },
+            BlockedRange { start_time: at(0), end_time: at(20) },
+            BlockedRange { start_time: at(22), end_time: at(23) },
+        ]);
+        assert!(!schedule.is_available(at(7), at(8)));
+        assert!(schedule.is_available(at(20), at(22)));
+        assert_eq!(schedule.blocked.len(), 3);
+        assert_eq!(schedule.blocked[0].start_time, at(0));
+    }
+
+    #[test]
+    fn grouped_ranges_match_per_vehicle_scan() {
+        let agreements = pseudo_random_agreements(400, 25);
+        let vehicle_ids = (1..=25).collect::<Vec<i32>>();
+        let (window_start, window_end) = (at(72), at(72 + 96));
+
+        let grouped = group_by_vehicle(&agreements, &[], window_start, window_end);
+        let scanned = per_vehicle_scan(&vehicle_ids, &agreements, window_start, window_end);
+
+        for vehicle_id in &vehicle_ids {
+            let mut expected = scanned[vehicle_id].clone();
+            expected.sort_by(|a, b| a.start_time.cmp(&b.start_time).then(a.end_time.cmp(&b.end_time)));
+            let schedule = grouped.get(vehicle_id).cloned().unwrap_or_default();
+            assert_eq!(schedule.blocked, expected);
+
+            for start_hour in (60..180).step_by(7) {
+                for length in [1, 3, 24, 50] {
+                    let (start, end) = (at(start_hour), at(start_hour + length));
+                    assert_eq!(schedule.is_available(start, end), scan_is_available(&expected, start, end));
+                }
+            }
+        }
+    }
+
+    // cargo test --release -- --ignored --nocapture compare_with_per_vehicle_scan
+    #[test]
+    #[ignore]
+    fn compare_with_per_vehicle_scan() {
+        let agreements = pseudo_random_agreements(50_000, 500);
+        let vehicle_ids = (1..=500).collect::<Vec<i32>>();
+        let (window_start, window_end) = (at(0), at(24 * 14));
+
+        let timer = Instant::now();
+        let scanned = per_vehicle_scan(&vehicle_ids, &agreements, window_start, window_end);
+        let scan_available = vehicle_ids.iter()
+            .filter(|id| scan_is_available(&scanned[*id], at(100), at(110)))
+            .count();
+        let scan_elapsed = timer.elapsed();
+
+        let timer = Instant::now();
+        let grouped = group_by_vehicle(&agreements, &[], window_start, window_end);
+        let grouped_available = vehicle_ids.iter()
+            .filter(|id| grouped.get(*id).is_none_or(|schedule| schedule.is_available(at(100), at(110))))
+            .count();
+        let grouped_elapsed = timer.elapsed();
+
+        assert_eq!(scan_available, grouped_available);
+        println!("per vehicle scan: {:?}, grouped: {:?}", scan_elapsed, grouped_elapsed);
+    }
+
+    fn fit(vehicle_id: i32, location_id: i32, capacity: i32, small_bags: i32, large_bags: i32) -> VehicleFit {
+        VehicleFit { vehicle_id, location_id, capacity, small_bags, large_bags }
+    }
+
+    #[test]
+    fn earliest_start_is_a_quarter_hour_with_lead_time() {
+        assert_eq!(earliest_start_time(at(0) + Duration::minutes(7)), at(0) + Duration::minutes(30));
+        assert_eq!(earliest_start_time(at(0) + Duration::minutes(50)), at(1) + Duration::minutes(15));
+        assert_eq!(earliest_start_time(at(0)), at(0) + Duration::minutes(30));
+    }
+
+    #[test]
+    fn shifted_starts_keep_trip_length_and_buffer() {
+        let buffer = Duration::minutes(proj_config::RSVP_BUFFER);
+        // booked 10:00 to 12:00, blocked 9:45 to 12:15 with the buffer
+        let booked = agreement(1, 1, at(10), at(12));
+        let schedule = VehicleSchedule::new(vec![blocked_range(&booked, at(0), at(48))]);
+
+        // nearest first, so a late request lands after the booking and an early one before it
+        let starts = shifted_starts(&schedule, at(11), Duration::hours(1), at(0), at(48), 2);
+        assert_eq!(starts, vec![at(12) + buffer, at(12) + buffer + Duration::minutes(15)]);
+        let early_starts = shifted_starts(&schedule, at(10), Duration::hours(1), at(0), at(48), 1);
+        assert_eq!(early_starts, vec![at(9) - buffer]);
+        for start in starts.into_iter().chain(early_starts) {
+            assert!(schedule.is_available(start, start + Duration::hours(1)));
+        }
+    }
+
+    #[test]
+    fn shifted_starts_respect_lead_time() {
+        let schedule = VehicleSchedule::new(vec![BlockedRange { start_time: at(10), end_time: at(12) }]);
+        let starts = shifted_starts(&schedule, at(11), Duration::hours(1), at(11), at(48), 3);
+        assert!(starts.iter().all(|start| *start >= at(11)));
+        assert_eq!(starts[0], at(12));
+    }
+
+    #[test]
+    fn similar_vehicles_must_fit_the_party() {
+        let target = fit(1, 1, 5, 2, 2);
+        assert_eq!(fit(2, 2, 5, 2, 2).similarity_to(&target), Some(0));
+        assert_eq!(fit(3, 2, 7, 2, 3).similarity_to(&target), Some(5));
+        assert_eq!(fit(4, 2, 4, 4, 4).similarity_to(&target), None);
+        assert_eq!(fit(5, 2, 5, 4, 1).similarity_to(&target), None);
+    }
+
+    #[test]
+    fn suggestions_rank_similar_vehicles_before_other_times() {
+        let vehicles = [fit(1, 1, 5, 2, 2), fit(2, 2, 7, 2, 2), fit(3, 2, 5, 2, 2), fit(4, 1, 2, 1, 0)];
+        let mut schedules = HashMap::new();
+        schedules.insert(1, VehicleSchedule::new(vec![BlockedRange { start_time: at(10), end_time: at(12) }]));
+
+        let suggestions = suggest(Some(1), &vehicles, &schedules, at(10), at(11), at(0), at(48));
+        let similar = suggestions.iter()
+            .filter(|s| s.kind == SuggestionKind::SimilarVehicle)
+            .map(|s| s.vehicle_id)
+            .collect::<Vec<_>>();
+        assert_eq!(similar, vec![3, 2]);
+        assert_eq!(suggestions[0].vehicle_id, 3);
+        let shifted = suggestions.iter().filter(|s| s.kind == SuggestionKind::ShiftedTime).collect::<Vec<_>>();
+        assert!(!shifted.is_empty());
+        assert!(shifted.iter().all(|s| s.vehicle_id == 1 && s.end_time - s.start_time == Duration::hours(1)));
+    }
+
+    #[test]
+    fn suggestions_without_a_vehicle_pick_the_nearest_free_start() {
+        let vehicles = [fit(1, 1, 5, 2, 2), fit(2, 1, 5, 2, 2)];
+        let mut schedules = HashMap::new();
+        schedules.insert(1, VehicleSchedule::new(vec![BlockedRange { start_time: at(8), end_time: at(16) }]));
+        schedules.insert(2, VehicleSchedule::new(vec![BlockedRange { start_time: at(9), end_time: at(11) }]));
+
+        let suggestions = suggest(None, &vehicles, &schedules, at(10), at(11), at(0), at(48));
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].vehicle_id, 2);
+        assert_eq!(suggestions[0].start_time, at(11));
+    }
+}
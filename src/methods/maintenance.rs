@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use crate::{connection_pool, model, schema};
+use crate::helper_model::VeygoError;
+use diesel::prelude::*;
+
+// odometer reading at which the next service of this type is due,
+// a vehicle with no record of this service is treated as last serviced at 0
+pub fn next_due_odometer(last_service: Option<&model::VehicleService>, service: &model::Service) -> i32 {
+    let last_odometer = last_service.map(|record| record.odometer).unwrap_or(0);
+    last_odometer.saturating_add(service.interval)
+}
+
+pub fn is_service_overdue(odometer: i32, last_service: Option<&model::VehicleService>, service: &model::Service) -> bool {
+    odometer >= next_due_odometer(last_service, service)
+}
+
+// every service type is checked against every vehicle, `vehicles` are (id, odometer) pairs
+pub fn overdue_services(
+    vehicles: &[(i32, i32)],
+    services: &[model::Service],
+    records: &[model::VehicleService],
+) -> HashMap<i32, Vec<model::Service>> {
+    let last_services = records
+        .iter()
+        .map(|record| ((record.vehicle_id, record.service_id), record))
+        .collect::<HashMap<_, _>>();
+
+    let mut overdue: HashMap<i32, Vec<model::Service>> = HashMap::new();
+    for &(vehicle_id, odometer) in vehicles {
+        for service in services {
+            let last_service = last_services.get(&(vehicle_id, service.id)).copied();
+            if is_service_overdue(odometer, last_service, service) {
+                overdue.entry(vehicle_id).or_default().push(service.clone());
+            }
+        }
+    }
+    overdue
+}
+
+pub async fn overdue_services_by_vehicle(vehicle_ids: Option<&[i32]>) -> Result<HashMap<i32, Vec<model::Service>>, VeygoError> {
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::vehicles_services::dsl as vs_q;
+    use schema::services::dsl as s_q;
+    use schema::vehicles::dsl as v_q;
+    let mut vehicle_query = v_q::vehicles
+        .select((v_q::id, v_q::odometer))
+        .into_boxed();
+    let mut record_query = vs_q::vehicles_services.into_boxed();
+    if let Some(vehicle_ids) = vehicle_ids {
+        vehicle_query = vehicle_query.filter(v_q::id.eq_any(vehicle_ids));
+        record_query = record_query.filter(vs_q::vehicle_id.eq_any(vehicle_ids));
+    }
+
+    let vehicles = vehicle_query.get_results::<(i32, i32)>(&mut conn);
+    let services = s_q::services.get_results::<model::Service>(&mut conn);
+    let records = record_query.get_results::<model::VehicleService>(&mut conn);
+    let (Ok(vehicles), Ok(services), Ok(records)) = (vehicles, services, records) else {
+        return Err(VeygoError::InternalServerError);
+    };
+    Ok(overdue_services(&vehicles, &services, &records))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oil_change() -> model::Service {
+        model::Service { id: 1, interval: 5000, note: String::from("Oil change") }
+    }
+
+    fn serviced_at(odometer: i32) -> model::VehicleService {
+        model::VehicleService { vehicle_id: 7, service_id: 1, odometer, document: String::new() }
+    }
+
+    #[test]
+    fn service_is_due_at_last_service_plus_interval() {
+        let last = serviced_at(12_000);
+        assert_eq!(next_due_odometer(Some(&last), &oil_change()), 17_000);
+        assert!(!is_service_overdue(16_999, Some(&last), &oil_change()));
+        assert!(is_service_overdue(17_000, Some(&last), &oil_change()));
+        assert!(is_service_overdue(20_000, Some(&last), &oil_change()));
+    }
+
+    #[test]
+    fn next_due_odometer_does_not_overflow() {
+        let last = serviced_at(i32::MAX - 10);
+        assert_eq!(next_due_odometer(Some(&last), &oil_change()), i32::MAX);
+    }
+
+    #[test]
+    fn never_serviced_counts_from_zero() {
+        assert_eq!(next_due_odometer(None, &oil_change()), 5000);
+        assert!(!is_service_overdue(4_999, None, &oil_change()));
+        assert!(is_service_overdue(5_000, None, &oil_change()));
+    }
+
+    #[test]
+    fn every_service_type_is_checked_for_every_vehicle() {
+        let tire_rotation = model::Service { id: 2, interval: 8000, note: String::from("Tire rotation") };
+        let services = [oil_change(), tire_rotation.clone()];
+        // vehicle 7 had an oil change at 12,000 but never a rotation, vehicle 8 has no records at all
+        let records = [serviced_at(12_000)];
+        let vehicles = [(7, 15_000), (8, 6_000), (9, 1_000)];
+
+        let overdue = overdue_services(&vehicles, &services, &records);
+        assert_eq!(overdue.get(&7).map(|s| s.iter().map(|s| s.id).collect::<Vec<_>>()), Some(vec![2]));
+        assert_eq!(overdue.get(&8).map(|s| s.iter().map(|s| s.id).collect::<Vec<_>>()), Some(vec![1]));
+        assert!(!overdue.contains_key(&9));
+    }
+}
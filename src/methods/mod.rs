@@ -5,3 +5,11 @@ pub mod tokens;
 pub mod user;
 pub mod diesel_fn;
 pub mod rental_rate;
+pub mod pricing;
+pub mod receipt;
+pub mod audit;
+pub mod availability;
+pub mod maintenance;
+pub mod telemetry;
+pub mod remote_vehicle;
+pub mod remote_command;
@@ -0,0 +1,537 @@
+use std::cmp::max;
+use crate::{methods, model};
+use chrono::TimeDelta;
+use rust_decimal::prelude::*;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ProtectionRates {
+    pub liability: Option<Decimal>,
+    pub pcdw: Option<Decimal>,
+    pub pcdw_ext: Option<Decimal>,
+    pub rsa: Option<Decimal>,
+    pub pai: Option<Decimal>,
+}
+
+impl ProtectionRates {
+    pub fn hourly_total(&self) -> Decimal {
+        self.liability.unwrap_or(Decimal::zero())
+            + self.pcdw.unwrap_or(Decimal::zero())
+            + self.pcdw_ext.unwrap_or(Decimal::zero())
+            + self.rsa.unwrap_or(Decimal::zero())
+            + self.pai.unwrap_or(Decimal::zero())
+    }
+
+    // hourly rate of each selected product, in the order shown to the renter
+    pub fn selected(&self) -> Vec<(&'static str, Decimal)> {
+        [
+            ("Liability Protection", self.liability),
+            ("Partial Collision Damage Waiver", self.pcdw),
+            ("Limited Collision Damage Waiver", self.pcdw_ext),
+            ("Roadside Assistance", self.rsa),
+            ("Personal Accident Insurance", self.pai),
+        ]
+        .into_iter()
+        .filter_map(|(name, rate)| rate.map(|rate| (name, rate)))
+        .collect()
+    }
+}
+
+impl From<&model::Agreement> for ProtectionRates {
+    fn from(agreement: &model::Agreement) -> Self {
+        ProtectionRates {
+            liability: agreement.liability_protection_rate,
+            pcdw: agreement.pcdw_protection_rate,
+            pcdw_ext: agreement.pcdw_ext_protection_rate,
+            rsa: agreement.rsa_protection_rate,
+            pai: agreement.pai_protection_rate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricingInput<'a> {
+    // reserved window, duration revenue is always billed on this
+    pub reserved_duration: TimeDelta,
+    // same as reserved before the trip, includes late return at check-in
+    pub actual_duration: TimeDelta,
+    pub duration_rate: Decimal,
+    pub msrp_factor: Decimal,
+    pub rate_offer: Decimal,
+    pub protection_rates: ProtectionRates,
+    pub hours_using_reward: Decimal,
+    pub promo_amount: Option<Decimal>,
+    pub manual_discount: Option<Decimal>,
+    pub mileage_package_cost: Decimal,
+    pub low_fuel_fee: Decimal,
+    pub over_mileage_fee: Decimal,
+    pub taxed_charges: Decimal,
+    pub non_taxed_charges: Decimal,
+    pub taxes: &'a [model::Tax],
+}
+
+impl<'a> PricingInput<'a> {
+    pub fn for_reservation(
+        trip_duration: TimeDelta,
+        duration_rate: Decimal,
+        msrp_factor: Decimal,
+        rate_offer: Decimal,
+        taxes: &'a [model::Tax],
+    ) -> Self {
+        PricingInput {
+            reserved_duration: trip_duration,
+            actual_duration: trip_duration,
+            duration_rate,
+            msrp_factor,
+            rate_offer,
+            protection_rates: ProtectionRates::default(),
+            hours_using_reward: Decimal::zero(),
+            promo_amount: None,
+            manual_discount: None,
+            mileage_package_cost: Decimal::zero(),
+            low_fuel_fee: Decimal::zero(),
+            over_mileage_fee: Decimal::zero(),
+            taxed_charges: Decimal::zero(),
+            non_taxed_charges: Decimal::zero(),
+            taxes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TaxRates {
+    pub percent_sales: Decimal,
+    pub percent_non_sales: Decimal,
+    pub daily: Decimal,
+    pub fixed: Decimal,
+    // daily and fixed taxes that are themselves subject to sales tax
+    pub subject_to_sales_tax: Decimal,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PriceBreakdown {
+    #[serde(with = "rust_decimal::serde::str")]
+    pub duration_revenue_before_reward: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub reward_discount: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub promo_discount: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub duration_revenue: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub late_return_fee: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub insurance_revenue: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub mileage_package_cost: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub low_fuel_fee: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub over_mileage_fee: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub taxed_charges: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub non_taxed_charges: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total_subject_to_rental_tax: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub percentage_tax: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub daily_tax: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub fixed_tax: Decimal,
+    pub tax_lines: Vec<TaxLine>,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub total: Decimal,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TaxLine {
+    pub name: String,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub amount: Decimal,
+}
+
+impl PriceBreakdown {
+    pub fn total_cents(&self) -> i64 {
+        self.total.mantissa() as i64
+    }
+}
+
+pub fn round_up_hours(duration: TimeDelta) -> i32 {
+    let hours = Decimal::new(duration.num_minutes(), 0) / Decimal::new(60, 0);
+    let mut hours_round_up = hours.round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
+    hours_round_up.rescale(0);
+    hours_round_up.mantissa() as i32
+}
+
+pub fn mileage_package_cost(
+    miles: i32,
+    discounted_rate: i32,
+    mileage_package_overwrite: Option<Decimal>,
+    duration_rate: Decimal,
+    msrp_factor: Decimal,
+    mileage_conversion: Decimal,
+) -> Decimal {
+    let base_rate_for_mp = if let Some(overwrite) = mileage_package_overwrite {
+        overwrite
+    } else {
+        duration_rate * msrp_factor * mileage_conversion
+    };
+
+    base_rate_for_mp * Decimal::new(miles as i64, 0) * Decimal::new(discounted_rate as i64, 2)
+}
+
+pub fn tax_applies(tax_obj: &model::Tax, hours_round_up: i32) -> bool {
+    if let Some(threshold) = tax_obj.threshold && let Some(is_lower) = tax_obj.is_lower {
+        is_lower && hours_round_up < threshold || !is_lower && hours_round_up >= threshold
+    } else {
+        true
+    }
+}
+
+pub fn aggregate_tax_rates(taxes: &[model::Tax], hours_round_up: i32, billable_days_count: i32) -> TaxRates {
+    let mut rates = TaxRates::default();
+
+    for tax_obj in taxes {
+        if tax_applies(tax_obj, hours_round_up) {
+            match tax_obj.tax_type {
+                model::TaxType::Percent => {
+                    if tax_obj.is_sales_tax {
+                        rates.percent_sales += tax_obj.multiplier;
+                    } else {
+                        rates.percent_non_sales += tax_obj.multiplier;
+                    }
+                },
+                model::TaxType::Daily => {
+                    if tax_obj.is_sales_tax {
+                        rates.subject_to_sales_tax += tax_obj.multiplier * Decimal::new(billable_days_count as i64, 0);
+                    }
+                    rates.daily += tax_obj.multiplier;
+                }
+                model::TaxType::Fixed => {
+                    if tax_obj.is_sales_tax {
+                        rates.subject_to_sales_tax += tax_obj.multiplier;
+                    }
+                    rates.fixed += tax_obj.multiplier;
+                }
+            }
+        }
+    }
+
+    rates
+}
+
+pub fn calculate(input: &PricingInput) -> PriceBreakdown {
+    // 1. total rental revenue
+    let total_hours_reserved = Decimal::new(input.reserved_duration.num_minutes(), 0) / Decimal::new(60, 0);
+    let total_hours_driven = Decimal::new(input.actual_duration.num_minutes(), 0) / Decimal::new(60, 0);
+    let hours_round_up = round_up_hours(input.actual_duration);
+
+    let billable_days_count: i32 = methods::rental_rate::billable_days_count(input.actual_duration);
+    let billable_duration_hours: Decimal = methods::rental_rate::calculate_billable_duration_hours(input.reserved_duration);
+
+    let hourly_rate = input.duration_rate * input.msrp_factor * input.rate_offer;
+    let duration_revenue_before_reward = billable_duration_hours * hourly_rate;
+
+    let reward_discount = if input.hours_using_reward > Decimal::zero() && total_hours_reserved > Decimal::zero() {
+        let avg_hourly_rate = duration_revenue_before_reward / total_hours_reserved;
+        avg_hourly_rate * input.hours_using_reward
+    } else {
+        Decimal::zero()
+    };
+    let duration_revenue = duration_revenue_before_reward - reward_discount;
+
+    // manual discount supersedes the promo
+    let duration_revenue_after_promo = match (input.manual_discount, input.promo_amount) {
+        (Some(discount), _) => max(Decimal::zero(), duration_revenue - discount),
+        (None, Some(promo)) => max(Decimal::zero(), duration_revenue - promo),
+        (None, None) => duration_revenue,
+    };
+    let promo_discount = duration_revenue - duration_revenue_after_promo;
+
+    // late return is billed at twice the rate and is not discounted
+    let late_hours = max(total_hours_driven - total_hours_reserved, Decimal::zero())
+        .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
+    let late_return_fee = Decimal::new(2, 0) * late_hours * hourly_rate;
+
+    // 2. total insurance revenue, including late return
+    let insurance_revenue = Decimal::new(hours_round_up as i64, 0) * input.protection_rates.hourly_total();
+
+    // 3. taxes
+    let rates = aggregate_tax_rates(input.taxes, hours_round_up, billable_days_count);
+
+    let total_subject_to_rental_tax = duration_revenue_after_promo + late_return_fee
+        + insurance_revenue + input.mileage_package_cost
+        + input.low_fuel_fee + input.over_mileage_fee;
+    let total_revenue = total_subject_to_rental_tax + input.taxed_charges;
+    let total_subject_to_sales_tax = total_revenue + rates.subject_to_sales_tax;
+
+    let percentage_tax = total_subject_to_rental_tax * rates.percent_non_sales
+        + total_subject_to_sales_tax * rates.percent_sales;
+    let daily_tax = Decimal::new(billable_days_count as i64, 0) * rates.daily;
+    let fixed_tax = rates.fixed;
+
+    let days = Decimal::new(billable_days_count as i64, 0);
+    let tax_lines = input.taxes.iter()
+        .filter(|tax_obj| tax_applies(tax_obj, hours_round_up))
+        .map(|tax_obj| {
+            let amount = match tax_obj.tax_type {
+                model::TaxType::Percent => {
+                    if tax_obj.is_sales_tax {
+                        total_subject_to_sales_tax * tax_obj.multiplier
+                    } else {
+                        total_subject_to_rental_tax * tax_obj.multiplier
+                    }
+                }
+                model::TaxType::Daily => days * tax_obj.multiplier,
+                model::TaxType::Fixed => tax_obj.multiplier,
+            };
+            TaxLine { name: tax_obj.name.clone(), amount }
+        })
+        .collect::<Vec<TaxLine>>();
+
+    // 4. summarize
+    let total = total_revenue + percentage_tax + daily_tax + fixed_tax + input.non_taxed_charges;
+    let mut total_2dp = total.round_dp(2);
+    total_2dp.rescale(2);
+
+    PriceBreakdown {
+        duration_revenue_before_reward,
+        reward_discount,
+        promo_discount,
+        duration_revenue: duration_revenue_after_promo,
+        late_return_fee,
+        insurance_revenue,
+        mileage_package_cost: input.mileage_package_cost,
+        low_fuel_fee: input.low_fuel_fee,
+        over_mileage_fee: input.over_mileage_fee,
+        taxed_charges: input.taxed_charges,
+        non_taxed_charges: input.non_taxed_charges,
+        total_subject_to_rental_tax,
+        percentage_tax,
+        daily_tax,
+        fixed_tax,
+        tax_lines,
+        total: total_2dp,
+    }
+}
+
+// sales tax owed on charges billed after the trip was settled
+pub fn sales_tax_on_charges(taxes: &[model::Tax], hours_round_up: i32, taxed_charges: Decimal) -> Vec<TaxLine> {
+    taxes.iter()
+        .filter(|tax_obj| tax_obj.is_sales_tax && tax_obj.tax_type == model::TaxType::Percent)
+        .filter(|tax_obj| tax_applies(tax_obj, hours_round_up))
+        .map(|tax_obj| TaxLine { name: tax_obj.name.clone(), amount: taxed_charges * tax_obj.multiplier })
+        .collect()
+}
+
+// -------------------------------------------------------------------------
+// Tests
+// -------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn tax(tax_type: model::TaxType, multiplier: &str, is_sales_tax: bool, threshold: Option<i32>, is_lower: Option<bool>) -> model::Tax {
+        model::Tax {
+            id: 1,
+            name: String::from("Test Tax"),
+            multiplier: dec(multiplier),
+            is_sales_tax,
+            tax_type,
+            is_deposit_tax: false,
+            threshold,
+            is_lower,
+        }
+    }
+
+    // 10 hours at $10/h, no tier discount past hour 8 applies to hours 9 and 10
+    fn ten_hour_input(taxes: &[model::Tax]) -> PricingInput<'_> {
+        PricingInput::for_reservation(TimeDelta::hours(10), dec("10"), dec("1"), dec("1"), taxes)
+    }
+
+    #[test]
+    fn no_taxes() {
+        let breakdown = calculate(&ten_hour_input(&[]));
+        // 8 + 2 * 0.25 billable hours
+        assert_eq!(breakdown.duration_revenue, dec("85"));
+        assert_eq!(breakdown.total, dec("85.00"));
+        assert_eq!(breakdown.total_cents(), 8500);
+    }
+
+    #[test]
+    fn percent_non_sales_tax() {
+        let taxes = [tax(model::TaxType::Percent, "0.10", false, None, None)];
+        let breakdown = calculate(&ten_hour_input(&taxes));
+        assert_eq!(breakdown.percentage_tax, dec("8.5"));
+        assert_eq!(breakdown.total, dec("93.50"));
+    }
+
+    #[test]
+    fn percent_sales_tax() {
+        let taxes = [tax(model::TaxType::Percent, "0.10", true, None, None)];
+        let breakdown = calculate(&ten_hour_input(&taxes));
+        assert_eq!(breakdown.percentage_tax, dec("8.5"));
+        assert_eq!(breakdown.total, dec("93.50"));
+    }
+
+    #[test]
+    fn daily_non_sales_tax_is_not_subject_to_sales_tax() {
+        let taxes = [
+            tax(model::TaxType::Daily, "2", false, None, None),
+            tax(model::TaxType::Percent, "0.10", true, None, None),
+        ];
+        let breakdown = calculate(&ten_hour_input(&taxes));
+        assert_eq!(breakdown.daily_tax, dec("2"));
+        assert_eq!(breakdown.percentage_tax, dec("8.5"));
+        assert_eq!(breakdown.total, dec("95.50"));
+    }
+
+    #[test]
+    fn daily_sales_tax_is_subject_to_sales_tax() {
+        let taxes = [
+            tax(model::TaxType::Daily, "2", true, None, None),
+            tax(model::TaxType::Percent, "0.10", true, None, None),
+        ];
+        let input = PricingInput::for_reservation(TimeDelta::hours(30), dec("10"), dec("1"), dec("1"), &taxes);
+        let breakdown = calculate(&input);
+        // 30 hours spans 2 billable days
+        assert_eq!(breakdown.daily_tax, dec("4"));
+        let rates = aggregate_tax_rates(&taxes, 30, 2);
+        assert_eq!(rates.subject_to_sales_tax, dec("4"));
+        assert_eq!(breakdown.percentage_tax, (breakdown.total_subject_to_rental_tax + dec("4")) * dec("0.10"));
+    }
+
+    #[test]
+    fn fixed_non_sales_tax() {
+        let taxes = [
+            tax(model::TaxType::Fixed, "5", false, None, None),
+            tax(model::TaxType::Percent, "0.10", true, None, None),
+        ];
+        let breakdown = calculate(&ten_hour_input(&taxes));
+        assert_eq!(breakdown.fixed_tax, dec("5"));
+        assert_eq!(breakdown.percentage_tax, dec("8.5"));
+        assert_eq!(breakdown.total, dec("98.50"));
+    }
+
+    #[test]
+    fn fixed_sales_tax_is_subject_to_sales_tax() {
+        let taxes = [
+            tax(model::TaxType::Fixed, "5", true, None, None),
+            tax(model::TaxType::Percent, "0.10", true, None, None),
+        ];
+        let breakdown = calculate(&ten_hour_input(&taxes));
+        assert_eq!(breakdown.fixed_tax, dec("5"));
+        assert_eq!(breakdown.percentage_tax, dec("9.0"));
+        assert_eq!(breakdown.total, dec("99.00"));
+    }
+
+    #[test]
+    fn tax_lines_itemize_applied_taxes() {
+        let taxes = [
+            tax(model::TaxType::Percent, "0.10", false, None, None),
+            tax(model::TaxType::Daily, "2", false, None, None),
+            tax(model::TaxType::Fixed, "5", false, Some(4), Some(true)),
+        ];
+        let breakdown = calculate(&ten_hour_input(&taxes));
+        assert_eq!(breakdown.tax_lines.len(), 2);
+        assert_eq!(breakdown.tax_lines[0].amount, dec("8.5"));
+        assert_eq!(breakdown.tax_lines[1].amount, dec("2"));
+        let itemized: Decimal = breakdown.tax_lines.iter().map(|line| line.amount).sum();
+        assert_eq!(itemized, breakdown.percentage_tax + breakdown.daily_tax + breakdown.fixed_tax);
+    }
+
+    #[test]
+    fn threshold_is_lower_counts_below_threshold() {
+        let taxes = [tax(model::TaxType::Fixed, "5", false, Some(24), Some(true))];
+        assert_eq!(aggregate_tax_rates(&taxes, 23, 1).fixed, dec("5"));
+        assert_eq!(aggregate_tax_rates(&taxes, 24, 1).fixed, Decimal::zero());
+    }
+
+    #[test]
+    fn threshold_is_higher_counts_at_or_above_threshold() {
+        let taxes = [tax(model::TaxType::Fixed, "5", false, Some(24), Some(false))];
+        assert_eq!(aggregate_tax_rates(&taxes, 23, 1).fixed, Decimal::zero());
+        assert_eq!(aggregate_tax_rates(&taxes, 24, 1).fixed, dec("5"));
+    }
+
+    #[test]
+    fn threshold_without_direction_always_counts() {
+        let taxes = [
+            tax(model::TaxType::Fixed, "5", false, Some(24), None),
+            tax(model::TaxType::Fixed, "1", false, None, Some(true)),
+        ];
+        assert_eq!(aggregate_tax_rates(&taxes, 1, 1).fixed, dec("6"));
+        assert_eq!(aggregate_tax_rates(&taxes, 100, 5).fixed, dec("6"));
+    }
+
+    #[test]
+    fn threshold_uses_rounded_up_hours() {
+        let taxes = [tax(model::TaxType::Percent, "0.10", false, Some(11), Some(true))];
+        let input = PricingInput::for_reservation(TimeDelta::minutes(10 * 60 + 15), dec("10"), dec("1"), dec("1"), &taxes);
+        // 10h15m rounds up to 11 hours, which is not lower than 11
+        assert_eq!(calculate(&input).percentage_tax, Decimal::zero());
+    }
+
+    #[test]
+    fn promo_and_reward_hours() {
+        let mut input = ten_hour_input(&[]);
+        input.hours_using_reward = dec("2");
+        input.promo_amount = Some(dec("20"));
+        let breakdown = calculate(&input);
+        assert_eq!(breakdown.reward_discount, dec("17"));
+        assert_eq!(breakdown.promo_discount, dec("20"));
+        assert_eq!(breakdown.total, dec("48.00"));
+    }
+
+    #[test]
+    fn manual_discount_supersedes_promo() {
+        let mut input = ten_hour_input(&[]);
+        input.promo_amount = Some(dec("20"));
+        input.manual_discount = Some(dec("100"));
+        let breakdown = calculate(&input);
+        assert_eq!(breakdown.duration_revenue, Decimal::zero());
+        assert_eq!(breakdown.total, dec("0.00"));
+    }
+
+    #[test]
+    fn late_return_and_charges() {
+        let taxes = [tax(model::TaxType::Percent, "0.10", true, None, None)];
+        let mut input = ten_hour_input(&taxes);
+        input.actual_duration = TimeDelta::minutes(11 * 60 + 5);
+        input.protection_rates.liability = Some(dec("1"));
+        input.taxed_charges = dec("10");
+        input.non_taxed_charges = dec("50");
+        let breakdown = calculate(&input);
+        // 1h05m late rounds up to 2 hours at twice the rate
+        assert_eq!(breakdown.late_return_fee, dec("40"));
+        // protection covers the 12 hours driven
+        assert_eq!(breakdown.insurance_revenue, dec("12"));
+        assert_eq!(breakdown.percentage_tax, dec("14.7"));
+        assert_eq!(breakdown.total, dec("211.70"));
+    }
+
+    #[test]
+    fn mileage_package_overwrite() {
+        assert_eq!(mileage_package_cost(100, 80, Some(dec("0.20")), dec("10"), dec("1"), dec("0.05")), dec("16"));
+        assert_eq!(mileage_package_cost(100, 80, None, dec("10"), dec("1"), dec("0.05")), dec("40"));
+    }
+
+    #[test]
+    fn sales_tax_on_late_charges_skips_other_taxes() {
+        let taxes = [
+            tax(model::TaxType::Percent, "0.10", true, None, None),
+            tax(model::TaxType::Percent, "0.05", false, None, None),
+            tax(model::TaxType::Daily, "2", true, None, None),
+            tax(model::TaxType::Percent, "0.20", true, Some(24), Some(false)),
+        ];
+        let lines = sales_tax_on_charges(&taxes, 10, dec("12"));
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].amount, dec("1.2"));
+    }
+}
@@ -0,0 +1,402 @@
+use std::cmp::max;
+use crate::{connection_pool, helper_model, integration, methods, model, schema};
+use crate::helper_model::VeygoError;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use rust_decimal::prelude::*;
+
+pub fn money(amount: Decimal) -> String {
+    let mut amount_2dp = amount.round_dp(2);
+    amount_2dp.rescale(2);
+    if amount_2dp < Decimal::zero() {
+        format!("-${}", -amount_2dp)
+    } else {
+        format!("${}", amount_2dp)
+    }
+}
+
+pub fn line(label: impl Into<String>, amount: Decimal) -> helper_model::ReceiptLine {
+    helper_model::ReceiptLine { label: label.into(), amount: money(amount) }
+}
+
+fn local_time(time: DateTime<Utc>, timezone: &str) -> String {
+    match timezone.parse::<Tz>() {
+        Ok(tz) => time.with_timezone(&tz).format("%b %-d, %Y %-I:%M %p %Z").to_string(),
+        Err(_) => time.format("%b %-d, %Y %-I:%M %p UTC").to_string(),
+    }
+}
+
+pub async fn build_receipt(agreement: &model::Agreement) -> Result<helper_model::ReceiptTemplate, VeygoError> {
+    let price = methods::agreement::settled_price(agreement).await?;
+
+    let mut conn = connection_pool().await.get().unwrap();
+
+    use schema::vehicles::dsl as v_q;
+    use schema::locations::dsl as l_q;
+    use schema::apartments::dsl as apt_q;
+    let vehicle_name = v_q::vehicles
+        .find(agreement.vehicle_id)
+        .select(v_q::name)
+        .get_result::<String>(&mut conn);
+    let location = l_q::locations
+        .find(agreement.location_id)
+        .inner_join(apt_q::apartments)
+        .select((l_q::name, apt_q::timezone))
+        .get_result::<(String, String)>(&mut conn);
+    let (Ok(vehicle_name), Ok((location_name, timezone))) = (vehicle_name, location) else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    use schema::charges::dsl as c_q;
+    let charges = c_q::charges
+        .filter(c_q::agreement_id.eq(agreement.id))
+        .order(c_q::time.asc())
+        .get_results::<model::Charge>(&mut conn);
+    let Ok(charges) = charges else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    use schema::payments::dsl as pmt_q;
+    let payments = pmt_q::payments
+        .filter(pmt_q::agreement_id.eq(agreement.id))
+        .filter(pmt_q::payment_type.eq(model::PaymentType::Succeeded))
+        .order(pmt_q::time.asc())
+        .get_results::<model::Payment>(&mut conn);
+    let Ok(payments) = payments else {
+        return Err(VeygoError::InternalServerError);
+    };
+
+    let pickup_time = agreement.actual_pickup_time.unwrap_or(agreement.rsvp_pickup_time);
+    let drop_off_time = agreement.actual_drop_off_time.unwrap_or(agreement.rsvp_drop_off_time);
+
+    // Rental time, discounts, late fee, protections and mileage
+    let mut rental_lines = vec![line(
+        format!("Rental time ({} hours reserved)", methods::pricing::round_up_hours(agreement.rsvp_drop_off_time - agreement.rsvp_pickup_time)),
+        price.duration_revenue_before_reward,
+    )];
+    if !price.reward_discount.is_zero() {
+        rental_lines.push(line("Reward hours", -price.reward_discount));
+    }
+    if !price.promo_discount.is_zero() {
+        let label = if agreement.manual_discount.is_some() { "Discount" } else { "Promo" };
+        rental_lines.push(line(label, -price.promo_discount));
+    }
+    if !price.late_return_fee.is_zero() {
+        rental_lines.push(line("Late return fee", price.late_return_fee));
+    }
+    let protection_hours = methods::pricing::round_up_hours(
+        max(drop_off_time, agreement.rsvp_drop_off_time) - agreement.rsvp_pickup_time
+    );
+    for (name, rate) in methods::pricing::ProtectionRates::from(agreement).selected() {
+        rental_lines.push(line(
+            format!("{} ({} hours)", name, protection_hours),
+            rate * Decimal::new(protection_hours as i64, 0),
+        ));
+    }
+    if !price.mileage_package_cost.is_zero() {
+        rental_lines.push(line("Mileage package", price.mileage_package_cost));
+    }
+    if !price.over_mileage_fee.is_zero() {
+        rental_lines.push(line("Over mileage", price.over_mileage_fee));
+    }
+    if !price.low_fuel_fee.is_zero() {
+        rental_lines.push(line("Low fuel", price.low_fuel_fee));
+    }
+
+    let charge_lines = charges
+        .iter()
+        .map(|charge| {
+            let label = if charge.is_taxed {
+                charge.name.clone()
+            } else {
+                format!("{} (not taxed)", charge.name)
+            };
+            line(label, charge.amount)
+        })
+        .collect::<Vec<_>>();
+
+    let tax_lines = price.tax_lines
+        .iter()
+        .map(|tax_line| line(tax_line.name.clone(), tax_line.amount))
+        .collect::<Vec<_>>();
+
+    let mut payment_lines = Vec::new();
+    let mut amount_paid = Decimal::zero();
+    for payment in &payments {
+        let label = payment.note.clone().unwrap_or(String::from("Card payment"));
+        payment_lines.push(line(format!("{} on {}", label, local_time(payment.time, &timezone)), payment.amount));
+        amount_paid += payment.amount;
+        if payment.refund_amount > Decimal::zero() {
+            payment_lines.push(line("Refund", -payment.refund_amount));
+            amount_paid -= payment.refund_amount;
+        }
+    }
+
+    Ok(helper_model::ReceiptTemplate {
+        confirmation: agreement.confirmation.clone(),
+        renter_name: agreement.user_name.clone(),
+        vehicle_name,
+        location_name,
+        pickup_time: local_time(pickup_time, &timezone),
+        drop_off_time: local_time(drop_off_time, &timezone),
+        rental_lines,
+        charge_lines,
+        tax_lines,
+        total: money(price.total),
+        payment_lines,
+        amount_paid: money(amount_paid),
+    })
+}
+
+fn pdf_escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => String::from("\\\\"),
+            '(' => String::from("\\("),
+            ')' => String::from("\\)"),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => String::from("?"),
+        })
+        .collect()
+}
+
+// Plain single-font PDF, monospaced so the amounts line up without measuring glyphs
+pub fn render_pdf(receipt: &helper_model::ReceiptTemplate) -> Vec<u8> {
+    const LINES_PER_PAGE: usize = 54;
+    const WIDTH: usize = 78;
+
+    let row = |label: &str, amount: &str| {
+        let label_width = WIDTH - amount.len() - 1;
+        let label: String = label.chars().take(label_width).collect();
+        format!("{:<label_width$} {}", label, amount)
+    };
+    let section = |text_lines: &mut Vec<String>, title: &str, lines: &[helper_model::ReceiptLine]| {
+        if lines.is_empty() {
+            return;
+        }
+        text_lines.push(String::new());
+        text_lines.push(String::from(title));
+        text_lines.push("-".repeat(WIDTH));
+        for receipt_line in lines {
+            text_lines.push(row(&receipt_line.label, &receipt_line.amount));
+        }
+    };
+
+    let mut text_lines = vec![
+        String::from("VEYGO RECEIPT"),
+        String::new(),
+        format!("Confirmation: {}", receipt.confirmation),
+        format!("Renter:       {}", receipt.renter_name),
+        format!("Vehicle:      {}", receipt.vehicle_name),
+        format!("Location:     {}", receipt.location_name),
+        format!("Pick-up:      {}", receipt.pickup_time),
+        format!("Return:       {}", receipt.drop_off_time),
+    ];
+    section(&mut text_lines, "RENTAL", &receipt.rental_lines);
+    section(&mut text_lines, "CHARGES", &receipt.charge_lines);
+    section(&mut text_lines, "TAXES", &receipt.tax_lines);
+    text_lines.push("=".repeat(WIDTH));
+    text_lines.push(row("TOTAL", &receipt.total));
+    section(&mut text_lines, "PAYMENTS", &receipt.payment_lines);
+    text_lines.push("=".repeat(WIDTH));
+    text_lines.push(row("AMOUNT PAID", &receipt.amount_paid));
+
+    let pages = text_lines.chunks(LINES_PER_PAGE).collect::<Vec<_>>();
+
+    // objects: 1 catalog, 2 page tree, 3 font, then a page and its content stream per page
+    let mut objects: Vec<String> = Vec::new();
+    let kids = (0..pages.len())
+        .map(|i| format!("{} 0 R", 4 + i * 2))
+        .collect::<Vec<_>>()
+        .join(" ");
+    objects.push(String::from("<< /Type /Catalog /Pages 2 0 R >>"));
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, pages.len()));
+    objects.push(String::from("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>"));
+    for (i, page) in pages.iter().enumerate() {
+        let mut content = String::from("BT\n/F1 9 Tf\n12 TL\n40 752 Td\n");
+        for text_line in page.iter() {
+            content.push_str(&format!("({}) Tj T*\n", pdf_escape(text_line)));
+        }
+        content.push_str("ET");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + i * 2
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref_offset = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+    pdf.into_bytes()
+}
+
+pub async fn email_receipt(agreement: &model::Agreement) -> Result<(), VeygoError> {
+    let receipt = build_receipt(agreement).await?;
+    let Ok(html) = receipt.render() else {
+        return Err(VeygoError::InternalServerError);
+    };
+    let attachment = integration::mailgun_veygo::Attachment {
+        filename: format!("Veygo-Receipt-{}.pdf", agreement.confirmation),
+        content_type: Some(String::from("application/pdf")),
+        bytes: render_pdf(&receipt),
+    };
+
+    let email = integration::mailgun_veygo::make_email_obj(&agreement.user_email, &agreement.user_name);
+    let subject = format!("Your Veygo receipt for RSVP #{}", agreement.confirmation);
+    let result = integration::mailgun_veygo::send_email(
+        None,
+        vec![email],
+        &subject,
+        &html,
+        Some(vec![attachment]),
+    ).await;
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(VeygoError::InternalServerError),
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt_with(renter_name: &str, rental_lines: Vec<helper_model::ReceiptLine>) -> helper_model::ReceiptTemplate {
+        helper_model::ReceiptTemplate {
+            confirmation: String::from("ABC123"),
+            renter_name: String::from(renter_name),
+            vehicle_name: String::from("Model 3"),
+            location_name: String::from("Garage"),
+            pickup_time: String::from("Oct 18, 2026 10:00 AM"),
+            drop_off_time: String::from("Oct 18, 2026 2:00 PM"),
+            rental_lines,
+            charge_lines: vec![line("Cleaning (interior)", Decimal::new(2500, 2))],
+            tax_lines: vec![line("Sales tax", Decimal::new(175, 2))],
+            total: money(Decimal::new(6675, 2)),
+            payment_lines: vec![line("Visa 4242", Decimal::new(6675, 2))],
+            amount_paid: money(Decimal::new(6675, 2)),
+        }
+    }
+
+    fn rental_lines(count: usize) -> Vec<helper_model::ReceiptLine> {
+        (0..count).map(|i| line(format!("Rental hour {}", i + 1), Decimal::new(1000, 2))).collect()
+    }
+
+    fn find(haystack: &[u8], needle: &str, from: usize) -> Option<usize> {
+        haystack[from..].windows(needle.len()).position(|w| w == needle.as_bytes()).map(|i| i + from)
+    }
+
+    // (object number, byte offset) pairs from the xref table, and where the table starts
+    fn xref_entries(pdf: &[u8]) -> (Vec<(usize, usize)>, usize) {
+        let text = String::from_utf8(pdf.to_vec()).unwrap();
+        let startxref = text.rsplit("startxref\n").next().unwrap();
+        let xref_offset = startxref.lines().next().unwrap().parse::<usize>().unwrap();
+        let entries = text[xref_offset..]
+            .lines()
+            .skip(3)
+            .take_while(|l| l.ends_with(" n "))
+            .enumerate()
+            .map(|(i, l)| (i + 1, l[..10].parse::<usize>().unwrap()))
+            .collect();
+        (entries, xref_offset)
+    }
+
+    #[test]
+    fn escapes_pdf_string_delimiters() {
+        assert_eq!(pdf_escape(r"O'Neil (Jr.) \ co"), r"O'Neil \(Jr.\) \\ co");
+        assert_eq!(pdf_escape("Zoë\n"), "Zo??");
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        let pdf = render_pdf(&receipt_with("Jane Doe", rental_lines(3)));
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        let (entries, xref_offset) = xref_entries(&pdf);
+        assert!(pdf[xref_offset..].starts_with(b"xref\n0 "));
+        assert_eq!(entries.len(), 5);
+        for (number, offset) in entries {
+            let header = format!("{} 0 obj\n", number);
+            assert!(pdf[offset..].starts_with(header.as_bytes()), "object {} is not at {}", number, offset);
+        }
+        assert!(find(&pdf, &format!("/Size {} ", 6), 0).is_some());
+    }
+
+    #[test]
+    fn stream_lengths_match_their_contents() {
+        let pdf = render_pdf(&receipt_with("Jane Doe", rental_lines(120)));
+        let mut from = 0;
+        let mut streams = 0;
+        while let Some(length_at) = find(&pdf, "<< /Length ", from) {
+            let digits_at = length_at + "<< /Length ".len();
+            let digits_end = find(&pdf, " >>", digits_at).unwrap();
+            let length = std::str::from_utf8(&pdf[digits_at..digits_end]).unwrap().parse::<usize>().unwrap();
+            let content_at = find(&pdf, "stream\n", digits_end).unwrap() + "stream\n".len();
+            let content_end = find(&pdf, "\nendstream", content_at).unwrap();
+            assert_eq!(content_end - content_at, length);
+            from = content_end;
+            streams += 1;
+        }
+        assert!(streams > 1);
+    }
+
+    #[test]
+    fn renter_names_and_line_items_are_escaped() {
+        let mut receipt = receipt_with(r"Smith (\Jo)", vec![line("Pet fee (dog)", Decimal::new(1500, 2))]);
+        receipt.tax_lines = vec![line(r"City \ county tax", Decimal::new(120, 2))];
+        let pdf = String::from_utf8(render_pdf(&receipt)).unwrap();
+
+        assert!(pdf.contains(r"Renter:       Smith \(\\Jo\)"));
+        assert!(pdf.contains(r"(Pet fee \(dog\)"));
+        assert!(pdf.contains(r"(City \\ county tax"));
+        assert!(!pdf.contains("(dog)"));
+        // every content line is one balanced string operand
+        for text_line in pdf.lines().filter(|l| l.ends_with(") Tj T*")) {
+            let unescaped = text_line.replace(r"\\", "").replace(r"\(", "").replace(r"\)", "");
+            assert_eq!(unescaped.matches('(').count(), 1, "{}", text_line);
+            assert_eq!(unescaped.matches(')').count(), 1, "{}", text_line);
+        }
+    }
+
+    #[test]
+    fn long_receipts_span_several_pages() {
+        // 8 header lines, a blank line, title and rule before each section's items, 2 lines per total
+        let pdf = render_pdf(&receipt_with("Jane Doe", rental_lines(100)));
+        let text = String::from_utf8(pdf.clone()).unwrap();
+
+        assert!(text.contains("/Kids [4 0 R 6 0 R 8 0 R] /Count 3"));
+        assert_eq!(text.matches("/Type /Page /Parent 2 0 R").count(), 3);
+        assert_eq!(text.matches(") Tj T*").count(), 8 + (3 + 100) + (3 + 1) * 3 + 2 * 2);
+        let (entries, _) = xref_entries(&pdf);
+        assert_eq!(entries.len(), 3 + 3 * 2);
+
+        // the last line of the receipt lands on the last page
+        let last_page = text.rfind("9 0 obj").unwrap();
+        assert!(text[last_page..].contains("AMOUNT PAID"));
+        assert!(!text[..last_page].contains("AMOUNT PAID"));
+    }
+
+    #[test]
+    fn short_receipts_fit_one_page() {
+        let text = String::from_utf8(render_pdf(&receipt_with("Jane Doe", rental_lines(3)))).unwrap();
+        assert!(text.contains("/Kids [4 0 R] /Count 1"));
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+use diesel::prelude::*;
+use crate::{connection_pool, methods, model, proj_config, schema};
+use crate::methods::remote_vehicle::{self, RemoteVehicle, RemoteVehicleError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommandOrigin {
+    pub vehicle_id: i32,
+    pub agreement_id: Option<i32>,
+    pub triggered_by: Option<i32>,
+}
+
+pub fn command_status(result: &Result<(), RemoteVehicleError>) -> (model::RemoteCommandStatus, Option<String>) {
+    match result {
+        Ok(()) => (model::RemoteCommandStatus::Succeeded, None),
+        Err(RemoteVehicleError::Rejected(reason)) => (model::RemoteCommandStatus::Rejected, Some(reason.clone())),
+        Err(RemoteVehicleError::Offline) => (model::RemoteCommandStatus::Offline, None),
+        Err(RemoteVehicleError::Timeout) => (model::RemoteCommandStatus::Timeout, None),
+        Err(RemoteVehicleError::Unsupported) => (model::RemoteCommandStatus::Unsupported, None),
+        Err(RemoteVehicleError::Provider(err)) => (model::RemoteCommandStatus::Failed, Some(err.clone())),
+    }
+}
+
+// no point retrying a command the provider does not have
+pub fn is_retryable(err: &RemoteVehicleError) -> bool {
+    !matches!(err, RemoteVehicleError::Unsupported)
+}
+
+// wait before the attempt after `failed_attempts` failures, doubling each time
+pub fn retry_delay(failed_attempts: u32) -> Duration {
+    let factor = 2u64.saturating_pow(failed_attempts.saturating_sub(1));
+    Duration::from_secs(proj_config::REMOTE_LOCK_RETRY_BACKOFF.saturating_mul(factor))
+}
+
+// door commands from the admin console need the vehicle's admin pin
+pub fn requires_admin_pin(command: model::RemoteCommandType) -> bool {
+    matches!(command, model::RemoteCommandType::Lock | model::RemoteCommandType::Unlock)
+}
+
+// a vehicle without a stored pin never accepts one
+pub fn admin_pin_matches(stored: Option<&str>, given: Option<&str>) -> bool {
+    match (stored, given) {
+        (Some(stored), Some(given)) => !stored.is_empty() && stored == given.trim(),
+        _ => false,
+    }
+}
+
+// sends the command and records the attempt, a failed write to the log never fails the command
+pub async fn send_logged(
+    remote: &dyn RemoteVehicle,
+    command: model::RemoteCommandType,
+    origin: CommandOrigin,
+    attempt: i32,
+) -> Result<(), RemoteVehicleError> {
+    let started = Instant::now();
+    let result = remote_vehicle::send_command(remote, command).await;
+    let latency_ms = i32::try_from(started.elapsed().as_millis()).unwrap_or(i32::MAX);
+
+    let (status, detail) = command_status(&result);
+    let log = model::NewRemoteCommandLog {
+        vehicle_id: origin.vehicle_id,
+        agreement_id: origin.agreement_id,
+        triggered_by: origin.triggered_by,
+        command,
+        attempt,
+        status,
+        detail,
+        latency_ms,
+    };
+    let mut pool = connection_pool().await.get().unwrap();
+    use schema::remote_commands::dsl as rc_q;
+    let inserted = diesel::insert_into(rc_q::remote_commands)
+        .values(&log)
+        .execute(&mut pool);
+    if inserted.is_err() {
+        eprintln!("remote-command: DB error logging {:?} for vehicle #{}", command, origin.vehicle_id);
+    }
+    result
+}
+
+// a lock that does not land leaves the car open, so failures keep retrying in the background
+pub async fn lock_with_retry(remote: Box<dyn RemoteVehicle>, origin: CommandOrigin) -> Result<(), RemoteVehicleError> {
+    let first = send_logged(remote.as_ref(), model::RemoteCommandType::Lock, origin, 1).await;
+    if let Err(err) = &first && is_retryable(err) {
+        tokio::spawn(retry_lock(remote, origin));
+    }
+    first
+}
+
+async fn retry_lock(remote: Box<dyn RemoteVehicle>, origin: CommandOrigin) {
+    for attempt in 2..=proj_config::REMOTE_LOCK_ATTEMPTS {
+        tokio::time::sleep(retry_delay(attempt - 1)).await;
+        match send_logged(remote.as_ref(), model::RemoteCommandType::Lock, origin, attempt as i32).await {
+            Ok(()) => return,
+            Err(err) if !is_retryable(&err) => break,
+            Err(_) => {}
+        }
+    }
+    alert_lock_failed(origin).await;
+}
+
+async fn alert_lock_failed(origin: CommandOrigin) {
+    let mut pool = connection_pool().await.get().unwrap();
+    use schema::vehicles::dsl as v_q;
+    use schema::locations::dsl as l_q;
+    let vehicle = v_q::vehicles
+        .find(origin.vehicle_id)
+        .inner_join(l_q::locations)
+        .select((v_q::name, v_q::license_number, l_q::apartment_id))
+        .get_result::<(String, String, i32)>(&mut pool);
+    let Ok((name, license_number, apartment_id)) = vehicle else {
+        eprintln!("remote-command: DB error loading vehicle #{} for lock alert", origin.vehicle_id);
+        return
+    };
+
+    let message = format!(
+        "{} ({}) did not lock after {} attempts. Please check the vehicle. ",
+        name, license_number, proj_config::REMOTE_LOCK_ATTEMPTS
+    );
+    if methods::user::notify_apartment_staff(apartment_id, "Vehicle Not Locked", &message).await.is_err() {
+        eprintln!("remote-command: DB error alerting staff about vehicle #{}", origin.vehicle_id);
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_from_the_base_backoff() {
+        let base = proj_config::REMOTE_LOCK_RETRY_BACKOFF;
+        assert_eq!(retry_delay(1), Duration::from_secs(base));
+        assert_eq!(retry_delay(2), Duration::from_secs(base * 2));
+        assert_eq!(retry_delay(4), Duration::from_secs(base * 8));
+    }
+
+    #[test]
+    fn retry_delay_does_not_overflow() {
+        assert_eq!(retry_delay(200), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn results_map_to_logged_status() {
+        assert_eq!(command_status(&Ok(())), (model::RemoteCommandStatus::Succeeded, None));
+        assert_eq!(
+            command_status(&Err(RemoteVehicleError::Rejected(String::from("door open")))),
+            (model::RemoteCommandStatus::Rejected, Some(String::from("door open"))),
+        );
+        assert_eq!(command_status(&Err(RemoteVehicleError::Timeout)), (model::RemoteCommandStatus::Timeout, None));
+        assert_eq!(command_status(&Err(RemoteVehicleError::Offline)), (model::RemoteCommandStatus::Offline, None));
+        assert_eq!(
+            command_status(&Err(RemoteVehicleError::Provider(String::from("502")))),
+            (model::RemoteCommandStatus::Failed, Some(String::from("502"))),
+        );
+    }
+
+    #[test]
+    fn only_door_commands_need_the_admin_pin() {
+        assert!(requires_admin_pin(model::RemoteCommandType::Lock));
+        assert!(requires_admin_pin(model::RemoteCommandType::Unlock));
+        assert!(!requires_admin_pin(model::RemoteCommandType::Wake));
+        assert!(!requires_admin_pin(model::RemoteCommandType::Honk));
+        assert!(!requires_admin_pin(model::RemoteCommandType::Flash));
+        assert!(!requires_admin_pin(model::RemoteCommandType::ClimateOn));
+    }
+
+    #[test]
+    fn admin_pin_must_be_stored_and_match() {
+        assert!(admin_pin_matches(Some("4821"), Some("4821")));
+        assert!(admin_pin_matches(Some("4821"), Some(" 4821 ")));
+        assert!(!admin_pin_matches(Some("4821"), Some("1284")));
+        assert!(!admin_pin_matches(Some("4821"), None));
+        assert!(!admin_pin_matches(None, Some("4821")));
+        assert!(!admin_pin_matches(Some(""), Some("")));
+    }
+
+    #[test]
+    fn unsupported_commands_are_not_retried() {
+        assert!(!is_retryable(&RemoteVehicleError::Unsupported));
+        assert!(is_retryable(&RemoteVehicleError::Timeout));
+        assert!(is_retryable(&RemoteVehicleError::Offline));
+        assert!(is_retryable(&RemoteVehicleError::Rejected(String::from("door open"))));
+    }
+}
@@ -0,0 +1,664 @@
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use futures::future::{BoxFuture, FutureExt};
+use reqwest::Method;
+use crate::{helper_model, integration, model, proj_config};
+use crate::integration::geotab_veygo::GeotabError;
+use crate::integration::revers_veygo::ReversError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteVehicleError {
+    // the vehicle has no remote management, or the provider lacks this command
+    Unsupported,
+    Offline,
+    Timeout,
+    // the provider answered and refused, e.g. a door is open
+    Rejected(String),
+    Provider(String),
+}
+
+impl fmt::Display for RemoteVehicleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteVehicleError::Unsupported => write!(f, "not supported by this vehicle"),
+            RemoteVehicleError::Offline => write!(f, "vehicle is offline"),
+            RemoteVehicleError::Timeout => write!(f, "vehicle did not respond in time"),
+            RemoteVehicleError::Rejected(reason) => write!(f, "command rejected: {}", reason),
+            RemoteVehicleError::Provider(err) => write!(f, "provider error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RemoteVehicleError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Online,
+    Asleep,
+    Offline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemoteTelemetry {
+    pub odometer: i32,
+    // battery or fuel level in percent
+    pub level: i32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+pub type RemoteResult<'a, T> = BoxFuture<'a, Result<T, RemoteVehicleError>>;
+
+pub trait RemoteVehicle: Send + Sync {
+    fn wake(&self) -> RemoteResult<'_, ()>;
+    // connection state as reported by the provider, reading it never wakes the car
+    fn status(&self) -> RemoteResult<'_, ConnectionState>;
+    fn lock(&self) -> RemoteResult<'_, ()>;
+    fn unlock(&self) -> RemoteResult<'_, ()>;
+    fn honk(&self) -> RemoteResult<'_, ()>;
+    fn flash(&self) -> RemoteResult<'_, ()>;
+    fn set_climate(&self, on: bool) -> RemoteResult<'_, ()>;
+    fn read_telemetry(&self) -> RemoteResult<'_, RemoteTelemetry>;
+}
+
+pub fn for_vehicle(remote_mgmt: model::RemoteMgmtType, remote_mgmt_id: &str) -> Result<Box<dyn RemoteVehicle>, RemoteVehicleError> {
+    let remote_mgmt_id = remote_mgmt_id.to_string();
+    let remote: Box<dyn RemoteVehicle> = match remote_mgmt {
+        model::RemoteMgmtType::Tesla => Box::new(TeslaVehicle { vehicle_tag: remote_mgmt_id }),
+        model::RemoteMgmtType::Geotab => Box::new(GeotabVehicle { device_id: remote_mgmt_id }),
+        model::RemoteMgmtType::Revers => Box::new(ReversVehicle { vehicle_id: remote_mgmt_id }),
+        model::RemoteMgmtType::None => return Err(RemoteVehicleError::Unsupported),
+    };
+    #[cfg(test)]
+    if let Some(fake) = fake::installed() {
+        return Ok(Box::new(fake));
+    }
+    Ok(remote)
+}
+
+pub async fn within_timeout<T>(future: impl Future<Output = Result<T, RemoteVehicleError>>) -> Result<T, RemoteVehicleError> {
+    let limit = Duration::from_secs(proj_config::REMOTE_REQUEST_TIMEOUT);
+    tokio::time::timeout(limit, future).await.unwrap_or(Err(RemoteVehicleError::Timeout))
+}
+
+async fn wait_until_online_with(remote: &dyn RemoteVehicle, attempts: u32, interval: Duration) -> Result<(), RemoteVehicleError> {
+    for attempt in 0..attempts {
+        if let Ok(ConnectionState::Online) = within_timeout(remote.status()).await {
+            return Ok(());
+        }
+        // a single wake request, the car takes a few seconds to come up
+        if attempt == 0 {
+            let _ = within_timeout(remote.wake()).await;
+        }
+        tokio::time::sleep(interval).await;
+    }
+    Err(RemoteVehicleError::Timeout)
+}
+
+pub async fn wait_until_online(remote: &dyn RemoteVehicle) -> Result<(), RemoteVehicleError> {
+    let interval = Duration::from_secs(proj_config::REMOTE_WAKE_POLL_INTERVAL);
+    wait_until_online_with(remote, proj_config::REMOTE_WAKE_ATTEMPTS, interval).await
+}
+
+// reads and commands are still attempted when the car never reported online, it may have woken late
+pub async fn read_telemetry(remote: &dyn RemoteVehicle) -> Result<RemoteTelemetry, RemoteVehicleError> {
+    let _ = wait_until_online(remote).await;
+    within_timeout(remote.read_telemetry()).await
+}
+
+// the odometer and level are copied onto `vehicle`, saving it is left to the caller
+pub async fn refresh_readings(remote: &dyn RemoteVehicle, vehicle: &mut model::Vehicle) -> Result<RemoteTelemetry, RemoteVehicleError> {
+    let telemetry = read_telemetry(remote).await?;
+    vehicle.odometer = telemetry.odometer;
+    vehicle.tank_level_percentage = telemetry.level;
+    Ok(telemetry)
+}
+
+pub async fn send_command(remote: &dyn RemoteVehicle, command: model::RemoteCommandType) -> Result<(), RemoteVehicleError> {
+    let woken = wait_until_online(remote).await;
+    let future = match command {
+        // waking on its own only succeeds once the car reports online
+        model::RemoteCommandType::Wake => return woken,
+        model::RemoteCommandType::Lock => remote.lock(),
+        model::RemoteCommandType::Unlock => remote.unlock(),
+        model::RemoteCommandType::Honk => remote.honk(),
+        model::RemoteCommandType::Flash => remote.flash(),
+        model::RemoteCommandType::ClimateOn => remote.set_climate(true),
+        model::RemoteCommandType::ClimateOff => remote.set_climate(false),
+    };
+    within_timeout(future).await
+}
+
+struct TeslaVehicle {
+    vehicle_tag: String,
+}
+
+impl TeslaVehicle {
+    async fn request(&self, method: Method, path: &str) -> Result<reqwest::Response, RemoteVehicleError> {
+        let response = integration::tesla_veygo::tesla_make_request(method, path, None)
+            .await
+            .map_err(|err| RemoteVehicleError::Provider(err.to_string()))?;
+        match response.status().as_u16() {
+            200..=299 => Ok(response),
+            408 => Err(RemoteVehicleError::Offline),
+            status => Err(RemoteVehicleError::Provider(format!("Tesla API returned {}", status))),
+        }
+    }
+
+    async fn command(&self, command: &str) -> Result<(), RemoteVehicleError> {
+        let path = format!("/api/1/vehicles/{}/command/{}", self.vehicle_tag, command);
+        let response = self.request(Method::POST, &path).await?;
+        let Ok(json) = response.json::<serde_json::Value>().await else {
+            return Ok(());
+        };
+        let result = json.get("response").and_then(|r| r.get("result")).and_then(|r| r.as_bool());
+        if result == Some(false) {
+            let reason = json["response"]["reason"].as_str().unwrap_or_default().to_string();
+            return Err(RemoteVehicleError::Rejected(reason));
+        }
+        Ok(())
+    }
+}
+
+impl RemoteVehicle for TeslaVehicle {
+    fn wake(&self) -> RemoteResult<'_, ()> {
+        async move {
+            let wake_path = format!("/api/1/vehicles/{}/wake_up", self.vehicle_tag);
+            self.request(Method::POST, &wake_path).await.map(|_| ())
+        }.boxed()
+    }
+
+    fn status(&self) -> RemoteResult<'_, ConnectionState> {
+        async move {
+            let status_path = format!("/api/1/vehicles/{}", self.vehicle_tag);
+            let response = self.request(Method::GET, &status_path).await?;
+            let Ok(json) = response.json::<serde_json::Value>().await else {
+                return Err(RemoteVehicleError::Provider(String::from("Tesla API response JSON decode error")));
+            };
+            Ok(match json["response"]["state"].as_str() {
+                Some("online") => ConnectionState::Online,
+                Some("asleep") => ConnectionState::Asleep,
+                _ => ConnectionState::Offline,
+            })
+        }.boxed()
+    }
+
+    fn lock(&self) -> RemoteResult<'_, ()> {
+        self.command("door_lock").boxed()
+    }
+
+    fn unlock(&self) -> RemoteResult<'_, ()> {
+        self.command("door_unlock").boxed()
+    }
+
+    fn honk(&self) -> RemoteResult<'_, ()> {
+        self.command("honk_horn").boxed()
+    }
+
+    fn flash(&self) -> RemoteResult<'_, ()> {
+        self.command("flash_lights").boxed()
+    }
+
+    // the car conditions to the temperature last set in it
+    fn set_climate(&self, on: bool) -> RemoteResult<'_, ()> {
+        let command = if on { "auto_conditioning_start" } else { "auto_conditioning_stop" };
+        self.command(command).boxed()
+    }
+
+    fn read_telemetry(&self) -> RemoteResult<'_, RemoteTelemetry> {
+        async move {
+            let tesla_path = format!("/api/1/vehicles/{}/vehicle_data?endpoints=location_data%3Bcharge_state%3Bvehicle_state", self.vehicle_tag);
+            let response = self.request(Method::GET, &tesla_path).await?;
+            let Ok(envelope) = response.json::<helper_model::TeslaVehicleDataEnvelope>().await else {
+                return Err(RemoteVehicleError::Provider(String::from("Tesla API response JSON decode error")));
+            };
+            let data = envelope.response;
+            Ok(RemoteTelemetry {
+                odometer: data.vehicle_state.odometer.round() as i32,
+                level: data.charge_state.battery_level,
+                latitude: Some(data.drive_state.latitude),
+                longitude: Some(data.drive_state.longitude),
+            })
+        }.boxed()
+    }
+}
+
+struct GeotabVehicle {
+    device_id: String,
+}
+
+impl From<GeotabError> for RemoteVehicleError {
+    fn from(err: GeotabError) -> Self {
+        match err {
+            GeotabError::DeviceOffline => RemoteVehicleError::Offline,
+            GeotabError::CommandRejected(reason) => RemoteVehicleError::Rejected(reason),
+            GeotabError::NoHornChannel => RemoteVehicleError::Unsupported,
+            err => RemoteVehicleError::Provider(err.to_string()),
+        }
+    }
+}
+
+// the GO device keeps its own connection, there is nothing to wake
+impl RemoteVehicle for GeotabVehicle {
+    fn wake(&self) -> RemoteResult<'_, ()> {
+        async { Ok(()) }.boxed()
+    }
+
+    fn status(&self) -> RemoteResult<'_, ConnectionState> {
+        async { Ok(ConnectionState::Online) }.boxed()
+    }
+
+    fn lock(&self) -> RemoteResult<'_, ()> {
+        async move {
+            Ok(integration::geotab_veygo::geotab_lock_doors(&self.device_id).await?)
+        }.boxed()
+    }
+
+    fn unlock(&self) -> RemoteResult<'_, ()> {
+        async move {
+            Ok(integration::geotab_veygo::geotab_unlock_doors(&self.device_id).await?)
+        }.boxed()
+    }
+
+    fn honk(&self) -> RemoteResult<'_, ()> {
+        async move {
+            Ok(integration::geotab_veygo::geotab_honk_horn(&self.device_id).await?)
+        }.boxed()
+    }
+
+    fn flash(&self) -> RemoteResult<'_, ()> {
+        async { Err(RemoteVehicleError::Unsupported) }.boxed()
+    }
+
+    fn set_climate(&self, _on: bool) -> RemoteResult<'_, ()> {
+        async { Err(RemoteVehicleError::Unsupported) }.boxed()
+    }
+
+    fn read_telemetry(&self) -> RemoteResult<'_, RemoteTelemetry> {
+        async move {
+            let data = integration::geotab_veygo::geotab_vehicle_data(&self.device_id).await?;
+            Ok(RemoteTelemetry {
+                odometer: data.odometer,
+                level: data.fuel_level,
+                latitude: Some(data.latitude),
+                longitude: Some(data.longitude),
+            })
+        }.boxed()
+    }
+}
+
+struct ReversVehicle {
+    vehicle_id: String,
+}
+
+impl From<ReversError> for RemoteVehicleError {
+    fn from(err: ReversError) -> Self {
+        match err {
+            ReversError::VehicleOffline => RemoteVehicleError::Offline,
+            ReversError::CommandTimedOut => RemoteVehicleError::Timeout,
+            ReversError::CommandFailed(reason) => RemoteVehicleError::Rejected(reason),
+            err => RemoteVehicleError::Provider(err.to_string()),
+        }
+    }
+}
+
+// Revers queues commands until the car connects, so waking is left to the provider
+impl RemoteVehicle for ReversVehicle {
+    fn wake(&self) -> RemoteResult<'_, ()> {
+        async { Ok(()) }.boxed()
+    }
+
+    fn status(&self) -> RemoteResult<'_, ConnectionState> {
+        async { Ok(ConnectionState::Online) }.boxed()
+    }
+
+    fn lock(&self) -> RemoteResult<'_, ()> {
+        async move {
+            Ok(integration::revers_veygo::revers_lock_doors(&self.vehicle_id).await?)
+        }.boxed()
+    }
+
+    fn unlock(&self) -> RemoteResult<'_, ()> {
+        async move {
+            Ok(integration::revers_veygo::revers_unlock_doors(&self.vehicle_id).await?)
+        }.boxed()
+    }
+
+    fn honk(&self) -> RemoteResult<'_, ()> {
+        async { Err(RemoteVehicleError::Unsupported) }.boxed()
+    }
+
+    fn flash(&self) -> RemoteResult<'_, ()> {
+        async { Err(RemoteVehicleError::Unsupported) }.boxed()
+    }
+
+    fn set_climate(&self, _on: bool) -> RemoteResult<'_, ()> {
+        async { Err(RemoteVehicleError::Unsupported) }.boxed()
+    }
+
+    fn read_telemetry(&self) -> RemoteResult<'_, RemoteTelemetry> {
+        async move {
+            let status = integration::revers_veygo::revers_vehicle_status(&self.vehicle_id).await?;
+            Ok(RemoteTelemetry {
+                odometer: status.odometer,
+                level: status.fuel_level,
+                latitude: status.latitude,
+                longitude: status.longitude,
+            })
+        }.boxed()
+    }
+}
+
+// Stand-in vehicle for tests, for_vehicle hands it to every handler running inside `with_installed`
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum FakeCall {
+        Wake,
+        Status,
+        Lock,
+        Unlock,
+        Honk,
+        Flash,
+        Climate(bool),
+        ReadTelemetry,
+    }
+
+    #[derive(Debug)]
+    struct FakeState {
+        // status polls answered with Asleep before the car reports Online
+        polls_until_online: u32,
+        telemetry: Result<RemoteTelemetry, RemoteVehicleError>,
+        command_result: Result<(), RemoteVehicleError>,
+        calls: Vec<FakeCall>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct FakeRemoteVehicle {
+        state: Arc<Mutex<FakeState>>,
+    }
+
+    impl FakeRemoteVehicle {
+        pub fn new(telemetry: RemoteTelemetry) -> FakeRemoteVehicle {
+            FakeRemoteVehicle {
+                state: Arc::new(Mutex::new(FakeState {
+                    polls_until_online: 0,
+                    telemetry: Ok(telemetry),
+                    command_result: Ok(()),
+                    calls: Vec::new(),
+                })),
+            }
+        }
+
+        pub fn asleep_for(self, polls: u32) -> FakeRemoteVehicle {
+            self.state.lock().unwrap().polls_until_online = polls;
+            self
+        }
+
+        pub fn failing_with(self, err: RemoteVehicleError) -> FakeRemoteVehicle {
+            {
+                let mut state = self.state.lock().unwrap();
+                state.telemetry = Err(err.clone());
+                state.command_result = Err(err);
+            }
+            self
+        }
+
+        pub fn calls(&self) -> Vec<FakeCall> {
+            self.state.lock().unwrap().calls.clone()
+        }
+
+        fn record(&self, call: FakeCall) {
+            self.state.lock().unwrap().calls.push(call);
+        }
+
+        fn command(&self, call: FakeCall) -> RemoteResult<'_, ()> {
+            self.record(call);
+            let result = self.state.lock().unwrap().command_result.clone();
+            async move { result }.boxed()
+        }
+    }
+
+    impl RemoteVehicle for FakeRemoteVehicle {
+        fn wake(&self) -> RemoteResult<'_, ()> {
+            self.record(FakeCall::Wake);
+            async { Ok(()) }.boxed()
+        }
+
+        fn status(&self) -> RemoteResult<'_, ConnectionState> {
+            let mut state = self.state.lock().unwrap();
+            state.calls.push(FakeCall::Status);
+            let connection = if state.polls_until_online == 0 {
+                ConnectionState::Online
+            } else {
+                state.polls_until_online -= 1;
+                ConnectionState::Asleep
+            };
+            async move { Ok(connection) }.boxed()
+        }
+
+        fn lock(&self) -> RemoteResult<'_, ()> {
+            self.command(FakeCall::Lock)
+        }
+
+        fn unlock(&self) -> RemoteResult<'_, ()> {
+            self.command(FakeCall::Unlock)
+        }
+
+        fn honk(&self) -> RemoteResult<'_, ()> {
+            self.command(FakeCall::Honk)
+        }
+
+        fn flash(&self) -> RemoteResult<'_, ()> {
+            self.command(FakeCall::Flash)
+        }
+
+        fn set_climate(&self, on: bool) -> RemoteResult<'_, ()> {
+            self.command(FakeCall::Climate(on))
+        }
+
+        fn read_telemetry(&self) -> RemoteResult<'_, RemoteTelemetry> {
+            self.record(FakeCall::ReadTelemetry);
+            let telemetry = self.state.lock().unwrap().telemetry.clone();
+            async move { telemetry }.boxed()
+        }
+    }
+
+    // scoped to the task, tests running in parallel each see only their own fake
+    tokio::task_local! {
+        static INSTALLED: FakeRemoteVehicle;
+    }
+
+    pub async fn with_installed<F: Future>(fake: FakeRemoteVehicle, future: F) -> F::Output {
+        INSTALLED.scope(fake, future).await
+    }
+
+    pub(super) fn installed() -> Option<FakeRemoteVehicle> {
+        INSTALLED.try_with(FakeRemoteVehicle::clone).ok()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fake::{FakeCall, FakeRemoteVehicle};
+
+    fn parked_car() -> RemoteTelemetry {
+        RemoteTelemetry { odometer: 12_345, level: 80, latitude: Some(40.4237), longitude: Some(-86.9212) }
+    }
+
+    #[tokio::test]
+    async fn online_car_is_not_woken() {
+        let car = FakeRemoteVehicle::new(parked_car());
+        assert_eq!(wait_until_online_with(&car, 16, Duration::from_millis(1)).await, Ok(()));
+        assert_eq!(car.calls(), vec![FakeCall::Status]);
+    }
+
+    #[tokio::test]
+    async fn sleeping_car_is_woken_once_and_polled() {
+        let car = FakeRemoteVehicle::new(parked_car()).asleep_for(3);
+        assert_eq!(wait_until_online_with(&car, 16, Duration::from_millis(1)).await, Ok(()));
+        assert_eq!(car.calls(), vec![
+            FakeCall::Status, FakeCall::Wake, FakeCall::Status, FakeCall::Status, FakeCall::Status,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn car_that_never_wakes_times_out() {
+        let car = FakeRemoteVehicle::new(parked_car()).asleep_for(100);
+        let result = wait_until_online_with(&car, 4, Duration::from_millis(1)).await;
+        assert_eq!(result, Err(RemoteVehicleError::Timeout));
+        let wakes = car.calls().iter().filter(|call| **call == FakeCall::Wake).count();
+        assert_eq!(wakes, 1);
+    }
+
+    #[tokio::test]
+    async fn commands_reach_the_matching_call() {
+        let car = FakeRemoteVehicle::new(parked_car());
+        assert_eq!(send_command(&car, model::RemoteCommandType::Lock).await, Ok(()));
+        assert_eq!(send_command(&car, model::RemoteCommandType::Unlock).await, Ok(()));
+        assert_eq!(send_command(&car, model::RemoteCommandType::Honk).await, Ok(()));
+        let commands = car.calls().into_iter().filter(|call| *call != FakeCall::Status).collect::<Vec<_>>();
+        assert_eq!(commands, vec![FakeCall::Lock, FakeCall::Unlock, FakeCall::Honk]);
+    }
+
+    #[tokio::test]
+    async fn console_commands_reach_the_matching_call() {
+        let car = FakeRemoteVehicle::new(parked_car());
+        assert_eq!(send_command(&car, model::RemoteCommandType::Flash).await, Ok(()));
+        assert_eq!(send_command(&car, model::RemoteCommandType::ClimateOn).await, Ok(()));
+        assert_eq!(send_command(&car, model::RemoteCommandType::ClimateOff).await, Ok(()));
+        let commands = car.calls().into_iter().filter(|call| *call != FakeCall::Status).collect::<Vec<_>>();
+        assert_eq!(commands, vec![FakeCall::Flash, FakeCall::Climate(true), FakeCall::Climate(false)]);
+    }
+
+    #[tokio::test]
+    async fn wake_command_only_wakes() {
+        let car = FakeRemoteVehicle::new(parked_car()).asleep_for(1);
+        assert_eq!(send_command(&car, model::RemoteCommandType::Wake).await, Ok(()));
+        assert_eq!(car.calls(), vec![FakeCall::Status, FakeCall::Wake, FakeCall::Status]);
+    }
+
+    #[tokio::test]
+    async fn provider_errors_are_passed_through() {
+        let car = FakeRemoteVehicle::new(parked_car()).failing_with(RemoteVehicleError::Rejected(String::from("door open")));
+        assert_eq!(send_command(&car, model::RemoteCommandType::Lock).await, Err(RemoteVehicleError::Rejected(String::from("door open"))));
+        assert_eq!(read_telemetry(&car).await, Err(RemoteVehicleError::Rejected(String::from("door open"))));
+    }
+
+    #[test]
+    fn vehicles_without_remote_management_are_unsupported() {
+        assert!(matches!(for_vehicle(model::RemoteMgmtType::None, ""), Err(RemoteVehicleError::Unsupported)));
+    }
+
+    fn parked_vehicle(remote_mgmt: model::RemoteMgmtType) -> model::Vehicle {
+        model::Vehicle {
+            id: 7,
+            vin: String::from("5YJ3E1EA7KF000001"),
+            name: String::from("Boiler"),
+            capacity: 5,
+            doors: 4,
+            small_bags: 2,
+            large_bags: 1,
+            carplay: true,
+            lane_keep: true,
+            available: true,
+            license_number: String::from("ABC123"),
+            license_state: String::from("IN"),
+            year: String::from("2022"),
+            make: String::from("Tesla"),
+            model: String::from("Model 3"),
+            msrp_factor: rust_decimal::Decimal::ONE,
+            image_link: None,
+            odometer: 12_000,
+            tank_size: rust_decimal::Decimal::ZERO,
+            tank_level_percentage: 50,
+            first_transponder_number: None,
+            first_transponder_company_id: None,
+            second_transponder_number: None,
+            second_transponder_company_id: None,
+            third_transponder_number: None,
+            third_transponder_company_id: None,
+            fourth_transponder_number: None,
+            fourth_transponder_company_id: None,
+            location_id: 1,
+            remote_mgmt,
+            remote_mgmt_id: String::from("tag"),
+            requires_own_insurance: false,
+            admin_pin: None,
+            maintenance_hold: false,
+        }
+    }
+
+    // what check-in, check-out and generate-snapshot do with the vehicle they loaded
+    async fn refresh_like_a_handler(vehicle: &mut model::Vehicle) -> Result<RemoteTelemetry, RemoteVehicleError> {
+        let remote = for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id)?;
+        refresh_readings(remote.as_ref(), vehicle).await
+    }
+
+    #[tokio::test]
+    async fn installed_fake_is_handed_out_for_every_provider() {
+        for remote_mgmt in [model::RemoteMgmtType::Tesla, model::RemoteMgmtType::Geotab, model::RemoteMgmtType::Revers] {
+            let car = FakeRemoteVehicle::new(parked_car());
+            let mut vehicle = parked_vehicle(remote_mgmt);
+
+            let telemetry = fake::with_installed(car.clone(), refresh_like_a_handler(&mut vehicle)).await;
+            assert_eq!(telemetry, Ok(parked_car()));
+            assert_eq!((vehicle.odometer, vehicle.tank_level_percentage), (12_345, 80));
+            assert_eq!(car.calls(), vec![FakeCall::Status, FakeCall::ReadTelemetry]);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_reading_leaves_the_vehicle_alone() {
+        let car = FakeRemoteVehicle::new(parked_car()).failing_with(RemoteVehicleError::Offline);
+        let mut vehicle = parked_vehicle(model::RemoteMgmtType::Tesla);
+
+        let telemetry = fake::with_installed(car, refresh_like_a_handler(&mut vehicle)).await;
+        assert_eq!(telemetry, Err(RemoteVehicleError::Offline));
+        assert_eq!((vehicle.odometer, vehicle.tank_level_percentage), (12_000, 50));
+    }
+
+    #[tokio::test]
+    async fn concurrent_installs_do_not_see_each_other() {
+        let first = FakeRemoteVehicle::new(parked_car());
+        let second = FakeRemoteVehicle::new(RemoteTelemetry { odometer: 99, level: 10, latitude: None, longitude: None });
+        let (mut first_vehicle, mut second_vehicle) = (parked_vehicle(model::RemoteMgmtType::Tesla), parked_vehicle(model::RemoteMgmtType::Geotab));
+
+        let (first_reading, second_reading) = tokio::join!(
+            fake::with_installed(first.clone(), refresh_like_a_handler(&mut first_vehicle)),
+            fake::with_installed(second.clone(), refresh_like_a_handler(&mut second_vehicle)),
+        );
+        assert_eq!(first_reading.map(|telemetry| telemetry.odometer), Ok(12_345));
+        assert_eq!(second_reading.map(|telemetry| telemetry.odometer), Ok(99));
+        assert_eq!(first.calls().len(), 2);
+        assert_eq!(second.calls().len(), 2);
+    }
+
+    #[test]
+    fn revers_errors_map_to_remote_errors() {
+        assert_eq!(RemoteVehicleError::from(ReversError::VehicleOffline), RemoteVehicleError::Offline);
+        assert_eq!(RemoteVehicleError::from(ReversError::CommandTimedOut), RemoteVehicleError::Timeout);
+        assert_eq!(
+            RemoteVehicleError::from(ReversError::CommandFailed(String::from("hood open"))),
+            RemoteVehicleError::Rejected(String::from("hood open")),
+        );
+        assert!(matches!(RemoteVehicleError::from(ReversError::Unauthorized), RemoteVehicleError::Provider(_)));
+    }
+
+    #[test]
+    fn geotab_errors_map_to_remote_errors() {
+        assert_eq!(RemoteVehicleError::from(GeotabError::DeviceOffline), RemoteVehicleError::Offline);
+        assert_eq!(
+            RemoteVehicleError::from(GeotabError::CommandRejected(String::from("No IOX relay installed"))),
+            RemoteVehicleError::Rejected(String::from("No IOX relay installed")),
+        );
+        assert_eq!(RemoteVehicleError::from(GeotabError::NoHornChannel), RemoteVehicleError::Unsupported);
+        assert!(matches!(RemoteVehicleError::from(GeotabError::Unauthorized), RemoteVehicleError::Provider(_)));
+    }
+}
@@ -0,0 +1,13 @@
+use std::env;
+use std::time::Duration;
+use crate::proj_config;
+
+// gap between remote management calls that keeps the poller under its per-minute budget
+pub fn request_spacing() -> Duration {
+    let per_minute = env::var("TELEMETRY_REQUESTS_PER_MINUTE")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(proj_config::TELEMETRY_REQUESTS_PER_MINUTE);
+    Duration::from_millis(60_000 / per_minute)
+}
@@ -1,5 +1,5 @@
 use std::option::Option;
-use crate::{connection_pool, model, schema};
+use crate::{connection_pool, integration, model, schema};
 use crate::helper_model::VeygoError;
 use chrono::{Datelike, Duration, NaiveDate, Utc};
 use diesel::prelude::*;
@@ -270,3 +270,28 @@ fn user_plan_renewal_date(
 
     NaiveDate::from_ymd_opt(year, month, day).ok_or(VeygoError::InternalServerError)
 }
+
+// push an alert to every admin plus the managers of this apartment
+pub async fn notify_apartment_staff(apartment_id: i32, title: &str, message: &str) -> Result<(), VeygoError> {
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::renters::dsl as r_q;
+    let staff = r_q::renters
+        .filter(r_q::admin_apple_apns.is_not_null())
+        .filter(r_q::employee_tier.eq(model::EmployeeTier::Admin).or(r_q::apartment_id.eq(apartment_id)))
+        .get_results::<model::Renter>(&mut pool);
+    let Ok(staff) = staff else {
+        return Err(VeygoError::InternalServerError);
+    };
+    for employee in staff {
+        if !(employee.is_operational_admin() || employee.is_operational_manager() && employee.apartment_id == apartment_id) {
+            continue
+        }
+        if let Some(admin_apns) = &employee.admin_apple_apns {
+            let _ = integration::apns_veygo::send_notification(
+                admin_apns, title, message, true
+            ).await;
+        }
+    }
+    Ok(())
+}
@@ -98,6 +98,29 @@ pub enum RemoteMgmtType {
     None,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = sql_types::RemoteCommandEnum)]
+pub enum RemoteCommandType {
+    Lock,
+    Unlock,
+    Honk,
+    Wake,
+    Flash,
+    ClimateOn,
+    ClimateOff,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = sql_types::RemoteCommandStatusEnum)]
+pub enum RemoteCommandStatus {
+    Succeeded,
+    Rejected,
+    Offline,
+    Timeout,
+    Unsupported,
+    Failed,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
 #[diesel(sql_type = sql_types::AgreementStatusEnum)]
 pub enum AgreementStatus {
@@ -262,7 +285,7 @@ impl FromSql<sql_types::AuditActionEnum, Pg> for AuditActionType {
 impl ToSql<sql_types::RemoteMgmtEnum, Pg> for RemoteMgmtType {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
         match *self {
-            RemoteMgmtType::Revers => out.write_all(b"reverse")?,
+            RemoteMgmtType::Revers => out.write_all(b"revers")?,
             RemoteMgmtType::Tesla => out.write_all(b"tesla")?,
             RemoteMgmtType::Geotab => out.write_all(b"geotab")?,
             RemoteMgmtType::None => out.write_all(b"none")?,
@@ -274,7 +297,7 @@ impl ToSql<sql_types::RemoteMgmtEnum, Pg> for RemoteMgmtType {
 impl FromSql<sql_types::RemoteMgmtEnum, Pg> for RemoteMgmtType {
     fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
         match bytes.as_bytes() {
-            b"reverse" => Ok(RemoteMgmtType::Revers),
+            b"revers" => Ok(RemoteMgmtType::Revers),
             b"tesla" => Ok(RemoteMgmtType::Tesla),
             b"geotab" => Ok(RemoteMgmtType::Geotab),
             b"none" => Ok(RemoteMgmtType::None),
@@ -283,6 +306,64 @@ impl FromSql<sql_types::RemoteMgmtEnum, Pg> for RemoteMgmtType {
     }
 }
 
+impl ToSql<sql_types::RemoteCommandEnum, Pg> for RemoteCommandType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            RemoteCommandType::Lock => out.write_all(b"lock")?,
+            RemoteCommandType::Unlock => out.write_all(b"unlock")?,
+            RemoteCommandType::Honk => out.write_all(b"honk")?,
+            RemoteCommandType::Wake => out.write_all(b"wake")?,
+            RemoteCommandType::Flash => out.write_all(b"flash")?,
+            RemoteCommandType::ClimateOn => out.write_all(b"climate_on")?,
+            RemoteCommandType::ClimateOff => out.write_all(b"climate_off")?,
+        }
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::RemoteCommandEnum, Pg> for RemoteCommandType {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"lock" => Ok(RemoteCommandType::Lock),
+            b"unlock" => Ok(RemoteCommandType::Unlock),
+            b"honk" => Ok(RemoteCommandType::Honk),
+            b"wake" => Ok(RemoteCommandType::Wake),
+            b"flash" => Ok(RemoteCommandType::Flash),
+            b"climate_on" => Ok(RemoteCommandType::ClimateOn),
+            b"climate_off" => Ok(RemoteCommandType::ClimateOff),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl ToSql<sql_types::RemoteCommandStatusEnum, Pg> for RemoteCommandStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            RemoteCommandStatus::Succeeded => out.write_all(b"succeeded")?,
+            RemoteCommandStatus::Rejected => out.write_all(b"rejected")?,
+            RemoteCommandStatus::Offline => out.write_all(b"offline")?,
+            RemoteCommandStatus::Timeout => out.write_all(b"timeout")?,
+            RemoteCommandStatus::Unsupported => out.write_all(b"unsupported")?,
+            RemoteCommandStatus::Failed => out.write_all(b"failed")?,
+        }
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::RemoteCommandStatusEnum, Pg> for RemoteCommandStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"succeeded" => Ok(RemoteCommandStatus::Succeeded),
+            b"rejected" => Ok(RemoteCommandStatus::Rejected),
+            b"offline" => Ok(RemoteCommandStatus::Offline),
+            b"timeout" => Ok(RemoteCommandStatus::Timeout),
+            b"unsupported" => Ok(RemoteCommandStatus::Unsupported),
+            b"failed" => Ok(RemoteCommandStatus::Failed),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
 impl ToSql<sql_types::AgreementStatusEnum, Pg> for AgreementStatus {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
         match *self {
@@ -731,6 +812,8 @@ pub struct Apartment {
     pub latitude_higher_bound: f64,
     pub longitude_lower_bound: f64,
     pub longitude_higher_bound: f64,
+    // battery or tank percentage a car needs before the next pickup
+    pub min_pickup_charge: i32,
 }
 
 #[derive(Insertable, Debug, Clone, PartialEq, Deserialize)]
@@ -782,6 +865,8 @@ pub struct NewApartment {
     pub latitude_higher_bound: Option<f64>,
     pub longitude_lower_bound: Option<f64>,
     pub longitude_higher_bound: Option<f64>,
+    #[serde(default)]
+    pub min_pickup_charge: Option<i32>,
 }
 
 #[derive(Queryable, Selectable, Identifiable, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -888,6 +973,7 @@ pub struct Vehicle {
     pub remote_mgmt_id: String,
     pub requires_own_insurance: bool,
     pub admin_pin: Option<String>,
+    pub maintenance_hold: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -955,6 +1041,7 @@ pub struct PublishAdminVehicle {
     pub remote_mgmt_id: String,
     pub requires_own_insurance: bool,
     pub admin_pin: Option<String>,
+    pub maintenance_hold: bool,
 }
 
 impl From<Vehicle> for PublishRenterVehicle {
@@ -1022,6 +1109,7 @@ impl From<Vehicle> for PublishAdminVehicle {
             remote_mgmt_id: v.remote_mgmt_id,
             requires_own_insurance: v.requires_own_insurance,
             admin_pin: v.admin_pin,
+            maintenance_hold: v.maintenance_hold,
         }
     }
 }
@@ -1065,6 +1153,82 @@ pub struct NewVehicle {
     pub requires_own_insurance: bool,
 }
 
+// distinguishes a missing field (leave as is) from an explicit null (clear it)
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(AsChangeset, Debug, Clone, PartialEq, Deserialize, Default)]
+#[diesel(table_name = vehicles)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VehicleUpdate {
+    pub name: Option<String>,
+    pub license_number: Option<String>,
+    pub license_state: Option<String>,
+    #[serde(default, with = "rust_decimal::serde::str_option")]
+    pub msrp_factor: Option<Decimal>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub image_link: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub first_transponder_number: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub first_transponder_company_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub second_transponder_number: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub second_transponder_company_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub third_transponder_number: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub third_transponder_company_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub fourth_transponder_number: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub fourth_transponder_company_id: Option<Option<i32>>,
+    pub location_id: Option<i32>,
+    pub remote_mgmt: Option<RemoteMgmtType>,
+    pub remote_mgmt_id: Option<String>,
+    pub requires_own_insurance: Option<bool>,
+}
+
+#[derive(Queryable, Identifiable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(table_name = services)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Service {
+    pub id: i32,
+    // miles between services
+    pub interval: i32,
+    pub note: String,
+}
+
+#[derive(Insertable, Debug, Clone, PartialEq, Eq, Deserialize)]
+#[diesel(table_name = services)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewService {
+    pub interval: i32,
+    pub note: String,
+}
+
+// the most recent service of each type performed on a vehicle
+#[derive(
+    Queryable, Identifiable, Insertable, AsChangeset, Associations, Debug, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[diesel(belongs_to(Vehicle))]
+#[diesel(belongs_to(Service))]
+#[diesel(primary_key(vehicle_id, service_id))]
+#[diesel(table_name = vehicles_services)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VehicleService {
+    pub vehicle_id: i32,
+    pub service_id: i32,
+    pub odometer: i32,
+    pub document: String,
+}
+
 #[derive(
     Queryable, Identifiable, Associations, Debug, Clone, PartialEq, Serialize, Deserialize,
 )]
@@ -1333,6 +1497,7 @@ pub struct Agreement {
     #[serde(with = "rust_decimal::serde::str")]
     pub minimum_earning_rate: Decimal,
     pub deposit_pmt_id: Option<i32>,
+    pub void_reason: Option<String>,
 }
 
 #[derive(Deserialize, Insertable, Debug, Clone, PartialEq)]
@@ -1407,6 +1572,11 @@ pub struct Charge {
     pub transponder_company_id: Option<i32>,
     pub vehicle_identifier: Option<String>,
     pub is_taxed: bool,
+    // declined card payments by the nightly late-charge run
+    pub billing_attempts: i32,
+    // set once the charge is handed to staff instead of being billed
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub flagged_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize, Insertable, Debug, Clone, PartialEq)]
@@ -1662,6 +1832,144 @@ pub struct ApartmentTax {
     pub tax_id: i32,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, AsChangeset, Queryable, Insertable)]
+#[diesel(table_name = cancellation_fees)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CancellationFee {
+    pub apartment_id: i32,
+    pub hours_before: i32,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub fee: Decimal,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, AsChangeset, Queryable, Insertable)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(table_name = overdue_alerts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OverdueAlert {
+    pub agreement_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub renter_notified_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub staff_notified_at: Option<DateTime<Utc>>,
+    pub conflicting_agreement_id: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, AsChangeset, Queryable, Insertable)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(table_name = low_charge_alerts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LowChargeAlert {
+    pub agreement_id: i32,
+    pub vehicle_id: i32,
+    pub minimum_level: i32,
+    // last level seen before pickup
+    pub level: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub detected_at: DateTime<Utc>,
+    // trip the car was out on when its renter was asked to bring it back charged
+    pub current_agreement_id: Option<i32>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub renter_notified_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub staff_notified_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub handed_off_at: Option<DateTime<Utc>>,
+    pub handoff_level: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Insertable)]
+#[diesel(table_name = sent_reminders)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewSentReminder {
+    pub agreement_id: i32,
+    pub reminder: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Queryable, Identifiable, AsChangeset)]
+#[diesel(table_name = vehicle_blackouts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VehicleBlackout {
+    pub id: i32,
+    pub vehicle_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub reason: String,
+    pub created_by: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Insertable)]
+#[diesel(table_name = vehicle_blackouts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewVehicleBlackout {
+    pub vehicle_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    pub reason: String,
+    pub created_by: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Queryable, Identifiable)]
+#[diesel(table_name = vehicle_telemetry)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VehicleTelemetry {
+    pub id: i32,
+    pub vehicle_id: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub recorded_at: DateTime<Utc>,
+    pub battery_level: i32,
+    pub odometer: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Insertable)]
+#[diesel(table_name = vehicle_telemetry)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewVehicleTelemetry {
+    pub vehicle_id: i32,
+    pub battery_level: i32,
+    pub odometer: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Queryable, Identifiable)]
+#[diesel(table_name = remote_commands)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RemoteCommandLog {
+    pub id: i32,
+    pub vehicle_id: i32,
+    pub agreement_id: Option<i32>,
+    // renter or admin who asked for it, retries keep the original requester
+    pub triggered_by: Option<i32>,
+    pub command: RemoteCommandType,
+    pub attempt: i32,
+    pub status: RemoteCommandStatus,
+    pub detail: Option<String>,
+    pub latency_ms: i32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub sent_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Insertable)]
+#[diesel(table_name = remote_commands)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NewRemoteCommandLog {
+    pub vehicle_id: i32,
+    pub agreement_id: Option<i32>,
+    pub triggered_by: Option<i32>,
+    pub command: RemoteCommandType,
+    pub attempt: i32,
+    pub status: RemoteCommandStatus,
+    pub detail: Option<String>,
+    pub latency_ms: i32,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, AsChangeset, Queryable)]
 #[diesel(treat_none_as_null = true)]
 #[diesel(table_name = audits)]
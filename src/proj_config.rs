@@ -8,6 +8,53 @@ pub static DEPOSIT_AMOUNT: i64 = 200;
 #[allow(dead_code)]
 pub const PRICE_PER_CENT_ON_GAS: Decimal = Decimal::from_parts(150, 0, 0, false, 2);
 
+// minutes after the reserved pickup before an unpicked reservation is voided as a no-show
+#[allow(dead_code)]
+pub static NO_SHOW_GRACE_PERIOD: i64 = 60;
+#[allow(dead_code)]
+pub const NO_SHOW_FEE: Decimal = Decimal::from_parts(2500, 0, 0, false, 2);
+// minutes between repeated overdue alerts for the same agreement
+#[allow(dead_code)]
+pub static OVERDUE_ALERT_INTERVAL: i64 = 60;
+// minutes overdue before staff are alerted, unless the next reservation is already at risk
+#[allow(dead_code)]
+pub static OVERDUE_STAFF_ESCALATION: i64 = 30;
+// minutes ahead to look for the next reservation on an overdue vehicle
+#[allow(dead_code)]
+pub static OVERDUE_CONFLICT_WINDOW: i64 = 180;
+// how often the frequent scheduled task runs, in seconds
+#[allow(dead_code)]
+pub static FREQUENT_TASK_INTERVAL: u64 = 300;
+// declined payments before late tolls and citations are handed to staff
+#[allow(dead_code)]
+pub static LATE_CHARGE_MAX_ATTEMPTS: i32 = 3;
+// minutes ahead of a pickup to start checking the car's charge
+#[allow(dead_code)]
+pub static LOW_CHARGE_LOOKAHEAD: i64 = 720;
+// seconds between fleet telemetry sweeps
+#[allow(dead_code)]
+pub static TELEMETRY_POLL_INTERVAL: u64 = 900;
+// remote management calls per minute the telemetry poller may make, overridable with TELEMETRY_REQUESTS_PER_MINUTE
+#[allow(dead_code)]
+pub static TELEMETRY_REQUESTS_PER_MINUTE: u64 = 30;
+// days of telemetry history to keep
+#[allow(dead_code)]
+pub static TELEMETRY_RETENTION_DAYS: i64 = 90;
+// status polls while waiting for a remote vehicle to wake, one per REMOTE_WAKE_POLL_INTERVAL seconds
+#[allow(dead_code)]
+pub static REMOTE_WAKE_ATTEMPTS: u32 = 16;
+#[allow(dead_code)]
+pub static REMOTE_WAKE_POLL_INTERVAL: u64 = 1;
+// seconds a single remote management request may take before it counts as timed out
+#[allow(dead_code)]
+pub static REMOTE_REQUEST_TIMEOUT: u64 = 20;
+// tries at a remote lock before staff are told the car may be open
+#[allow(dead_code)]
+pub static REMOTE_LOCK_ATTEMPTS: u32 = 5;
+// seconds before the first lock retry, doubled after every failure
+#[allow(dead_code)]
+pub static REMOTE_LOCK_RETRY_BACKOFF: u64 = 30;
+
 #[allow(dead_code)]
 pub static MIN_IOS_VERSION: &str = "1.0.1";
 #[allow(dead_code)]
@@ -1,4 +1,5 @@
-use crate::{connection_pool, integration, model, helper_model::VeygoError, methods};
+use crate::{connection_pool, integration, model, helper_model, helper_model::VeygoError, methods, proj_config};
+use askama::Template;
 use chrono::{Datelike, NaiveTime, Utc};
 use diesel::prelude::*;
 use std::time::Duration;
@@ -231,6 +232,872 @@ pub async fn nightly_task() {
         let _ = diesel::delete(
             ro_q::rate_offers.filter(ro_q::exp.lt(now))
         ).execute(&mut pool);
+        // Bill tolls and citations that arrived after their trip was closed
+        bill_late_charges().await;
+        // Hold vehicles that have driven past a service interval
+        check_maintenance_due().await;
+        // Delete telemetry past its retention period
+        use crate::schema::vehicle_telemetry::dsl as vt_q;
+        let _ = diesel::delete(
+            vt_q::vehicle_telemetry.filter(vt_q::recorded_at.lt(now - chrono::Duration::days(proj_config::TELEMETRY_RETENTION_DAYS)))
+        ).execute(&mut pool);
         println!("===== Daily Tasks Completed =====\n");
     }
 }
+
+pub async fn frequent_task() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(proj_config::FREQUENT_TASK_INTERVAL)).await;
+
+        void_no_shows().await;
+        alert_overdue_returns().await;
+        send_trip_reminders().await;
+        check_pickup_charge().await;
+    }
+}
+
+pub async fn telemetry_task() {
+    loop {
+        poll_fleet_telemetry().await;
+        tokio::time::sleep(Duration::from_secs(proj_config::TELEMETRY_POLL_INTERVAL)).await;
+    }
+}
+
+// reads every remote managed vehicle that is already awake
+async fn poll_fleet_telemetry() {
+    use crate::schema::vehicles::dsl as v_q;
+    let vehicles = v_q::vehicles
+        .filter(v_q::remote_mgmt.ne(model::RemoteMgmtType::None))
+        .order(v_q::id)
+        .get_results::<model::Vehicle>(&mut connection_pool().await.get().unwrap());
+    let Ok(vehicles) = vehicles else {
+        eprintln!("telemetry: database error loading vehicles");
+        return
+    };
+
+    let spacing = methods::telemetry::request_spacing();
+    let mut recorded = 0;
+    for vehicle in vehicles {
+        let Ok(remote) = methods::remote_vehicle::for_vehicle(vehicle.remote_mgmt, &vehicle.remote_mgmt_id) else {
+            continue
+        };
+        // sleeping cars are skipped rather than woken, waking drains the 12V battery
+        let state = methods::remote_vehicle::within_timeout(remote.status()).await;
+        tokio::time::sleep(spacing).await;
+        match state {
+            Ok(methods::remote_vehicle::ConnectionState::Online) => {}
+            Ok(_) => continue,
+            Err(err) => {
+                eprintln!("telemetry: error reading state of vehicle #{}: {}", vehicle.id, err);
+                continue
+            }
+        }
+
+        let data = methods::remote_vehicle::within_timeout(remote.read_telemetry()).await;
+        tokio::time::sleep(spacing).await;
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("telemetry: error reading telemetry of vehicle #{}: {}", vehicle.id, err);
+                continue
+            }
+        };
+        let (Some(latitude), Some(longitude)) = (data.latitude, data.longitude) else {
+            continue
+        };
+
+        // a connection is only taken once there is something to write, the sweep spends most of its time waiting
+        let mut pool = connection_pool().await.get().unwrap();
+        let telemetry = model::NewVehicleTelemetry {
+            vehicle_id: vehicle.id,
+            battery_level: data.level,
+            odometer: data.odometer,
+            latitude,
+            longitude,
+        };
+        use crate::schema::vehicle_telemetry::dsl as vt_q;
+        let inserted = diesel::insert_into(vt_q::vehicle_telemetry)
+            .values(&telemetry)
+            .execute(&mut pool);
+        if inserted.is_err() {
+            eprintln!("telemetry: DB error saving telemetry for vehicle #{}", vehicle.id);
+            continue
+        }
+
+        // only these two columns, the vehicle row may have been edited since the sweep started
+        let updated = diesel::update(v_q::vehicles.find(vehicle.id))
+            .set((v_q::odometer.eq(telemetry.odometer), v_q::tank_level_percentage.eq(telemetry.battery_level)))
+            .execute(&mut pool);
+        if updated.is_err() {
+            eprintln!("telemetry: DB error updating vehicle #{}", vehicle.id);
+        }
+        recorded += 1;
+    }
+    println!("telemetry: recorded {} vehicle(s)", recorded);
+}
+
+async fn void_no_shows() {
+    let now = Utc::now();
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::agreements::dsl as ag_q;
+    use crate::schema::renters::dsl as r_q;
+    use crate::schema::payment_methods::dsl as pm_q;
+    let no_shows = ag_q::agreements
+        .inner_join(r_q::renters)
+        .inner_join(pm_q::payment_methods)
+        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+        .filter(ag_q::actual_pickup_time.is_null())
+        .filter(ag_q::rsvp_pickup_time.lt(now - chrono::Duration::minutes(proj_config::NO_SHOW_GRACE_PERIOD)))
+        .select((ag_q::agreements::all_columns(), r_q::stripe_id, r_q::apple_apns, pm_q::token))
+        .get_results::<(model::Agreement, String, Option<String>, String)>(&mut pool);
+
+    let Ok(no_shows) = no_shows else {
+        eprintln!("no-show: database error loading agreements");
+        return;
+    };
+
+    for (agreement, stripe_id, apple_apns, payment_method_token) in no_shows {
+        // Void before charging, a later run or a failure below never reaches the fee twice
+        let claimed = diesel::update(
+            ag_q::agreements
+                .find(agreement.id)
+                .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                .filter(ag_q::actual_pickup_time.is_null())
+        )
+            .set(ag_q::status.eq(model::AgreementStatus::Void))
+            .get_result::<model::Agreement>(&mut pool);
+        let agreement = match claimed {
+            Ok(agreement) => agreement,
+            // picked up or canceled since the query above
+            Err(diesel::result::Error::NotFound) => continue,
+            Err(_) => {
+                eprintln!("no-show: DB error voiding RSVP #{}", agreement.confirmation);
+                continue
+            }
+        };
+        println!("no-show: voided RSVP #{}", agreement.confirmation);
+
+        let mut no_show_fee_2dp = proj_config::NO_SHOW_FEE.round_dp(2);
+        no_show_fee_2dp.rescale(2);
+
+        let fee_intent = if no_show_fee_2dp.mantissa() >= 50 {
+            let description = "RSVP #".to_owned() + &*agreement.confirmation + " No-show Fee";
+            let idempotency_key = format!("no-show-fee-{}", agreement.id);
+            let pmi = integration::stripe_veygo::create_payment_intent_idempotent(
+                &stripe_id, &payment_method_token, no_show_fee_2dp.mantissa() as i64, PaymentIntentCaptureMethod::Automatic, &description, &idempotency_key
+            ).await;
+            match pmi {
+                Ok(pmi) => Some(pmi),
+                Err(err) => {
+                    // the reservation stays voided so the vehicle is released
+                    eprintln!("no-show: could not collect fee for RSVP #{}: {}", agreement.confirmation, err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // The booking payments are kept, only holds that were never captured are let go
+        if methods::agreement::release_auth_holds(agreement.id).await.is_err() {
+            eprintln!("no-show: could not release auth holds for RSVP #{}", agreement.confirmation);
+        }
+
+        if let Some(pmi) = &fee_intent {
+            let _ = diesel::update(pm_q::payment_methods.filter(pm_q::token.eq(&payment_method_token)))
+                .set(pm_q::last_used_date_time.eq(now))
+                .execute(&mut pool);
+
+            use crate::schema::payments::dsl as pmt_q;
+            let new_payment = model::NewPayment {
+                payment_type: model::PaymentType::Succeeded,
+                amount: no_show_fee_2dp,
+                note: Some(String::from("No-show fee")),
+                reference_number: Some(pmi.id.to_string()),
+                agreement_id: agreement.id,
+                renter_id: agreement.renter_id,
+                payment_method_id: Some(agreement.payment_method_id),
+                amount_authorized: no_show_fee_2dp,
+                capture_before: None,
+            };
+            if diesel::insert_into(pmt_q::payments).values(&new_payment).execute(&mut pool).is_err() {
+                eprintln!("no-show: DB error saving fee payment for RSVP #{}, payment {} collected", agreement.confirmation, pmi.id);
+            }
+        }
+
+        if methods::agreement::reverse_reward_transactions(agreement.id).await.is_err() {
+            eprintln!("no-show: could not return reward hours for RSVP #{}", agreement.confirmation);
+        }
+
+        let message = if fee_intent.is_some() {
+            format!("Your reservation #{} was not picked up and has been canceled. A no-show fee of ${} was charged. ", agreement.confirmation, no_show_fee_2dp)
+        } else {
+            format!("Your reservation #{} was not picked up and has been canceled. ", agreement.confirmation)
+        };
+        if let Some(renter_app_apns) = apple_apns {
+            let _ = integration::apns_veygo::send_notification(
+                &renter_app_apns, "Reservation Canceled", &message, false
+            ).await;
+        }
+        let renter_email = integration::mailgun_veygo::make_email_obj(&agreement.user_email, &agreement.user_name);
+        let _ = integration::mailgun_veygo::send_email(None, vec![renter_email], "Your reservation has been canceled", &message, None).await;
+    }
+}
+
+async fn alert_overdue_returns() {
+    let now = Utc::now();
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::agreements::dsl as ag_q;
+    use crate::schema::renters::dsl as r_q;
+    use crate::schema::locations::dsl as l_q;
+    use crate::schema::overdue_alerts::dsl as oa_q;
+    let overdue = ag_q::agreements
+        .inner_join(r_q::renters)
+        .inner_join(l_q::locations)
+        .left_join(oa_q::overdue_alerts)
+        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+        .filter(ag_q::actual_pickup_time.is_not_null())
+        .filter(ag_q::actual_drop_off_time.is_null())
+        .filter(ag_q::rsvp_drop_off_time.lt(now))
+        .select((
+            ag_q::agreements::all_columns(),
+            r_q::apple_apns,
+            l_q::apartment_id,
+            oa_q::overdue_alerts::all_columns().nullable(),
+        ))
+        .get_results::<(model::Agreement, Option<String>, i32, Option<model::OverdueAlert>)>(&mut pool);
+
+    let Ok(overdue) = overdue else {
+        eprintln!("overdue: database error loading agreements");
+        return;
+    };
+
+    let alert_interval = chrono::Duration::minutes(proj_config::OVERDUE_ALERT_INTERVAL);
+
+    for (agreement, apple_apns, apartment_id, alert) in overdue {
+        let mut alert = alert.unwrap_or(model::OverdueAlert {
+            agreement_id: agreement.id,
+            renter_notified_at: None,
+            staff_notified_at: None,
+            conflicting_agreement_id: None,
+        });
+        let overdue_minutes = (now - agreement.rsvp_drop_off_time).num_minutes();
+
+        // next reservation on this vehicle that the late return puts at risk
+        let next_agreement = ag_q::agreements
+            .filter(ag_q::vehicle_id.eq(agreement.vehicle_id))
+            .filter(ag_q::id.ne(agreement.id))
+            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+            .filter(ag_q::actual_pickup_time.is_null())
+            .filter(ag_q::rsvp_pickup_time.lt(now + chrono::Duration::minutes(proj_config::OVERDUE_CONFLICT_WINDOW + proj_config::RSVP_BUFFER)))
+            .order(ag_q::rsvp_pickup_time.asc())
+            .first::<model::Agreement>(&mut pool)
+            .optional();
+        let Ok(next_agreement) = next_agreement else {
+            eprintln!("overdue: database error loading next agreement for RSVP #{}", agreement.confirmation);
+            continue
+        };
+        let newly_flagged = next_agreement.as_ref().map(|ag| ag.id) != alert.conflicting_agreement_id;
+        alert.conflicting_agreement_id = next_agreement.as_ref().map(|ag| ag.id);
+
+        let renter_due = alert.renter_notified_at.is_none_or(|time| now - time >= alert_interval);
+        if renter_due {
+            if let Some(renter_app_apns) = &apple_apns {
+                let message = format!(
+                    "Your trip #{} was due back {} minutes ago. Please return the vehicle as soon as possible, late returns are charged at twice the rate. ",
+                    agreement.confirmation, overdue_minutes
+                );
+                let _ = integration::apns_veygo::send_notification(
+                    renter_app_apns, "Vehicle Overdue", &message, false
+                ).await;
+            }
+            alert.renter_notified_at = Some(now);
+        }
+
+        let staff_due = (overdue_minutes >= proj_config::OVERDUE_STAFF_ESCALATION || alert.conflicting_agreement_id.is_some())
+            && (newly_flagged || alert.staff_notified_at.is_none_or(|time| now - time >= alert_interval));
+        if staff_due {
+            let message = match &next_agreement {
+                Some(next) => format!(
+                    "RSVP #{} is {} minutes overdue. Next reservation #{} on this vehicle starts at {}, consider moving it to another vehicle. ",
+                    agreement.confirmation, overdue_minutes, next.confirmation, next.rsvp_pickup_time.format("%H:%M UTC")
+                ),
+                None => format!("RSVP #{} is {} minutes overdue. ", agreement.confirmation, overdue_minutes),
+            };
+            if methods::user::notify_apartment_staff(apartment_id, "Vehicle Overdue", &message).await.is_err() {
+                eprintln!("overdue: database error loading staff for apartment {}", apartment_id);
+                continue
+            }
+            alert.staff_notified_at = Some(now);
+        }
+
+        let result = diesel::insert_into(oa_q::overdue_alerts)
+            .values(&alert)
+            .on_conflict(oa_q::agreement_id)
+            .do_update()
+            .set(&alert)
+            .execute(&mut pool);
+        if result.is_err() {
+            eprintln!("overdue: DB error saving alert state for RSVP #{}", agreement.confirmation);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReminderEvent {
+    Pickup,
+    Return,
+}
+
+struct Reminder {
+    key: &'static str,
+    event: ReminderEvent,
+    minutes_before: i64,
+    push: bool,
+    sms: bool,
+    email: bool,
+}
+
+// tightest first, sending one also marks the wider reminders of the same event as sent
+const REMINDERS: [Reminder; 6] = [
+    Reminder { key: "pickup_15m", event: ReminderEvent::Pickup, minutes_before: 15, push: true, sms: false, email: false },
+    Reminder { key: "pickup_1h", event: ReminderEvent::Pickup, minutes_before: 60, push: true, sms: true, email: false },
+    Reminder { key: "pickup_24h", event: ReminderEvent::Pickup, minutes_before: 1440, push: true, sms: false, email: true },
+    Reminder { key: "return_15m", event: ReminderEvent::Return, minutes_before: 15, push: true, sms: false, email: false },
+    Reminder { key: "return_1h", event: ReminderEvent::Return, minutes_before: 60, push: true, sms: true, email: false },
+    Reminder { key: "return_24h", event: ReminderEvent::Return, minutes_before: 1440, push: true, sms: false, email: true },
+];
+
+async fn send_trip_reminders() {
+    let now = Utc::now();
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::agreements::dsl as ag_q;
+    use crate::schema::renters::dsl as r_q;
+    use crate::schema::vehicles::dsl as v_q;
+    use crate::schema::locations::dsl as l_q;
+    use crate::schema::apartments::dsl as apt_q;
+    use crate::schema::sent_reminders::dsl as sr_q;
+
+    for reminder in REMINDERS.iter() {
+        let window_end = now + chrono::Duration::minutes(reminder.minutes_before);
+        let already_sent = sr_q::sent_reminders
+            .filter(sr_q::reminder.eq(reminder.key))
+            .select(sr_q::agreement_id);
+
+        let mut query = ag_q::agreements
+            .inner_join(r_q::renters)
+            .inner_join(v_q::vehicles)
+            .inner_join(l_q::locations.inner_join(apt_q::apartments))
+            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+            .filter(ag_q::id.ne_all(already_sent))
+            .into_boxed();
+        query = match reminder.event {
+            ReminderEvent::Pickup => query
+                .filter(ag_q::actual_pickup_time.is_null())
+                .filter(ag_q::rsvp_pickup_time.gt(now))
+                .filter(ag_q::rsvp_pickup_time.le(window_end)),
+            ReminderEvent::Return => query
+                .filter(ag_q::actual_pickup_time.is_not_null())
+                .filter(ag_q::actual_drop_off_time.is_null())
+                .filter(ag_q::rsvp_drop_off_time.gt(now))
+                .filter(ag_q::rsvp_drop_off_time.le(window_end)),
+        };
+        let due = query
+            .select((ag_q::agreements::all_columns(), r_q::apple_apns, v_q::name, l_q::name, apt_q::timezone))
+            .get_results::<(model::Agreement, Option<String>, String, String, String)>(&mut pool);
+
+        let Ok(due) = due else {
+            eprintln!("reminders: database error loading agreements for {}", reminder.key);
+            continue
+        };
+
+        for (agreement, apple_apns, vehicle_name, location_name, timezone) in due {
+            // claim this reminder and every wider one first, so a restart never sends it twice
+            let claims = REMINDERS.iter()
+                .filter(|other| other.event == reminder.event && other.minutes_before >= reminder.minutes_before)
+                .map(|other| model::NewSentReminder { agreement_id: agreement.id, reminder: String::from(other.key) })
+                .collect::<Vec<_>>();
+            let claimed = diesel::insert_into(sr_q::sent_reminders)
+                .values(&claims)
+                .on_conflict_do_nothing()
+                .execute(&mut pool);
+            let Ok(claimed) = claimed else {
+                eprintln!("reminders: DB error recording {} for RSVP #{}", reminder.key, agreement.confirmation);
+                continue
+            };
+            if claimed == 0 {
+                continue
+            }
+
+            let (event_time, title, verb) = match reminder.event {
+                ReminderEvent::Pickup => (agreement.rsvp_pickup_time, "Upcoming Pickup", "pick up"),
+                ReminderEvent::Return => (agreement.rsvp_drop_off_time, "Upcoming Return", "return"),
+            };
+            let local_time = match timezone.parse::<chrono_tz::Tz>() {
+                Ok(tz) => event_time.with_timezone(&tz).format("%b %-d at %-I:%M %p %Z").to_string(),
+                Err(_) => event_time.format("%b %-d at %H:%M UTC").to_string(),
+            };
+            let message = format!(
+                "Reminder: please {} your {} at {} on {}. Confirmation #{}. ",
+                verb, vehicle_name, location_name, local_time, agreement.confirmation
+            );
+
+            if reminder.push && let Some(renter_app_apns) = &apple_apns {
+                let _ = integration::apns_veygo::send_notification(
+                    renter_app_apns, title, &message, false
+                ).await;
+            }
+            // fall back to a text when the renter cannot get a push
+            if reminder.sms || (reminder.push && apple_apns.is_none()) {
+                let _ = integration::twilio_veygo::send_text(&agreement.user_phone, &message).await;
+            }
+            if reminder.email {
+                let renter_email = integration::mailgun_veygo::make_email_obj(&agreement.user_email, &agreement.user_name);
+                let subject = format!("{} for RSVP #{}", title, agreement.confirmation);
+                let _ = integration::mailgun_veygo::send_email(None, vec![renter_email], &subject, &message, None).await;
+            }
+        }
+    }
+}
+
+async fn bill_late_charges() {
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::charges::dsl as c_q;
+    use crate::schema::agreements::dsl as ag_q;
+    let unmapped_charges = c_q::charges
+        .filter(c_q::agreement_id.is_null())
+        .filter(c_q::flagged_at.is_null())
+        .order(c_q::time.asc())
+        .get_results::<model::Charge>(&mut pool);
+    let Ok(unmapped_charges) = unmapped_charges else {
+        eprintln!("late charges: database error loading unmapped charges");
+        return
+    };
+
+    let mut billable: Vec<(model::Agreement, Vec<model::Charge>)> = Vec::new();
+    let mut outside_trips: Vec<model::Charge> = Vec::new();
+    for charge in unmapped_charges {
+        // only the trip that actually had the car when the charge happened pays for it
+        let trip = ag_q::agreements
+            .filter(ag_q::vehicle_id.eq(charge.vehicle_id))
+            .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+            .filter(ag_q::actual_pickup_time.le(charge.time))
+            .filter(ag_q::actual_drop_off_time.ge(charge.time).or(ag_q::actual_drop_off_time.is_null()))
+            .order(ag_q::actual_pickup_time.desc())
+            .first::<model::Agreement>(&mut pool)
+            .optional();
+        let matched = match trip {
+            Ok(Some(trip)) => trip,
+            Ok(None) => {
+                outside_trips.push(charge);
+                continue
+            }
+            Err(_) => {
+                eprintln!("late charges: database error matching charge #{}", charge.id);
+                continue
+            }
+        };
+        // the trip is still going, check-in maps the charge
+        if matched.vehicle_snapshot_after.is_none() {
+            continue
+        }
+
+        match billable.iter_mut().find(|(ag, _)| ag.id == matched.id) {
+            Some((_, charges)) => charges.push(charge),
+            None => billable.push((matched, vec![charge])),
+        }
+    }
+
+    flag_late_charges(&outside_trips, "did not happen during any trip").await;
+
+    for (agreement, charges) in billable {
+        use crate::schema::renters::dsl as r_q;
+        use crate::schema::payment_methods::dsl as pm_q;
+        use crate::schema::vehicles::dsl as v_q;
+        let renter = ag_q::agreements
+            .find(agreement.id)
+            .inner_join(r_q::renters)
+            .inner_join(pm_q::payment_methods)
+            .inner_join(v_q::vehicles)
+            .select((r_q::stripe_id, r_q::apple_apns, pm_q::token, v_q::name))
+            .get_result::<(String, Option<String>, String, String)>(&mut pool);
+        let Ok((stripe_id, apple_apns, payment_method_token, vehicle_name)) = renter else {
+            eprintln!("late charges: database error loading renter for RSVP #{}", agreement.confirmation);
+            continue
+        };
+
+        use crate::schema::agreements_taxes::dsl as agreements_taxes_query;
+        use crate::schema::taxes::dsl as t_q;
+        let taxes = agreements_taxes_query::agreements_taxes
+            .inner_join(t_q::taxes)
+            .filter(agreements_taxes_query::agreement_id.eq(&agreement.id))
+            .select(t_q::taxes::all_columns())
+            .get_results::<model::Tax>(&mut pool);
+        let Ok(taxes) = taxes else {
+            eprintln!("late charges: database error loading taxes for RSVP #{}", agreement.confirmation);
+            continue
+        };
+
+        let drop_off_time = agreement.actual_drop_off_time.unwrap_or(agreement.rsvp_drop_off_time);
+        let hours_round_up = methods::pricing::round_up_hours(
+            std::cmp::max(drop_off_time, agreement.rsvp_drop_off_time) - agreement.rsvp_pickup_time
+        );
+        let taxed_charges: Decimal = charges.iter().filter(|c| c.is_taxed).map(|c| c.amount).sum();
+        let non_taxed_charges: Decimal = charges.iter().filter(|c| !c.is_taxed).map(|c| c.amount).sum();
+        let tax_lines = methods::pricing::sales_tax_on_charges(&taxes, hours_round_up, taxed_charges);
+        let total: Decimal = taxed_charges + non_taxed_charges + tax_lines.iter().map(|t| t.amount).sum::<Decimal>();
+        let mut total_2dp = total.round_dp(2);
+        total_2dp.rescale(2);
+
+        // Amounts below the Stripe minimum charge are waived
+        let payment_note = if total_2dp.mantissa() >= 50 {
+            let description = "RSVP #".to_owned() + &*agreement.confirmation + " Tolls and Citations";
+            let pmi = integration::stripe_veygo::create_payment_intent(
+                &stripe_id, &payment_method_token, total_2dp.mantissa() as i64, PaymentIntentCaptureMethod::Automatic, &description
+            ).await;
+            let Ok(pmi) = pmi else {
+                // leave the charges unmapped so the next run tries again, up to the attempt limit
+                let charge_ids = charges.iter().map(|c| c.id).collect::<Vec<i32>>();
+                let attempts = charges.iter().map(|c| c.billing_attempts).max().unwrap_or(0) + 1;
+                let counted = diesel::update(c_q::charges.filter(c_q::id.eq_any(&charge_ids)))
+                    .set(c_q::billing_attempts.eq(attempts))
+                    .execute(&mut pool);
+                if counted.is_err() {
+                    eprintln!("late charges: DB error counting payment attempts for RSVP #{}", agreement.confirmation);
+                }
+                eprintln!("late charges: payment failed for RSVP #{}, attempt {}", agreement.confirmation, attempts);
+                if attempts >= proj_config::LATE_CHARGE_MAX_ATTEMPTS {
+                    let reason = format!("could not be charged to RSVP #{} after {} attempts", agreement.confirmation, attempts);
+                    flag_late_charges(&charges, &reason).await;
+                }
+                continue
+            };
+
+            let now = Utc::now();
+            let _ = diesel::update(pm_q::payment_methods.filter(pm_q::token.eq(&payment_method_token)))
+                .set(pm_q::last_used_date_time.eq(now))
+                .execute(&mut pool);
+
+            use crate::schema::payments::dsl as pmt_q;
+            let new_payment = model::NewPayment {
+                payment_type: model::PaymentType::Succeeded,
+                amount: total_2dp,
+                note: Some(String::from("Tolls and citations")),
+                reference_number: Some(pmi.id.to_string()),
+                agreement_id: agreement.id,
+                renter_id: agreement.renter_id,
+                payment_method_id: Some(agreement.payment_method_id),
+                amount_authorized: total_2dp,
+                capture_before: None,
+            };
+            let payment_result = diesel::insert_into(pmt_q::payments)
+                .values(&new_payment)
+                .execute(&mut pool);
+            if payment_result.is_err() {
+                eprintln!("late charges: DB error saving payment for RSVP #{}, payment collected", agreement.confirmation);
+            }
+            format!("We charged {} to your card on file.", methods::receipt::money(total_2dp))
+        } else {
+            String::from("This amount is below our minimum charge and has been waived.")
+        };
+
+        let charge_ids = charges.iter().map(|c| c.id).collect::<Vec<i32>>();
+        let mapped = diesel::update(c_q::charges.filter(c_q::id.eq_any(&charge_ids)))
+            .set(c_q::agreement_id.eq(Some(agreement.id)))
+            .execute(&mut pool);
+        if mapped.is_err() {
+            eprintln!("late charges: DB error mapping charges to RSVP #{}", agreement.confirmation);
+            continue
+        }
+
+        let charge_lines = charges.iter()
+            .map(|charge| methods::receipt::line(charge.name.clone(), charge.amount))
+            .collect::<Vec<_>>();
+        let tax_lines = tax_lines.iter()
+            .map(|tax_line| methods::receipt::line(tax_line.name.clone(), tax_line.amount))
+            .collect::<Vec<_>>();
+        let notice = helper_model::LateChargesTemplate {
+            confirmation: agreement.confirmation.clone(),
+            renter_name: agreement.user_name.clone(),
+            vehicle_name,
+            charge_lines,
+            tax_lines,
+            total: methods::receipt::money(total_2dp),
+            payment_note: payment_note.clone(),
+        };
+
+        let message = format!("{} in charges were added to RSVP #{}. {}", notice.total, agreement.confirmation, payment_note);
+        if let Some(renter_app_apns) = apple_apns {
+            let _ = integration::apns_veygo::send_notification(
+                &renter_app_apns, "Charges Added to Your Trip", &message, false
+            ).await;
+        }
+        if let Ok(html) = notice.render() {
+            let renter_email = integration::mailgun_veygo::make_email_obj(&agreement.user_email, &agreement.user_name);
+            let subject = format!("Charges added to RSVP #{}", agreement.confirmation);
+            let _ = integration::mailgun_veygo::send_email(None, vec![renter_email], &subject, &html, None).await;
+        }
+        println!("late charges: billed {} to RSVP #{}", notice.total, agreement.confirmation);
+    }
+}
+
+// Charges nobody can be billed for are kept off the nightly run and left to staff
+async fn flag_late_charges(charges: &[model::Charge], reason: &str) {
+    if charges.is_empty() {
+        return
+    }
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::charges::dsl as c_q;
+    let charge_ids = charges.iter().map(|c| c.id).collect::<Vec<i32>>();
+    let flagged = diesel::update(c_q::charges.filter(c_q::id.eq_any(&charge_ids)))
+        .set(c_q::flagged_at.eq(Some(Utc::now())))
+        .execute(&mut pool);
+    if flagged.is_err() {
+        eprintln!("late charges: DB error flagging charges {:?}", charge_ids);
+        return
+    }
+
+    use crate::schema::vehicles::dsl as v_q;
+    use crate::schema::locations::dsl as l_q;
+    let mut vehicle_ids = charges.iter().map(|c| c.vehicle_id).collect::<Vec<i32>>();
+    vehicle_ids.sort_unstable();
+    vehicle_ids.dedup();
+    let vehicles = v_q::vehicles
+        .inner_join(l_q::locations)
+        .filter(v_q::id.eq_any(&vehicle_ids))
+        .select((v_q::id, v_q::name, v_q::license_number, l_q::apartment_id))
+        .get_results::<(i32, String, String, i32)>(&mut pool);
+    let Ok(vehicles) = vehicles else {
+        eprintln!("late charges: DB error loading vehicles for flagged charges {:?}", charge_ids);
+        return
+    };
+
+    for (vehicle_id, name, license_number, apartment_id) in vehicles {
+        let vehicle_charges = charges.iter().filter(|c| c.vehicle_id == vehicle_id).collect::<Vec<_>>();
+        let total: Decimal = vehicle_charges.iter().map(|c| c.amount).sum();
+        let message = format!(
+            "{} charge(s) totaling {} on {} ({}) {}. Please review them. ",
+            vehicle_charges.len(), methods::receipt::money(total), name, license_number, reason
+        );
+        if methods::user::notify_apartment_staff(apartment_id, "Charges Need Review", &message).await.is_err() {
+            eprintln!("late charges: DB error alerting staff about vehicle #{}", vehicle_id);
+        }
+    }
+}
+
+async fn check_maintenance_due() {
+    let overdue = methods::maintenance::overdue_services_by_vehicle(None).await;
+    let Ok(overdue) = overdue else {
+        eprintln!("maintenance: database error loading service records");
+        return
+    };
+
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::vehicles::dsl as v_q;
+    use crate::schema::locations::dsl as l_q;
+    let vehicles = v_q::vehicles
+        .inner_join(l_q::locations)
+        .filter(v_q::maintenance_hold.eq(true).or(v_q::id.eq_any(overdue.keys().copied().collect::<Vec<i32>>())))
+        .select((v_q::vehicles::all_columns(), l_q::apartment_id))
+        .get_results::<(model::Vehicle, i32)>(&mut pool);
+    let Ok(vehicles) = vehicles else {
+        eprintln!("maintenance: database error loading vehicles");
+        return
+    };
+
+    for (vehicle, apartment_id) in vehicles {
+        let Some(services) = overdue.get(&vehicle.id) else {
+            // serviced since the hold was placed
+            let released = diesel::update(v_q::vehicles.find(vehicle.id))
+                .set(v_q::maintenance_hold.eq(false))
+                .execute(&mut pool);
+            if released.is_err() {
+                eprintln!("maintenance: DB error releasing hold on vehicle #{}", vehicle.id);
+            }
+            continue
+        };
+        // staff are alerted once, when the hold is placed
+        if vehicle.maintenance_hold {
+            continue
+        }
+        let held = diesel::update(v_q::vehicles.find(vehicle.id))
+            .set(v_q::maintenance_hold.eq(true))
+            .execute(&mut pool);
+        if held.is_err() {
+            eprintln!("maintenance: DB error placing hold on vehicle #{}", vehicle.id);
+            continue
+        }
+
+        let due = services.iter().map(|service| service.note.as_str()).collect::<Vec<&str>>().join(", ");
+        let message = format!(
+            "{} ({}) is at {} miles and overdue for: {}. It is held from new bookings until the service is recorded. ",
+            vehicle.name, vehicle.license_number, vehicle.odometer, due
+        );
+        if methods::user::notify_apartment_staff(apartment_id, "Service Overdue", &message).await.is_err() {
+            eprintln!("maintenance: database error loading staff for apartment {}", apartment_id);
+        }
+        println!("maintenance: held vehicle #{} for {}", vehicle.id, due);
+    }
+}
+
+// the next pickup on each car needs the apartment's minimum charge, ask the current renter or staff to top it up
+async fn check_pickup_charge() {
+    let now = Utc::now();
+    let mut pool = connection_pool().await.get().unwrap();
+
+    use crate::schema::agreements::dsl as ag_q;
+    use crate::schema::vehicles::dsl as v_q;
+    use crate::schema::locations::dsl as l_q;
+    use crate::schema::apartments::dsl as apt_q;
+    use crate::schema::renters::dsl as r_q;
+    use crate::schema::low_charge_alerts::dsl as lca_q;
+    let upcoming = ag_q::agreements
+        .inner_join(v_q::vehicles)
+        .inner_join(l_q::locations.inner_join(apt_q::apartments))
+        .left_join(lca_q::low_charge_alerts)
+        .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+        .filter(ag_q::actual_pickup_time.is_null())
+        .filter(ag_q::rsvp_pickup_time.gt(now))
+        .filter(ag_q::rsvp_pickup_time.le(now + chrono::Duration::minutes(proj_config::LOW_CHARGE_LOOKAHEAD)))
+        .order((ag_q::vehicle_id, ag_q::rsvp_pickup_time.asc()))
+        .select((
+            ag_q::agreements::all_columns(),
+            v_q::vehicles::all_columns(),
+            l_q::apartment_id,
+            apt_q::min_pickup_charge,
+            apt_q::timezone,
+            lca_q::low_charge_alerts::all_columns().nullable(),
+        ))
+        .get_results::<(model::Agreement, model::Vehicle, i32, i32, String, Option<model::LowChargeAlert>)>(&mut pool);
+    let Ok(upcoming) = upcoming else {
+        eprintln!("low charge: database error loading upcoming agreements");
+        return
+    };
+
+    let mut checked_vehicle_ids: Vec<i32> = Vec::new();
+    for (agreement, vehicle, apartment_id, min_pickup_charge, timezone, alert) in upcoming {
+        // only the next pickup on each car matters
+        if checked_vehicle_ids.contains(&vehicle.id) {
+            continue
+        }
+        checked_vehicle_ids.push(vehicle.id);
+
+        let level = vehicle.tank_level_percentage;
+        if level >= min_pickup_charge && alert.is_none() {
+            continue
+        }
+        let mut alert = alert.unwrap_or(model::LowChargeAlert {
+            agreement_id: agreement.id,
+            vehicle_id: vehicle.id,
+            minimum_level: min_pickup_charge,
+            level,
+            detected_at: now,
+            current_agreement_id: None,
+            renter_notified_at: None,
+            staff_notified_at: None,
+            handed_off_at: None,
+            handoff_level: None,
+        });
+        alert.level = level;
+        alert.minimum_level = min_pickup_charge;
+
+        if level < min_pickup_charge {
+            let local_time = match timezone.parse::<chrono_tz::Tz>() {
+                Ok(tz) => agreement.rsvp_pickup_time.with_timezone(&tz).format("%b %-d at %-I:%M %p %Z").to_string(),
+                Err(_) => agreement.rsvp_pickup_time.format("%b %-d at %H:%M UTC").to_string(),
+            };
+
+            let current_trip = ag_q::agreements
+                .inner_join(r_q::renters)
+                .filter(ag_q::vehicle_id.eq(vehicle.id))
+                .filter(ag_q::status.eq(model::AgreementStatus::Rental))
+                .filter(ag_q::actual_pickup_time.is_not_null())
+                .filter(ag_q::actual_drop_off_time.is_null())
+                .select((ag_q::id, r_q::apple_apns))
+                .first::<(i32, Option<String>)>(&mut pool)
+                .optional();
+            let Ok(current_trip) = current_trip else {
+                eprintln!("low charge: database error loading current trip on vehicle #{}", vehicle.id);
+                continue
+            };
+
+            match current_trip {
+                Some((current_agreement_id, apple_apns)) => {
+                    if alert.renter_notified_at.is_none() {
+                        if let Some(renter_app_apns) = &apple_apns {
+                            let message = format!(
+                                "Please return your {} with at least {}% charge, it is at {}%. The next renter picks it up on {}. ",
+                                vehicle.name, min_pickup_charge, level, local_time
+                            );
+                            let _ = integration::apns_veygo::send_notification(
+                                renter_app_apns, "Please Charge Before Return", &message, false
+                            ).await;
+                        }
+                        alert.current_agreement_id = Some(current_agreement_id);
+                        alert.renter_notified_at = Some(now);
+                    }
+                }
+                None => {
+                    if alert.staff_notified_at.is_none() {
+                        let message = format!(
+                            "{} ({}) is at {}%, below the {}% needed for RSVP #{} on {}. Please charge it before pickup. ",
+                            vehicle.name, vehicle.license_number, level, min_pickup_charge, agreement.confirmation, local_time
+                        );
+                        if methods::user::notify_apartment_staff(apartment_id, "Low Charge Before Pickup", &message).await.is_err() {
+                            eprintln!("low charge: database error loading staff for apartment {}", apartment_id);
+                            continue
+                        }
+                        alert.staff_notified_at = Some(now);
+                    }
+                }
+            }
+        }
+
+        let result = diesel::insert_into(lca_q::low_charge_alerts)
+            .values(&alert)
+            .on_conflict(lca_q::agreement_id)
+            .do_update()
+            .set(&alert)
+            .execute(&mut pool);
+        if result.is_err() {
+            eprintln!("low charge: DB error saving alert for RSVP #{}", agreement.confirmation);
+        }
+    }
+
+    // keep what the car actually had at handoff, for low charge disputes
+    let handed_off = lca_q::low_charge_alerts
+        .inner_join(ag_q::agreements)
+        .filter(lca_q::handed_off_at.is_null())
+        .filter(ag_q::actual_pickup_time.is_not_null())
+        .select((lca_q::low_charge_alerts::all_columns(), ag_q::actual_pickup_time, ag_q::vehicle_snapshot_before))
+        .get_results::<(model::LowChargeAlert, Option<chrono::DateTime<Utc>>, Option<i32>)>(&mut pool);
+    let Ok(handed_off) = handed_off else {
+        eprintln!("low charge: database error loading handed off agreements");
+        return
+    };
+    for (mut alert, actual_pickup_time, vehicle_snapshot_before) in handed_off {
+        use crate::schema::vehicle_snapshots::dsl as vs_q;
+        let handoff_level = match vehicle_snapshot_before {
+            Some(snapshot_id) => vs_q::vehicle_snapshots
+                .find(snapshot_id)
+                .select(vs_q::level)
+                .get_result::<i32>(&mut pool)
+                .ok(),
+            None => None,
+        };
+        alert.handed_off_at = actual_pickup_time;
+        alert.handoff_level = handoff_level;
+        let result = diesel::update(lca_q::low_charge_alerts.find(alert.agreement_id))
+            .set(&alert)
+            .execute(&mut pool);
+        if result.is_err() {
+            eprintln!("low charge: DB error recording handoff for agreement #{}", alert.agreement_id);
+        }
+    }
+}
@@ -29,6 +29,14 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "policy_enum"))]
     pub struct PolicyEnum;
 
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "remote_command_enum"))]
+    pub struct RemoteCommandEnum;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "remote_command_status_enum"))]
+    pub struct RemoteCommandStatusEnum;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "remote_mgmt_enum"))]
     pub struct RemoteMgmtEnum;
@@ -110,6 +118,8 @@ diesel::table! {
         date_of_creation -> Timestamptz,
         minimum_earning_rate -> Numeric,
         deposit_pmt_id -> Nullable<Int4>,
+        #[max_length = 255]
+        void_reason -> Nullable<Varchar>,
     }
 }
 
@@ -167,6 +177,7 @@ diesel::table! {
         longitude_lower_bound -> Float8,
         latitude_higher_bound -> Float8,
         longitude_higher_bound -> Float8,
+        min_pickup_charge -> Int4,
     }
 }
 
@@ -190,6 +201,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    cancellation_fees (apartment_id, hours_before) {
+        apartment_id -> Int4,
+        hours_before -> Int4,
+        fee -> Numeric,
+    }
+}
+
 diesel::table! {
     charges (id) {
         id -> Int4,
@@ -203,6 +222,8 @@ diesel::table! {
         #[max_length = 26]
         vehicle_identifier -> Nullable<Varchar>,
         is_taxed -> Bool,
+        billing_attempts -> Int4,
+        flagged_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -283,6 +304,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    low_charge_alerts (agreement_id) {
+        agreement_id -> Int4,
+        vehicle_id -> Int4,
+        minimum_level -> Int4,
+        level -> Int4,
+        detected_at -> Timestamptz,
+        current_agreement_id -> Nullable<Int4>,
+        renter_notified_at -> Nullable<Timestamptz>,
+        staff_notified_at -> Nullable<Timestamptz>,
+        handed_off_at -> Nullable<Timestamptz>,
+        handoff_level -> Nullable<Int4>,
+    }
+}
+
 diesel::table! {
     mileage_packages (id) {
         id -> Int4,
@@ -292,6 +328,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    overdue_alerts (agreement_id) {
+        agreement_id -> Int4,
+        renter_notified_at -> Nullable<Timestamptz>,
+        staff_notified_at -> Nullable<Timestamptz>,
+        conflicting_agreement_id -> Nullable<Int4>,
+    }
+}
+
 diesel::table! {
     payment_methods (id) {
         id -> Int4,
@@ -375,6 +420,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::RemoteCommandEnum;
+    use super::sql_types::RemoteCommandStatusEnum;
+
+    remote_commands (id) {
+        id -> Int4,
+        vehicle_id -> Int4,
+        agreement_id -> Nullable<Int4>,
+        triggered_by -> Nullable<Int4>,
+        command -> RemoteCommandEnum,
+        attempt -> Int4,
+        status -> RemoteCommandStatusEnum,
+        detail -> Nullable<Text>,
+        latency_ms -> Int4,
+        sent_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::GenderEnum;
@@ -441,6 +505,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sent_reminders (agreement_id, reminder) {
+        agreement_id -> Int4,
+        #[max_length = 16]
+        reminder -> Varchar,
+        sent_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     services (id) {
         id -> Int4,
@@ -508,6 +581,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    vehicle_blackouts (id) {
+        id -> Int4,
+        vehicle_id -> Int4,
+        start_time -> Timestamptz,
+        end_time -> Timestamptz,
+        #[max_length = 255]
+        reason -> Varchar,
+        created_by -> Int4,
+    }
+}
+
 diesel::table! {
     vehicle_snapshots (id) {
         id -> Int4,
@@ -528,6 +613,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    vehicle_telemetry (id) {
+        id -> Int4,
+        vehicle_id -> Int4,
+        recorded_at -> Timestamptz,
+        battery_level -> Int4,
+        odometer -> Int4,
+        latitude -> Float8,
+        longitude -> Float8,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::RemoteMgmtEnum;
@@ -580,6 +677,7 @@ diesel::table! {
         requires_own_insurance -> Bool,
         #[max_length = 4]
         admin_pin -> Nullable<Varchar>,
+        maintenance_hold -> Bool,
     }
 }
 
@@ -620,6 +718,7 @@ diesel::joinable!(agreements_taxes -> taxes (tax_id));
 diesel::joinable!(apartments_taxes -> apartments (apartment_id));
 diesel::joinable!(apartments_taxes -> taxes (tax_id));
 diesel::joinable!(audits -> renters (renter_id));
+diesel::joinable!(cancellation_fees -> apartments (apartment_id));
 diesel::joinable!(charges -> agreements (agreement_id));
 diesel::joinable!(charges -> transponder_companies (transponder_company_id));
 diesel::joinable!(charges -> vehicles (vehicle_id));
@@ -629,20 +728,30 @@ diesel::joinable!(damage_submissions -> renters (processed_by));
 diesel::joinable!(damages -> claims (claim_id));
 diesel::joinable!(damages -> vehicles (vehicle_id));
 diesel::joinable!(locations -> apartments (apartment_id));
+diesel::joinable!(low_charge_alerts -> agreements (agreement_id));
+diesel::joinable!(low_charge_alerts -> vehicles (vehicle_id));
+diesel::joinable!(overdue_alerts -> agreements (agreement_id));
 diesel::joinable!(payment_methods -> renters (renter_id));
 diesel::joinable!(payments -> payment_methods (payment_method_id));
 diesel::joinable!(payments -> renters (renter_id));
 diesel::joinable!(promos -> renters (user_id));
 diesel::joinable!(rate_offers -> apartments (apartment_id));
 diesel::joinable!(rate_offers -> renters (renter_id));
+diesel::joinable!(remote_commands -> agreements (agreement_id));
+diesel::joinable!(remote_commands -> renters (triggered_by));
+diesel::joinable!(remote_commands -> vehicles (vehicle_id));
 diesel::joinable!(renters -> apartments (apartment_id));
 diesel::joinable!(reward_transactions -> agreements (agreement_id));
 diesel::joinable!(reward_transactions -> renters (renter_id));
+diesel::joinable!(sent_reminders -> agreements (agreement_id));
 diesel::joinable!(subscription_payments -> apartments (apartment_id));
 diesel::joinable!(subscription_payments -> payment_methods (payment_method_id));
 diesel::joinable!(subscription_payments -> renters (renter_id));
+diesel::joinable!(vehicle_blackouts -> renters (created_by));
+diesel::joinable!(vehicle_blackouts -> vehicles (vehicle_id));
 diesel::joinable!(vehicle_snapshots -> renters (renter_id));
 diesel::joinable!(vehicle_snapshots -> vehicles (vehicle_id));
+diesel::joinable!(vehicle_telemetry -> vehicles (vehicle_id));
 diesel::joinable!(vehicles -> locations (location_id));
 diesel::joinable!(vehicles_services -> services (service_id));
 diesel::joinable!(vehicles_services -> vehicles (vehicle_id));
@@ -656,25 +765,32 @@ diesel::allow_tables_to_appear_in_same_query!(
     apartments,
     apartments_taxes,
     audits,
+    cancellation_fees,
     charges,
     claims,
     damage_submissions,
     damages,
     do_not_rent_lists,
     locations,
+    low_charge_alerts,
     mileage_packages,
+    overdue_alerts,
     payment_methods,
     payments,
     policies,
     promos,
     rate_offers,
+    remote_commands,
     renters,
     reward_transactions,
+    sent_reminders,
     services,
     subscription_payments,
     taxes,
     transponder_companies,
+    vehicle_blackouts,
     vehicle_snapshots,
+    vehicle_telemetry,
     vehicles,
     vehicles_services,
     verifications,